@@ -0,0 +1,214 @@
+//! An offline stand-in for the TVmaze and trakt apis used by the tests
+//!
+//! The server answers requests with the json fixtures in `tests/fixtures/mock-server` so that
+//! the caching, schedule and trakt conversion paths can be exercised without network access by
+//! pointing the api base urls to it. Every request is recorded for the tests to check what was
+//! sent to the apis.
+//!
+//! A request is answered with the fixture found at it's path with a `.json` extension, with
+//! the query of the request as a last path component i.e. `/lookup/shows?imdb=tt1553656` is
+//! answered with `lookup/shows/imdb=tt1553656.json`. When there is no fixture for the query,
+//! the fixture of the path without the query is used instead.
+//!
+//! `GET` requests without a fixture are answered with `404` and a `null` body while `POST`
+//! requests without a fixture are answered with `201` and an empty json object.
+
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{info, warn};
+
+/// The directory of the fixtures bundled with the source code
+pub fn get_fixtures_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/mock-server")
+}
+
+/// A request answered by the server
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: String,
+    /// The path of the request together with it's query
+    pub target: String,
+    pub body: String,
+}
+
+pub type RequestLog = Arc<Mutex<Vec<RecordedRequest>>>;
+
+pub struct MockServer {
+    listener: TcpListener,
+    fixtures_dir: Arc<PathBuf>,
+    request_log: RequestLog,
+}
+
+impl MockServer {
+    /// Binds the server to the given address, port `0` picks any free port
+    pub async fn bind(fixtures_dir: PathBuf, address: SocketAddr) -> io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(address).await?,
+            fixtures_dir: Arc::new(fixtures_dir),
+            request_log: RequestLog::default(),
+        })
+    }
+
+    /// The requests answered by the server so far
+    pub fn get_request_log(&self) -> RequestLog {
+        self.request_log.clone()
+    }
+
+    /// The base url to point the apis to i.e. `http://127.0.0.1:8080`
+    pub fn base_url(&self) -> io::Result<String> {
+        Ok(format!("http://{}", self.listener.local_addr()?))
+    }
+
+    /// Answers requests until an error occurs while accepting connections
+    pub async fn run(self) -> io::Result<()> {
+        info!("mock server listening at {}", self.base_url()?);
+
+        loop {
+            let (stream, peer_address) = self.listener.accept().await?;
+            let fixtures_dir = self.fixtures_dir.clone();
+            let request_log = self.request_log.clone();
+
+            tokio::spawn(async move {
+                if let Err(err) = handle_connection(stream, &fixtures_dir, &request_log).await {
+                    warn!("mock server failed to answer {}: {}", peer_address, err);
+                }
+            });
+        }
+    }
+}
+
+async fn handle_connection(
+    stream: TcpStream,
+    fixtures_dir: &Path,
+    request_log: &RequestLog,
+) -> io::Result<()> {
+    let mut stream = BufReader::new(stream);
+
+    let mut request_line = String::new();
+    stream.read_line(&mut request_line).await?;
+
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        if stream.read_line(&mut header).await? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or_default();
+            }
+        }
+    }
+
+    let mut body = vec![0; content_length];
+    stream.read_exact(&mut body).await?;
+
+    let mut request_line = request_line.split_whitespace();
+    let method = request_line.next().unwrap_or_default();
+    let target = request_line.next().unwrap_or_default();
+
+    request_log
+        .lock()
+        .expect("failed to lock the request log")
+        .push(RecordedRequest {
+            method: method.to_owned(),
+            target: target.to_owned(),
+            body: String::from_utf8_lossy(&body).into_owned(),
+        });
+
+    let (status, body) = match find_fixture(fixtures_dir, target) {
+        Some(fixture_path) => match tokio::fs::read_to_string(&fixture_path).await {
+            Ok(fixture) => ("200 OK", fixture),
+            Err(err) => {
+                warn!(
+                    "failed to read fixture '{}': {}",
+                    fixture_path.display(),
+                    err
+                );
+                ("500 Internal Server Error", "null".to_owned())
+            }
+        },
+        None if method == "POST" => ("201 Created", "{}".to_owned()),
+        None => ("404 Not Found", "null".to_owned()),
+    };
+
+    info!("mock server: {} {} -> {}", method, target, status);
+
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+
+    let stream = stream.get_mut();
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Finds the fixture for the given request target
+fn find_fixture(fixtures_dir: &Path, target: &str) -> Option<PathBuf> {
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path, Some(percent_decode(query))),
+        None => (target, None),
+    };
+
+    // Refusing anything that would lead outside the fixtures directory
+    if path.split('/').any(|component| component == "..")
+        || query
+            .as_ref()
+            .map(|query| query.contains('/') || query.contains('\\'))
+            .unwrap_or(false)
+    {
+        return None;
+    }
+
+    let path = path.trim_matches('/');
+    if path.is_empty() {
+        return None;
+    }
+
+    let fixture_path = fixtures_dir.join(path);
+
+    let mut path_fixture_path = fixture_path.clone().into_os_string();
+    path_fixture_path.push(".json");
+
+    query
+        .map(|query| fixture_path.join(format!("{}.json", query)))
+        .into_iter()
+        .chain(std::iter::once(PathBuf::from(path_fixture_path)))
+        .find(|fixture_path| fixture_path.is_file())
+}
+
+/// Decodes the percent encoded characters of a url query
+fn percent_decode(query: &str) -> String {
+    let bytes = query.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'%' => {
+                let byte = query
+                    .get(index + 1..index + 3)
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                if let Some(byte) = byte {
+                    decoded.push(byte);
+                    index += 3;
+                    continue;
+                }
+                decoded.push(b'%');
+            }
+            b'+' => decoded.push(b' '),
+            byte => decoded.push(byte),
+        }
+        index += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
@@ -1,3 +1,5 @@
 pub mod crates;
+#[cfg(test)]
+pub mod mock_server;
 pub mod trakt;
 pub mod tv_maze;
@@ -1,5 +1,15 @@
 use thiserror::Error;
 
+use crate::core::settings_config;
+
+pub mod sync;
+
+/// The default base url of the trakt api
+const DEFAULT_BASE_URL: &str = "https://api.trakt.tv";
+/// Environment variable overriding the base url of the trakt api i.e. when testing against
+/// a mock server
+pub const BASE_URL_ENVIRONMENT_VARIABLE: &str = "TRAKT_BASE_URL";
+
 pub mod import_shows {
     //! Import user shows from Trakt api
     use std::mem::ManuallyDrop;
@@ -15,7 +25,7 @@ pub mod import_shows {
     use crate::core::database::Series;
     use reqwest::header::HeaderValue;
 
-    const USER_WATCHED_SHOWS_ADDRESS: &str = "https://api.trakt.tv/users/SLUG/watched/shows";
+    const USER_WATCHED_SHOWS_PATH: &str = "/users/SLUG/watched/shows";
 
     #[derive(Debug, Error)]
     pub enum ImportError {
@@ -35,7 +45,7 @@ pub mod import_shows {
         headers.insert("trakt-api-version", HeaderValue::from_static("2"));
         headers.insert("trakt-api-key", HeaderValue::from_static(client_id));
 
-        let url = USER_WATCHED_SHOWS_ADDRESS.replace("SLUG", slug);
+        let url = super::get_api_url(&USER_WATCHED_SHOWS_PATH.replace("SLUG", slug));
 
         let pretty_json_str =
             super::get_pretty_json_from_url(url, headers, TraktStatusCode::Success).await?;
@@ -57,11 +67,15 @@ pub mod import_shows {
     }
 }
 
-mod convert {
+pub mod convert {
     //! Convert Trakt Shows to SeriesTroxides' database Shows
+    //!
+    //! The conversion works with any show exposing it's external ids and watched episodes
+    //! through `ExternalShow` so that imports from other trackers can reuse it.
 
     use std::mem::ManuallyDrop;
 
+    use chrono::{DateTime, Utc};
     use tokio::sync::mpsc;
 
     use super::trakt_data::TraktShow;
@@ -70,22 +84,64 @@ mod convert {
     use crate::core::caching::series_information::cache_series_information;
     use crate::core::database::Series;
 
-    async fn trakt_show_to_troxide(
-        trakt_show: &TraktShow,
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct WatchedEpisode {
+        pub season: u32,
+        pub episode: u32,
+        /// `None` when the tracker does not know when the episode was watched
+        pub watched_at: Option<DateTime<Utc>>,
+    }
+
+    /// A show of an external tracker that can be looked up on TVmaze
+    pub trait ExternalShow {
+        fn get_imdb_id(&self) -> Option<String>;
+
+        fn get_tvdb_id(&self) -> Option<u32>;
+
+        fn get_watched_episodes(&self) -> Vec<WatchedEpisode>;
+    }
+
+    impl ExternalShow for TraktShow {
+        fn get_imdb_id(&self) -> Option<String> {
+            Some(self.show.ids.imdb.clone())
+        }
+
+        fn get_tvdb_id(&self) -> Option<u32> {
+            Some(self.show.ids.tvdb)
+        }
+
+        fn get_watched_episodes(&self) -> Vec<WatchedEpisode> {
+            self.seasons
+                .iter()
+                .flat_map(|season| {
+                    season.episodes.iter().map(|episode| WatchedEpisode {
+                        season: season.number,
+                        episode: episode.number,
+                        watched_at: episode.last_watched_at,
+                    })
+                })
+                .collect()
+        }
+    }
+
+    async fn external_show_to_troxide(
+        external_show: &impl ExternalShow,
     ) -> Result<Option<(u32, ManuallyDrop<Series>)>, TvMazeApiError> {
-        let imdb_id = Id::Imdb(trakt_show.show.ids.imdb.clone());
+        let mut tvmaze_series_info = None;
 
-        let tvmaze_series_info = if let Some(tvmaze_series_info) = show_lookup(imdb_id).await? {
-            tvmaze_series_info
-        } else {
-            // Falling back to the tvdb id when imdb id fails
-            let tvdb_id = Id::Tvdb(trakt_show.show.ids.tvdb);
+        if let Some(imdb_id) = external_show.get_imdb_id() {
+            tvmaze_series_info = show_lookup(Id::Imdb(imdb_id)).await?;
+        }
 
-            if let Some(tvmaze_series_info) = show_lookup(tvdb_id).await? {
-                tvmaze_series_info
-            } else {
-                return Ok(None);
+        // Falling back to the tvdb id when imdb id fails
+        if tvmaze_series_info.is_none() {
+            if let Some(tvdb_id) = external_show.get_tvdb_id() {
+                tvmaze_series_info = show_lookup(Id::Tvdb(tvdb_id)).await?;
             }
+        }
+
+        let Some(tvmaze_series_info) = tvmaze_series_info else {
+            return Ok(None);
         };
 
         let tvmaze_series_id = tvmaze_series_info.id;
@@ -98,12 +154,21 @@ mod convert {
         let mut troxide_db_series =
             ManuallyDrop::new(Series::new(tvmaze_series_info.name, tvmaze_series_id));
 
-        trakt_show.seasons.iter().for_each(|season| {
-            let season_number = season.number;
-            season.episodes.iter().for_each(|episode| {
-                troxide_db_series.add_episode_unchecked(season_number, episode.number)
-            })
-        });
+        external_show
+            .get_watched_episodes()
+            .into_iter()
+            .for_each(|watched_episode| {
+                if let Some(watched_at) = watched_episode.watched_at {
+                    troxide_db_series.add_episode_unchecked_with_time(
+                        watched_episode.season,
+                        watched_episode.episode,
+                        watched_at,
+                    )
+                } else {
+                    troxide_db_series
+                        .add_episode_unchecked(watched_episode.season, watched_episode.episode)
+                }
+            });
 
         Ok(Some((tvmaze_series_id, troxide_db_series)))
     }
@@ -123,21 +188,34 @@ mod convert {
         trakt_shows: Vec<TraktShow>,
         progress_sender: mpsc::Sender<ProgressData>,
     ) -> Result<(Vec<(u32, ManuallyDrop<Series>)>, Vec<TraktShow>), TvMazeApiError> {
+        convert_external_shows_to_troxide(trakt_shows, progress_sender).await
+    }
+
+    /// Converts the shows of any external tracker to Series Troxide's database `Series`
+    ///
+    /// Since Conversion might fail, failed shows will be returned too
+    pub async fn convert_external_shows_to_troxide<S>(
+        external_shows: Vec<S>,
+        progress_sender: mpsc::Sender<ProgressData>,
+    ) -> Result<(Vec<(u32, ManuallyDrop<Series>)>, Vec<S>), TvMazeApiError>
+    where
+        S: ExternalShow + Clone + Send + Sync + 'static,
+    {
         progress_sender
-            .send(ProgressData::TotalImport(trakt_shows.len()))
+            .send(ProgressData::TotalImport(external_shows.len()))
             .await
             .expect("failed to send progress");
 
-        let mut ids_and_series = Vec::with_capacity(trakt_shows.len());
-        let mut failed = Vec::with_capacity(trakt_shows.len() / 2);
+        let mut ids_and_series = Vec::with_capacity(external_shows.len());
+        let mut failed = Vec::with_capacity(external_shows.len() / 2);
 
-        let handles: Vec<_> = trakt_shows
+        let handles: Vec<_> = external_shows
             .iter()
             .cloned()
             .map(|show| {
                 let progress_sender = progress_sender.clone();
                 tokio::spawn(async move {
-                    let res = trakt_show_to_troxide(&show).await;
+                    let res = external_show_to_troxide(&show).await;
                     if let Err(err) = progress_sender.send(ProgressData::Progressing).await {
                         tracing::warn!("failed to send import progress as: {}", err);
                     };
@@ -146,7 +224,7 @@ mod convert {
             })
             .collect();
 
-        for (show, handle) in trakt_shows.into_iter().zip(handles.into_iter()) {
+        for (show, handle) in external_shows.into_iter().zip(handles) {
             if let Some(id_and_series) = handle.await.expect("failed to join all the handles")? {
                 ids_and_series.push(id_and_series);
             } else {
@@ -162,11 +240,11 @@ pub mod user_settings {
     //! Get user settings for a user's trakt account
 
     use super::trakt_data::TraktStatusCode;
-    use super::{deserialize_json, get_pretty_json_from_url, ApiError};
+    use super::{deserialize_json, get_api_url, get_pretty_json_from_url, ApiError};
     use reqwest::header::HeaderValue;
     use serde::Deserialize;
 
-    const USER_SETTINGS_ADDRESS: &str = "https://api.trakt.tv/users/settings";
+    const USER_SETTINGS_PATH: &str = "/users/settings";
 
     #[derive(Debug, Deserialize, Clone)]
     pub struct UserSettings {
@@ -199,7 +277,7 @@ pub mod user_settings {
         headers.insert("trakt-api-key", HeaderValue::from_static(client_id));
 
         let pretty_json_str = get_pretty_json_from_url(
-            USER_SETTINGS_ADDRESS.to_owned(),
+            get_api_url(USER_SETTINGS_PATH),
             headers,
             TraktStatusCode::Success,
         )
@@ -432,7 +510,7 @@ pub mod trakt_data {
     #[derive(Deserialize, Debug, Clone)]
     pub struct Episode {
         pub number: u32,
-        // last_watched_at: String,
+        pub last_watched_at: Option<chrono::DateTime<chrono::Utc>>,
     }
 
     /// StatusCodes returned by the Trakt api
@@ -563,15 +641,15 @@ pub mod trakt_data {
 pub mod authentication {
     //! Authenticate the program to access user's trakt account
 
-    use super::{trakt_data::TraktStatusCode, ApiError};
+    use super::{get_api_url, trakt_data::TraktStatusCode, ApiError};
     use reqwest::header::HeaderValue;
     use serde::{Deserialize, Serialize};
     use tokio::sync::mpsc;
 
-    /// The url to retrieve device code
-    const DEVICE_CODE_URL: &str = "https://api.trakt.tv/oauth/device/code";
-    /// The url to retrieve user account token
-    const TOKEN_URL: &str = "https://api.trakt.tv/oauth/device/token";
+    /// The path to retrieve device code
+    const DEVICE_CODE_PATH: &str = "/oauth/device/code";
+    /// The path to retrieve user account token
+    const TOKEN_PATH: &str = "/oauth/device/token";
 
     /// Request body for retrieving `CodeResponse`
     #[derive(Serialize)]
@@ -648,7 +726,7 @@ pub mod authentication {
                 .expect("token reqwest body should be serializable");
 
             let response = client
-                .post(TOKEN_URL)
+                .post(get_api_url(TOKEN_PATH))
                 .headers(headers)
                 .body(json_body)
                 .send()
@@ -693,7 +771,7 @@ pub mod authentication {
 
         let client = reqwest::Client::new();
         let response = client
-            .post(DEVICE_CODE_URL)
+            .post(get_api_url(DEVICE_CODE_PATH))
             .headers(headers)
             .body(json_body)
             .send()
@@ -721,6 +799,31 @@ pub enum ApiError {
     Deserialization(String, serde_json::Error),
 }
 
+/// Returns the base url of the trakt api
+///
+/// The `TRAKT_BASE_URL` environment variable takes priority over the base url set
+/// in the settings.
+pub fn get_base_url() -> String {
+    std::env::var(BASE_URL_ENVIRONMENT_VARIABLE)
+        .ok()
+        .or_else(|| {
+            settings_config::SETTINGS
+                .read()
+                .ok()?
+                .get_current_settings()
+                .api
+                .trakt_base_url
+                .clone()
+        })
+        .map(|base_url| base_url.trim_end_matches('/').to_owned())
+        .unwrap_or_else(|| DEFAULT_BASE_URL.to_owned())
+}
+
+/// Returns the full url of the given trakt api path
+fn get_api_url(path: &str) -> String {
+    format!("{}{}", get_base_url(), path)
+}
+
 pub fn deserialize_json<'a, T: serde::Deserialize<'a>>(
     prettified_json: &'a str,
 ) -> Result<T, ApiError> {
@@ -760,3 +863,45 @@ pub async fn get_pretty_json_from_url(
         1,
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc;
+
+    use super::import_shows;
+    use crate::core::testing;
+
+    #[tokio::test]
+    async fn trakt_watched_shows_are_converted_to_series() {
+        testing::environment();
+
+        let (progress_sender, mut progress_receiver) = mpsc::channel(10);
+        tokio::spawn(async move { while progress_receiver.recv().await.is_some() {} });
+
+        let (ids_and_series, failed) =
+            import_shows::import("mock-user", "mock-client-id", progress_sender)
+                .await
+                .expect("failed to import the trakt shows");
+
+        assert!(failed.is_empty());
+        assert_eq!(ids_and_series.len(), 1);
+
+        let (series_id, series) = &ids_and_series[0];
+        assert_eq!(*series_id, 1);
+        assert_eq!(series.get_name(), "Under the Dome");
+
+        let first_season = series.get_season(1).expect("season 1 should be watched");
+        assert!(first_season.is_episode_watched(1));
+        assert!(first_season.is_episode_watched(2));
+        assert_eq!(
+            first_season
+                .get_episode_watch(1)
+                .and_then(|episode_watch| episode_watch.get_watched_at()),
+            Some("2013-06-25T03:00:00Z".parse().unwrap())
+        );
+        assert!(series
+            .get_season(2)
+            .map(|season| season.is_episode_watched(1))
+            .unwrap_or(false));
+    }
+}
@@ -0,0 +1,612 @@
+//! Two-way sync of watched episodes with a user's trakt history
+//!
+//! A sync first pulls the episodes added to the user's trakt history since the last sync
+//! and then pushes the episodes watched and unwatched locally since the last sync.
+//!
+//! Conflicts between an episode watched on trakt and the same episode unwatched locally
+//! are resolved by keeping whichever happened last. Trakt history does not expose when
+//! episodes are removed from it, hence remote unwatches are not pulled.
+
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Utc};
+use reqwest::header::{HeaderMap, HeaderValue};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::fs;
+use tracing::{info, warn};
+
+use super::trakt_data::TraktStatusCode;
+use super::{deserialize_json, get_api_url, ApiError};
+use crate::core::api::tv_maze::show_lookup::{show_lookup, Id};
+use crate::core::api::tv_maze::ApiError as TvMazeApiError;
+use crate::core::caching::series_information::{
+    cache_series_information, get_series_main_info_with_id,
+};
+use crate::core::database::{DatabaseError, Episode, Series, DB};
+use crate::core::{paths, profiles};
+
+const HISTORY_PATH: &str = "/sync/history";
+const HISTORY_REMOVE_PATH: &str = "/sync/history/remove";
+const SHOWS_HISTORY_PATH: &str = "/sync/history/shows";
+/// The maximum amount of history items trakt returns per page
+const HISTORY_PAGE_LIMIT: u32 = 100;
+const SYNC_STATE_FILENAME: &str = "trakt-sync-state";
+
+#[derive(Debug, Error)]
+pub enum SyncError {
+    #[error("trakt api error: {0}")]
+    TraktApi(ApiError),
+
+    #[error("tvmaze api error: {0}")]
+    TvMazeApi(TvMazeApiError),
+
+    #[error("database error: {0}")]
+    Database(DatabaseError),
+
+    #[error("filesystem error '{0}'")]
+    Io(std::io::Error),
+
+    #[error("sync state filepath could not be determined")]
+    UndeterminedSyncStateFilepath,
+}
+
+/// The outcome of a sync
+#[derive(Debug, Default, Clone)]
+pub struct SyncReport {
+    /// Episodes marked watched locally from the trakt history
+    pub pulled_episodes: usize,
+    /// Episodes added to the trakt history
+    pub pushed_episodes: usize,
+    /// Episodes removed from the trakt history
+    pub removed_episodes: usize,
+    /// Titles of trakt shows that could not be found on TVmaze
+    pub unmatched_shows: Vec<String>,
+}
+
+/// The sync state persisted between syncs
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SyncState {
+    /// The time the last successful sync started
+    last_sync: Option<DateTime<Utc>>,
+    /// Trakt show ids mapped to their TVmaze series ids
+    show_ids: HashMap<u32, u32>,
+}
+
+impl SyncState {
+    async fn load() -> Result<Self, SyncError> {
+        let sync_state_filepath =
+            Self::sync_state_filepath().ok_or(SyncError::UndeterminedSyncStateFilepath)?;
+
+        match fs::read_to_string(&sync_state_filepath).await {
+            Ok(file_content) => Ok(serde_json::from_str(&file_content).unwrap_or_else(|err| {
+                warn!("discarding invalid trakt sync state: {}", err);
+                Self::default()
+            })),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(SyncError::Io(err)),
+        }
+    }
+
+    async fn save(&self) -> Result<(), SyncError> {
+        let sync_state_filepath =
+            Self::sync_state_filepath().ok_or(SyncError::UndeterminedSyncStateFilepath)?;
+        fs::write(
+            sync_state_filepath,
+            serde_json::to_string_pretty(self).expect("sync state should be serializable"),
+        )
+        .await
+        .map_err(SyncError::Io)
+    }
+
+    fn sync_state_filepath() -> Option<std::path::PathBuf> {
+        paths::PATHS
+            .read()
+            .map(|paths| {
+                paths
+                    .get_data_dir_path()
+                    .join(profiles::get_profile_storage_name(
+                        SYNC_STATE_FILENAME,
+                        &profiles::get_active_profile(),
+                    ))
+            })
+            .ok()
+    }
+}
+
+/// An item of the user's trakt history
+#[derive(Debug, Deserialize)]
+struct HistoryItem {
+    watched_at: DateTime<Utc>,
+    episode: HistoryEpisode,
+    show: HistoryShow,
+}
+
+#[derive(Debug, Deserialize)]
+struct HistoryEpisode {
+    season: u32,
+    number: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct HistoryShow {
+    title: String,
+    ids: ShowIds,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct ShowIds {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trakt: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    imdb: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tvdb: Option<u32>,
+}
+
+/// Request body of the trakt history endpoints
+#[derive(Debug, Default, Serialize)]
+struct HistoryRequestBody {
+    shows: Vec<HistoryRequestShow>,
+}
+
+#[derive(Debug, Serialize)]
+struct HistoryRequestShow {
+    ids: ShowIds,
+    seasons: Vec<HistoryRequestSeason>,
+}
+
+#[derive(Debug, Serialize)]
+struct HistoryRequestSeason {
+    number: u32,
+    episodes: Vec<HistoryRequestEpisode>,
+}
+
+#[derive(Debug, Serialize)]
+struct HistoryRequestEpisode {
+    number: u32,
+    /// Trakt uses the current time when the watch time is not provided
+    #[serde(skip_serializing_if = "Option::is_none")]
+    watched_at: Option<DateTime<Utc>>,
+}
+
+/// Syncs the watched episodes with the trakt history of the user the access token belongs to
+pub async fn sync(client_id: &str, access_token: &str) -> Result<SyncReport, SyncError> {
+    let mut sync_state = SyncState::load().await?;
+    let sync_start_time = Utc::now();
+    let headers = authorized_headers(client_id, access_token);
+
+    let mut report = SyncReport::default();
+
+    let history = fetch_history(headers.clone(), sync_state.last_sync)
+        .await
+        .map_err(SyncError::TraktApi)?;
+
+    let pulled_episodes = pull(history, &mut sync_state, &mut report).await?;
+
+    let (watched, unwatched) =
+        collect_local_changes(sync_state.last_sync, &pulled_episodes, &sync_state.show_ids).await?;
+
+    if !watched.shows.is_empty() {
+        post_history(HISTORY_PATH, headers.clone(), &watched)
+            .await
+            .map_err(SyncError::TraktApi)?;
+        report.pushed_episodes = count_episodes(&watched);
+    }
+
+    if !unwatched.shows.is_empty() {
+        post_history(HISTORY_REMOVE_PATH, headers, &unwatched)
+            .await
+            .map_err(SyncError::TraktApi)?;
+        report.removed_episodes = count_episodes(&unwatched);
+    }
+
+    sync_state.last_sync = Some(sync_start_time);
+    sync_state.save().await?;
+
+    info!(
+        "trakt sync done: {} pulled, {} pushed, {} removed",
+        report.pulled_episodes, report.pushed_episodes, report.removed_episodes
+    );
+
+    Ok(report)
+}
+
+/// Applies the trakt history items to the database
+///
+/// Returns the series id, season number and episode number of every episode whose remote
+/// watch is reflected locally so that they are not pushed back to trakt. Episodes whose local
+/// unwatch wins are left out so that the unwatch is pushed.
+async fn pull(
+    history: Vec<HistoryItem>,
+    sync_state: &mut SyncState,
+    report: &mut SyncReport,
+) -> Result<HashSet<(u32, u32, Episode)>, SyncError> {
+    let mut pulled_episodes = HashSet::new();
+    let mut unmatched_trakt_ids = HashSet::new();
+
+    for history_item in history {
+        let series_id = match find_series_id(&history_item.show, sync_state).await? {
+            Some(series_id) => series_id,
+            None => {
+                if unmatched_trakt_ids.insert(history_item.show.ids.trakt) {
+                    report.unmatched_shows.push(history_item.show.title);
+                }
+                continue;
+            }
+        };
+
+        let season_number = history_item.episode.season;
+        let episode_number = history_item.episode.number;
+
+        let mut series = match DB.get_series(series_id).map_err(SyncError::Database)? {
+            Some(series) => series,
+            None => {
+                let mut series = Series::new(history_item.show.title, series_id);
+                series.mark_tracked();
+                series
+            }
+        };
+
+        let season = series.get_season(season_number);
+
+        if season
+            .map(|season| season.is_episode_watched(episode_number))
+            .unwrap_or(false)
+        {
+            pulled_episodes.insert((series_id, season_number, episode_number));
+            continue;
+        }
+
+        // The local unwatch wins when it happened after the remote watch
+        if let Some(unwatch_time) =
+            season.and_then(|season| season.get_unwatch_time(episode_number))
+        {
+            if unwatch_time > history_item.watched_at {
+                continue;
+            }
+        }
+
+        series.add_episode_unchecked_with_time(
+            season_number,
+            episode_number,
+            history_item.watched_at,
+        );
+        pulled_episodes.insert((series_id, season_number, episode_number));
+        report.pulled_episodes += 1;
+    }
+
+    Ok(pulled_episodes)
+}
+
+/// Finds the TVmaze series id of the given trakt show
+async fn find_series_id(
+    show: &HistoryShow,
+    sync_state: &mut SyncState,
+) -> Result<Option<u32>, SyncError> {
+    if let Some(series_id) = show
+        .ids
+        .trakt
+        .and_then(|trakt_id| sync_state.show_ids.get(&trakt_id))
+    {
+        return Ok(Some(*series_id));
+    }
+
+    let mut series_info = None;
+
+    if let Some(imdb_id) = show.ids.imdb.clone() {
+        series_info = show_lookup(Id::Imdb(imdb_id))
+            .await
+            .map_err(SyncError::TvMazeApi)?;
+    }
+
+    // Falling back to the tvdb id when imdb id fails
+    if series_info.is_none() {
+        if let Some(tvdb_id) = show.ids.tvdb {
+            series_info = show_lookup(Id::Tvdb(tvdb_id))
+                .await
+                .map_err(SyncError::TvMazeApi)?;
+        }
+    }
+
+    let Some(series_info) = series_info else {
+        return Ok(None);
+    };
+
+    // Caching the series information
+    let series_info_str = serde_json::to_string_pretty(&series_info)
+        .expect("SeriesMainInformation should be seriealizable");
+    cache_series_information(series_info.id, &series_info_str).await;
+
+    if let Some(trakt_id) = show.ids.trakt {
+        sync_state.show_ids.insert(trakt_id, series_info.id);
+    }
+
+    Ok(Some(series_info.id))
+}
+
+/// Collects the episodes watched and unwatched locally since the last sync
+///
+/// Every watch and unwatch is collected when there is no previous sync. Episodes that
+/// have just been pulled are skipped.
+async fn collect_local_changes(
+    last_sync: Option<DateTime<Utc>>,
+    pulled_episodes: &HashSet<(u32, u32, Episode)>,
+    show_ids: &HashMap<u32, u32>,
+) -> Result<(HistoryRequestBody, HistoryRequestBody), SyncError> {
+    let is_since_last_sync = |time: Option<DateTime<Utc>>| match (last_sync, time) {
+        (None, _) => true,
+        (Some(last_sync), Some(time)) => time > last_sync,
+        (Some(_), None) => false,
+    };
+
+    let mut watched = HistoryRequestBody::default();
+    let mut unwatched = HistoryRequestBody::default();
+
+    for series in DB.get_series_collection().map_err(SyncError::Database)? {
+        // Preventing the drop implementation from rewriting the unchanged series
+        let series = std::mem::ManuallyDrop::new(series);
+        let series_id = series.id();
+
+        let mut watched_seasons = vec![];
+        let mut unwatched_seasons = vec![];
+
+        for (season_number, season) in series.get_seasons() {
+            let is_pulled =
+                |episode: &Episode| pulled_episodes.contains(&(series_id, season_number, *episode));
+
+            let watched_episodes: Vec<_> = season
+                .get_episode_watches()
+                .filter(|(episode, _)| !is_pulled(episode))
+                .flat_map(|(episode, episode_watch)| {
+                    let watch_times: Vec<_> = if episode_watch.get_watched_at().is_some() {
+                        episode_watch.watch_times().map(Some).collect()
+                    } else {
+                        // The first watch time is unknown for episodes watched before
+                        // watch times were recorded
+                        std::iter::once(None)
+                            .chain(episode_watch.watch_times().map(Some))
+                            .collect()
+                    };
+
+                    watch_times
+                        .into_iter()
+                        .filter(|watched_at| is_since_last_sync(*watched_at))
+                        .map(move |watched_at| HistoryRequestEpisode {
+                            number: episode,
+                            watched_at,
+                        })
+                })
+                .collect();
+
+            let unwatched_episodes: Vec<_> = season
+                .get_unwatched_episodes()
+                .filter(|(episode, unwatch_time)| {
+                    !is_pulled(episode) && is_since_last_sync(Some(*unwatch_time))
+                })
+                .map(|(episode, _)| HistoryRequestEpisode {
+                    number: episode,
+                    watched_at: None,
+                })
+                .collect();
+
+            if !watched_episodes.is_empty() {
+                watched_seasons.push(HistoryRequestSeason {
+                    number: season_number,
+                    episodes: watched_episodes,
+                });
+            }
+
+            if !unwatched_episodes.is_empty() {
+                unwatched_seasons.push(HistoryRequestSeason {
+                    number: season_number,
+                    episodes: unwatched_episodes,
+                });
+            }
+        }
+
+        if watched_seasons.is_empty() && unwatched_seasons.is_empty() {
+            continue;
+        }
+
+        let Some(ids) = get_show_ids(series_id, show_ids).await? else {
+            warn!(
+                "skipping trakt sync of '{}' as it has no external ids",
+                series.get_name()
+            );
+            continue;
+        };
+
+        if !watched_seasons.is_empty() {
+            watched.shows.push(HistoryRequestShow {
+                ids: ids.clone(),
+                seasons: watched_seasons,
+            });
+        }
+
+        if !unwatched_seasons.is_empty() {
+            unwatched.shows.push(HistoryRequestShow {
+                ids,
+                seasons: unwatched_seasons,
+            });
+        }
+    }
+
+    Ok((watched, unwatched))
+}
+
+/// Gets the ids trakt can identify the given series with
+async fn get_show_ids(
+    series_id: u32,
+    show_ids: &HashMap<u32, u32>,
+) -> Result<Option<ShowIds>, SyncError> {
+    let trakt = show_ids
+        .iter()
+        .find(|(_, tvmaze_id)| **tvmaze_id == series_id)
+        .map(|(trakt_id, _)| *trakt_id);
+
+    let externals = get_series_main_info_with_id(series_id)
+        .await
+        .map_err(SyncError::TvMazeApi)?
+        .externals
+        .unwrap_or_default();
+
+    if trakt.is_none() && externals.imdb.is_none() && externals.thetvdb.is_none() {
+        return Ok(None);
+    }
+
+    Ok(Some(ShowIds {
+        trakt,
+        imdb: externals.imdb,
+        tvdb: externals.thetvdb,
+    }))
+}
+
+fn count_episodes(history_request_body: &HistoryRequestBody) -> usize {
+    history_request_body
+        .shows
+        .iter()
+        .flat_map(|show| show.seasons.iter())
+        .map(|season| season.episodes.len())
+        .sum()
+}
+
+fn authorized_headers(client_id: &str, access_token: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert("Content-Type", HeaderValue::from_static("application/json"));
+    headers.insert("trakt-api-version", HeaderValue::from_static("2"));
+    headers.insert(
+        "trakt-api-key",
+        HeaderValue::from_str(client_id).expect("client id should be a valid header value"),
+    );
+    headers.insert(
+        "Authorization",
+        HeaderValue::from_str(&format!("Bearer {}", access_token))
+            .expect("access token should be a valid header value"),
+    );
+    headers
+}
+
+/// Fetches all the episodes in the user's trakt history watched after the given time
+async fn fetch_history(
+    headers: HeaderMap,
+    start_at: Option<DateTime<Utc>>,
+) -> Result<Vec<HistoryItem>, ApiError> {
+    let client = reqwest::Client::new();
+    let mut history = vec![];
+    let mut page = 1;
+
+    loop {
+        let mut query = vec![
+            ("page", page.to_string()),
+            ("limit", HISTORY_PAGE_LIMIT.to_string()),
+        ];
+        if let Some(start_at) = start_at {
+            query.push(("start_at", start_at.to_rfc3339()));
+        }
+
+        let response = client
+            .get(get_api_url(SHOWS_HISTORY_PATH))
+            .headers(headers.clone())
+            .query(&query)
+            .send()
+            .await
+            .map_err(ApiError::Network)?;
+
+        TraktStatusCode::Success.error_if_different(response.status().into())?;
+
+        let page_count = response
+            .headers()
+            .get("X-Pagination-Page-Count")
+            .and_then(|page_count| page_count.to_str().ok())
+            .and_then(|page_count| page_count.parse::<u32>().ok())
+            .unwrap_or(1);
+
+        let text = response.text().await.map_err(ApiError::Network)?;
+        let pretty_json_str =
+            json::stringify_pretty(json::parse(&text).expect("text should be valid json"), 1);
+        history.extend(deserialize_json::<Vec<HistoryItem>>(&pretty_json_str)?);
+
+        if page >= page_count {
+            break;
+        }
+        page += 1;
+    }
+
+    Ok(history)
+}
+
+async fn post_history(
+    path: &str,
+    headers: HeaderMap,
+    history_request_body: &HistoryRequestBody,
+) -> Result<(), ApiError> {
+    let json_body = serde_json::to_string(history_request_body)
+        .expect("history request body should be serializable");
+
+    let response = reqwest::Client::new()
+        .post(get_api_url(path))
+        .headers(headers)
+        .body(json_body)
+        .send()
+        .await
+        .map_err(ApiError::Network)?;
+
+    // Both adding to and removing from history respond with 201 and 200 respectively
+    let status_code: TraktStatusCode = response.status().into();
+    if status_code != TraktStatusCode::PostSuccess {
+        TraktStatusCode::Success.error_if_different(status_code)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testing;
+
+    #[tokio::test]
+    async fn local_unwatch_after_the_remote_watch_is_pushed() {
+        let _database_guard = testing::lock_database().await;
+        let environment = testing::environment();
+
+        // The mock trakt history has the episode watched in 2013, long before this unwatch
+        {
+            let mut series = Series::new("Under the Dome".to_owned(), 1);
+            series.mark_tracked();
+            series.add_episode_unchecked(1, 3);
+            series.remove_episode(1, 3);
+        }
+
+        let report = sync("mock-client-id", "mock-access-token")
+            .await
+            .expect("failed to sync with the mock server");
+
+        assert_eq!(report.pulled_episodes, 0);
+        assert_eq!(report.removed_episodes, 1);
+
+        let series = DB
+            .get_series(1)
+            .expect("failed to read the series")
+            .expect("series should be in the database");
+        assert!(!series
+            .get_season(1)
+            .expect("season should be kept")
+            .is_episode_watched(3));
+
+        let removals = environment.get_requests("POST", HISTORY_REMOVE_PATH);
+        let removal: serde_json::Value = serde_json::from_str(
+            &removals
+                .last()
+                .expect("the unwatch should be pushed to trakt")
+                .body,
+        )
+        .expect("history removal should be json");
+        assert_eq!(removal["shows"][0]["ids"]["imdb"], "tt1553656");
+        assert_eq!(removal["shows"][0]["seasons"][0]["number"], 1);
+        assert_eq!(
+            removal["shows"][0]["seasons"][0]["episodes"][0]["number"],
+            3
+        );
+    }
+}
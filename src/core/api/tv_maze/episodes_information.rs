@@ -2,10 +2,10 @@ use chrono::{DateTime, Datelike, Duration, Local, Timelike, Utc};
 
 use super::{series_information::SeriesMainInformation, *};
 
-const EPISODE_INFORMATION_ADDRESS: &str =
-    "https://api.tvmaze.com/shows/SERIES-ID/episodebynumber?season=SEASON&number=EPISODE";
+const EPISODE_INFORMATION_PATH: &str =
+    "/shows/SERIES-ID/episodebynumber?season=SEASON&number=EPISODE";
 
-const EPISODE_LIST_ADDRESS: &str = "https://api.tvmaze.com/shows/SERIES-ID/episodes";
+const EPISODE_LIST_PATH: &str = "/shows/SERIES-ID/episodes";
 
 /// # An `Episode` data according to the TVmaze api
 ///
@@ -144,22 +144,18 @@ pub async fn get_episode_information(
     season: u32,
     episode: u32,
 ) -> Result<Episode, ApiError> {
-    let url = EPISODE_INFORMATION_ADDRESS.replace("SERIES-ID", &series_id.to_string());
+    let url = get_api_url(&EPISODE_INFORMATION_PATH.replace("SERIES-ID", &series_id.to_string()));
     let url = url.replace("SEASON", &season.to_string());
     let url = url.replace("EPISODE", &episode.to_string());
 
-    let prettified_json = get_pretty_json_from_url(url)
-        .await
-        .map_err(ApiError::Network)?;
+    let prettified_json = get_pretty_json_from_url(url).await?;
 
     deserialize_json(&prettified_json)
 }
 
 pub async fn get_episode_list(series_id: u32) -> Result<(Vec<Episode>, String), ApiError> {
-    let url = EPISODE_LIST_ADDRESS.replace("SERIES-ID", &series_id.to_string());
-    let prettified_json = get_pretty_json_from_url(url)
-        .await
-        .map_err(ApiError::Network)?;
+    let url = get_api_url(&EPISODE_LIST_PATH.replace("SERIES-ID", &series_id.to_string()));
+    let prettified_json = get_pretty_json_from_url(url).await?;
 
     Ok((deserialize_json(&prettified_json)?, prettified_json))
 }
@@ -0,0 +1,173 @@
+//! A shared http client for the TVmaze api
+//!
+//! TVmaze allows at least 20 calls every 10 seconds per IP address and answers with
+//! `429 Too Many Requests` when the limit is exceeded. The client paces the requests to the
+//! api with a token bucket matching that limit and retries failed requests with a bounded
+//! exponential backoff, waiting for the `Retry-After` duration when it's provided.
+//!
+//! Requests outside the api i.e. images from TVmaze's image server are not paced but are
+//! retried the same way.
+//!
+//! The outcome of every request is reported to the offline mode, requests being attempted
+//! only once while offline so that cached data is served without waiting on the backoff.
+
+use std::time::Duration;
+
+use lazy_static::lazy_static;
+use reqwest::{header, Response, StatusCode};
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+use tracing::warn;
+
+use crate::core::caching::offline;
+
+/// The amount of calls allowed in every rate limit window
+const RATE_LIMIT_CALLS: u32 = 20;
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(10);
+
+/// The maximum amount of attempts of a request before giving up
+const MAX_ATTEMPTS: u32 = 6;
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+lazy_static! {
+    pub static ref HTTP_CLIENT: HttpClient = HttpClient::new();
+}
+
+pub struct HttpClient {
+    /// The reqwest client, reused to keep the connections alive between requests
+    client: reqwest::Client,
+    rate_limiter: Mutex<TokenBucket>,
+}
+
+impl HttpClient {
+    fn new() -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            rate_limiter: Mutex::new(TokenBucket::new(RATE_LIMIT_CALLS, RATE_LIMIT_WINDOW)),
+        }
+    }
+
+    /// Sends a get request to the given url, retrying when the request fails
+    ///
+    /// An error is returned when the api keeps answering with `429 Too Many Requests` or a
+    /// server error. Other error statuses are returned as responses, as their body tells what
+    /// went wrong i.e. a show lookup answers with `null` when the show is not found.
+    pub async fn get(&self, url: &str) -> Result<Response, reqwest::Error> {
+        let is_api_request = url.starts_with(&super::get_base_url());
+
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let is_last_attempt = attempt == MAX_ATTEMPTS || offline::is_offline();
+
+            if is_api_request {
+                self.rate_limiter.lock().await.acquire().await;
+            }
+
+            match self.client.get(url).send().await {
+                Ok(response) => {
+                    offline::report_online();
+                    let status = response.status();
+
+                    let is_retryable =
+                        status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error();
+                    if !is_retryable {
+                        break Ok(response);
+                    }
+                    if is_last_attempt {
+                        break response.error_for_status();
+                    }
+
+                    let delay = get_retry_after(&response).unwrap_or_else(|| backoff(attempt));
+                    warn!(
+                        "request to '{}' answered with '{}', retrying in {:?}",
+                        url, status, delay
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                Err(err) => {
+                    let is_connection_error = err.is_connect() || err.is_timeout();
+                    if is_last_attempt || !(err.is_request() || is_connection_error) {
+                        if is_connection_error {
+                            offline::report_offline();
+                        }
+                        break Err(err);
+                    }
+
+                    let delay = backoff(attempt);
+                    warn!(
+                        "request to '{}' failed, retrying in {:?}: {}",
+                        url, delay, err
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+            }
+        }
+    }
+}
+
+/// Returns the delay before the given retry attempt
+///
+/// The delay doubles for every attempt up to `MAX_BACKOFF` and has a random jitter of up
+/// to a quarter of it so that concurrent requests do not retry all at once.
+fn backoff(attempt: u32) -> Duration {
+    let delay = INITIAL_BACKOFF
+        .saturating_mul(2_u32.saturating_pow(attempt.saturating_sub(1)))
+        .min(MAX_BACKOFF);
+    let jitter = delay.mul_f64(rand::random::<f64>() / 4.0);
+    delay + jitter
+}
+
+/// Reads the `Retry-After` header of the response, only the delay in seconds is supported
+fn get_retry_after(response: &Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+        .map(|seconds: u64| Duration::from_secs(seconds).min(MAX_BACKOFF))
+}
+
+/// A token bucket refilling it's tokens evenly over the rate limit window
+struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    /// The amount of tokens refilled every second
+    refill_rate: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(calls: u32, window: Duration) -> Self {
+        Self {
+            capacity: calls as f64,
+            tokens: calls as f64,
+            refill_rate: calls as f64 / window.as_secs_f64(),
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Takes a token from the bucket, waiting for one to be refilled when the bucket is empty
+    async fn acquire(&mut self) {
+        self.refill();
+
+        if self.tokens < 1.0 {
+            let wait_time = Duration::from_secs_f64((1.0 - self.tokens) / self.refill_rate);
+            tokio::time::sleep(wait_time).await;
+            self.refill();
+        }
+
+        self.tokens -= 1.0;
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_rate).min(self.capacity);
+        self.last_refill = now;
+    }
+}
@@ -24,34 +24,29 @@ pub enum ImageKind {
 /// Since Original images from TvMaze may have extremely high resolution up to 4k which can cause `wgpu` to crash,
 /// this function will thumbnail the original image to the size that is good enough to be displayed in the GUI.
 pub async fn load_image(image_url: String, image_resolution: ImageResolution) -> Option<Bytes> {
-    loop {
-        match reqwest::get(&image_url).await {
-            Ok(response) => {
-                if let Ok(bytes) = response.bytes().await {
-                    let image = image::load_from_memory(&bytes)
-                        .map_err(|err| error!("failed to load image from the api: {}", err))
-                        .ok()?;
+    let bytes = super::http_client::HTTP_CLIENT
+        .get(&image_url)
+        .await
+        .map_err(|err| error!("failed to request image from the api: {}", err))
+        .ok()?
+        .bytes()
+        .await
+        .map_err(|err| error!("failed to get image bytes from the api: {}", err))
+        .ok()?;
 
-                    break match image_resolution {
-                        ImageResolution::Original(image_kind) => {
-                            if should_lower_resolution(&image, image_kind) {
-                                lower_image_resolution(image, image_kind)
-                            } else {
-                                Some(bytes)
-                            }
-                        }
-                        ImageResolution::Medium => Some(bytes),
-                    };
-                }
-            }
-            Err(ref err) => {
-                if err.is_request() {
-                    super::random_async_sleep().await;
-                } else {
-                    break None;
-                }
+    let image = image::load_from_memory(&bytes)
+        .map_err(|err| error!("failed to load image from the api: {}", err))
+        .ok()?;
+
+    match image_resolution {
+        ImageResolution::Original(image_kind) => {
+            if should_lower_resolution(&image, image_kind) {
+                lower_image_resolution(image, image_kind)
+            } else {
+                Some(bytes)
             }
         }
+        ImageResolution::Medium => Some(bytes),
     }
 }
 
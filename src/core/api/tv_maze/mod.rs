@@ -1,7 +1,10 @@
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+use crate::core::settings_config;
+
 pub mod episodes_information;
+pub mod http_client;
 pub mod image;
 pub mod seasons_list;
 pub mod series_information;
@@ -12,6 +15,12 @@ pub mod show_lookup;
 pub mod tv_schedule;
 pub mod updates;
 
+/// The default base url of the TVmaze api
+const DEFAULT_BASE_URL: &str = "https://api.tvmaze.com";
+/// Environment variable overriding the base url of the TVmaze api i.e. when testing against
+/// a mock server
+pub const BASE_URL_ENVIRONMENT_VARIABLE: &str = "TVMAZE_BASE_URL";
+
 #[derive(Debug, Error)]
 pub enum ApiError {
     #[error("network error during request")]
@@ -20,6 +29,15 @@ pub enum ApiError {
     Deserialization(String, serde_json::Error),
     #[error("errored json from tvmaze: name: '{0}', message: '{1}'")]
     BadJson(String, String),
+    #[error("invalid json from tvmaze: {0}")]
+    InvalidJson(json::Error),
+}
+
+impl ApiError {
+    /// Whether the error comes from TVmaze not being reachable i.e. while offline
+    pub fn is_connection_error(&self) -> bool {
+        matches!(self, ApiError::Network(err) if err.is_connect() || err.is_timeout())
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -76,29 +94,55 @@ pub fn deserialize_json<'a, T: serde::Deserialize<'a>>(
     })
 }
 
+/// Returns the base url of the TVmaze api
+///
+/// The `TVMAZE_BASE_URL` environment variable takes priority over the base url set
+/// in the settings.
+pub fn get_base_url() -> String {
+    std::env::var(BASE_URL_ENVIRONMENT_VARIABLE)
+        .ok()
+        .or_else(|| {
+            settings_config::SETTINGS
+                .read()
+                .ok()?
+                .get_current_settings()
+                .api
+                .tvmaze_base_url
+                .clone()
+        })
+        .map(|base_url| base_url.trim_end_matches('/').to_owned())
+        .unwrap_or_else(|| DEFAULT_BASE_URL.to_owned())
+}
+
+/// Returns the full url of the given TVmaze api path
+fn get_api_url(path: &str) -> String {
+    format!("{}{}", get_base_url(), path)
+}
+
 /// Requests text response from the provided url
-async fn get_pretty_json_from_url(url: String) -> Result<String, reqwest::Error> {
-    let response = loop {
-        match reqwest::get(&url).await {
-            Ok(response) => break response,
-            Err(err) => {
-                if err.is_request() {
-                    random_async_sleep().await;
-                } else {
-                    return Err(err);
-                }
-            }
-        }
-    };
+async fn get_pretty_json_from_url(url: String) -> Result<String, ApiError> {
+    let response = http_client::HTTP_CLIENT
+        .get(&url)
+        .await
+        .map_err(ApiError::Network)?;
 
-    let text = response.text().await?;
+    let text = response.text().await.map_err(ApiError::Network)?;
 
-    Ok(json::stringify_pretty(json::parse(&text).unwrap(), 1))
+    let json = json::parse(&text).map_err(ApiError::InvalidJson)?;
+    Ok(json::stringify_pretty(json, 1))
 }
 
-/// Sleeps the current thread asynchronously between 0-0.2 seconds choosing a random
-/// value in between.
-async fn random_async_sleep() {
-    let random_val = rand::random::<u64>() / 100_000_000_000_000_000;
-    tokio::time::sleep(std::time::Duration::from_millis(random_val)).await;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testing;
+
+    #[tokio::test]
+    async fn invalid_json_is_returned_as_an_error() {
+        testing::environment();
+
+        let result = get_pretty_json_from_url(get_api_url("/invalid-json")).await;
+
+        assert!(matches!(result, Err(ApiError::InvalidJson(_))));
+    }
 }
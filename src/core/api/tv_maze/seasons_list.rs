@@ -1,7 +1,7 @@
 use super::*;
 
 // replace the word SERIES-ID with the actual series id
-const SEASONS_LIST_ADDRESS: &str = "https://api.tvmaze.com/shows/SERIES-ID/seasons";
+const SEASONS_LIST_PATH: &str = "/shows/SERIES-ID/seasons";
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct Season {
@@ -15,10 +15,8 @@ pub struct Season {
 }
 
 pub async fn get_seasons_list(series_id: u32) -> Result<Vec<Season>, ApiError> {
-    let url = SEASONS_LIST_ADDRESS.replace("SERIES-ID", &series_id.to_string());
-    let prettified_json = get_pretty_json_from_url(url)
-        .await
-        .map_err(ApiError::Network)?;
+    let url = get_api_url(&SEASONS_LIST_PATH.replace("SERIES-ID", &series_id.to_string()));
+    let prettified_json = get_pretty_json_from_url(url).await?;
 
     deserialize_json(&prettified_json)
 }
@@ -5,10 +5,10 @@ use serde::{Deserialize, Serialize};
 use std::hash::{Hash, Hasher};
 
 // The series id goes after the last slash(append at the end of the string)
-const SERIES_INFORMATION_ADDRESS: &str = "https://api.tvmaze.com/shows/";
+const SERIES_INFORMATION_PATH: &str = "/shows/";
 
 // Replace ID with the actual series id
-const SERIES_INFO_AND_EPISODE_LIST: &str = "https://api.tvmaze.com/shows/ID?embed=episodes";
+const SERIES_INFO_AND_EPISODE_LIST: &str = "/shows/ID?embed=episodes";
 
 #[derive(Debug, Eq, PartialEq, Clone, Hash)]
 pub enum Genre {
@@ -235,7 +235,8 @@ pub struct SeriesMainInformation {
     /// with an embedded list of series' episodes.
     #[serde(rename = "_embedded")]
     pub embedded_episode_list: Option<EmbeddedEpisodeList>,
-    // pub externals: ExternalIds,
+    #[serde(default)]
+    pub externals: Option<ExternalIds>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -337,29 +338,29 @@ pub struct Country {
     pub code: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct ExternalIds {
     pub imdb: Option<String>,
     pub thetvdb: Option<u32>,
 }
 
 pub async fn get_series_main_info_with_url(url: String) -> Result<String, ApiError> {
-    get_pretty_json_from_url(url)
-        .await
-        .map_err(ApiError::Network)
+    get_pretty_json_from_url(url).await
 }
 
 pub async fn get_series_main_info_with_id(series_id: u32) -> Result<String, ApiError> {
-    get_series_main_info_with_url(format!("{}{}", SERIES_INFORMATION_ADDRESS, series_id)).await
+    get_series_main_info_with_url(get_api_url(&format!(
+        "{}{}",
+        SERIES_INFORMATION_PATH, series_id
+    )))
+    .await
 }
 
 pub async fn get_series_info_and_episode_list(
     series_id: u32,
 ) -> Result<SeriesMainInformation, ApiError> {
-    let url = SERIES_INFO_AND_EPISODE_LIST.replace("ID", &series_id.to_string());
-    let pretty_json = get_pretty_json_from_url(url)
-        .await
-        .map_err(ApiError::Network)?;
+    let url = get_api_url(&SERIES_INFO_AND_EPISODE_LIST.replace("ID", &series_id.to_string()));
+    let pretty_json = get_pretty_json_from_url(url).await?;
 
     deserialize_json(&pretty_json)
 }
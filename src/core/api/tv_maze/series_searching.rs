@@ -4,7 +4,7 @@
 use super::*;
 
 // The series name goes after the equals sign
-const SERIES_SEARCH_ADDRESS: &str = "https://api.tvmaze.com/search/shows?q=";
+const SERIES_SEARCH_PATH: &str = "/search/shows?q=";
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct SeriesSearchResult {
@@ -12,11 +12,9 @@ pub struct SeriesSearchResult {
 }
 
 pub async fn search_series(series_name: String) -> Result<Vec<SeriesSearchResult>, ApiError> {
-    let url = format!("{}{}", SERIES_SEARCH_ADDRESS, series_name);
+    let url = get_api_url(&format!("{}{}", SERIES_SEARCH_PATH, series_name));
 
-    let prettified_json = get_pretty_json_from_url(url)
-        .await
-        .map_err(ApiError::Network)?;
+    let prettified_json = get_pretty_json_from_url(url).await?;
 
     deserialize_json(&prettified_json)
 }
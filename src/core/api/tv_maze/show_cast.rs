@@ -1,6 +1,6 @@
 use serde::Deserialize;
 
-use super::{get_pretty_json_from_url, ApiError, Image};
+use super::{get_api_url, get_pretty_json_from_url, ApiError, Image};
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct Cast {
@@ -51,12 +51,10 @@ pub struct Character {
 }
 
 // replace ID with the actual show id
-const SHOW_CAST_ADDRESS: &str = "https://api.tvmaze.com/shows/ID/cast";
+const SHOW_CAST_PATH: &str = "/shows/ID/cast";
 
 pub async fn get_show_cast(series_id: u32) -> Result<String, ApiError> {
-    let url = SHOW_CAST_ADDRESS.replace("ID", &series_id.to_string());
+    let url = get_api_url(&SHOW_CAST_PATH.replace("ID", &series_id.to_string()));
 
-    get_pretty_json_from_url(url)
-        .await
-        .map_err(ApiError::Network)
+    get_pretty_json_from_url(url).await
 }
@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-use super::{get_pretty_json_from_url, ApiError};
+use super::{get_api_url, get_pretty_json_from_url, ApiError};
 
 #[derive(PartialEq)]
 pub enum ImageType {
@@ -56,15 +56,13 @@ pub struct MediumResolution {
 }
 
 // Relplace ID with the actual series id
-const IMAGES_ADDRESS: &str = "https://api.tvmaze.com/shows/ID/images";
+const IMAGES_PATH: &str = "/shows/ID/images";
 
 /// Retrieves all the images available for the given series id
 pub async fn get_show_images(series_id: u32) -> Result<String, ApiError> {
-    let url = IMAGES_ADDRESS.replace("ID", &series_id.to_string());
+    let url = get_api_url(&IMAGES_PATH.replace("ID", &series_id.to_string()));
 
-    get_pretty_json_from_url(url)
-        .await
-        .map_err(ApiError::Network)
+    get_pretty_json_from_url(url).await
 }
 
 // /// Loads the most recent image banner from the provided series id
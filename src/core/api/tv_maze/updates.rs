@@ -1,13 +1,13 @@
 use super::deserialize_json;
-use super::get_pretty_json_from_url;
 use super::ApiError;
+use super::{get_api_url, get_pretty_json_from_url};
 
 use std::collections::HashMap;
 
 /// Retrieves all the shows update
-const SERIES_UPDATES_ADDRESS: &str = "https://api.tvmaze.com/updates/shows";
+const SERIES_UPDATES_PATH: &str = "/updates/shows";
 /// Retrieves the shows update with last update duration filter, the filter goes at the end of url.
-const SERIES_UPDATES_ADDRESS_FILTERED: &str = "https://api.tvmaze.com/updates/shows?since=";
+const SERIES_UPDATES_PATH_FILTERED: &str = "/updates/shows?since=";
 
 /// A list of all shows in the TVmaze database and the timestamp when they were last updated.
 /// Updating a direct or indirect child of a show will also mark the show itself as updated.
@@ -18,20 +18,19 @@ pub async fn get_shows_updates_index(
     last_updated: Option<LastUpdated>,
 ) -> Result<HashMap<String, i64>, ApiError> {
     let url = if let Some(last_updated) = last_updated {
-        format!("{}{}", SERIES_UPDATES_ADDRESS_FILTERED, last_updated)
+        get_api_url(&format!("{}{}", SERIES_UPDATES_PATH_FILTERED, last_updated))
     } else {
-        SERIES_UPDATES_ADDRESS.to_string()
+        get_api_url(SERIES_UPDATES_PATH)
     };
 
     tracing::info!("fetching shows updates");
 
-    let prettified_json = get_pretty_json_from_url(url)
-        .await
-        .map_err(ApiError::Network)?;
+    let prettified_json = get_pretty_json_from_url(url).await?;
 
     deserialize_json(&prettified_json)
 }
 
+#[derive(Debug, PartialEq, Eq)]
 pub enum LastUpdated {
     Day,
     Week,
@@ -17,6 +17,9 @@
 //! - `show-cast`. The list of top cast of the series.
 //! - `image-list`. The list of all images of the series i.e posters, banners, backgrounds etc.
 //!
+//! The series data can be kept in a tree of the database instead of files, see the
+//! `cache_store` module.
+//!
 //! ## Images cache directory
 //! This contains all the images ever loaded by series troxide. Having all images ever loaded by
 //! the program in the same directory makes it more efficient by reusing the same images that might
@@ -24,8 +27,25 @@
 //! in different shows and tvmaze reuses the same image for that actor. Another example is when
 //! searching for series, results may include a series that is already tracked and whose image
 //! is already cached.
+//!
+//! ## Cache manifest
+//! The fetch times of the cache files are recorded in a manifest at the root of the cache
+//! directory, see the `cache_manifest` module.
+//!
+//! ## Cache size limit
+//! The cache is kept under the configured size by evicting the least recently used files,
+//! see the `cache_usage` module.
+//!
+//! ## Memory cache
+//! Recently read cache files are kept in memory on top of the cache directories, see the
+//! `memory_cache` module. Concurrent loads of the same cache file or image share a single
+//! fetch, see the `in_flight` module.
+//!
+//! ## Offline mode
+//! When TVmaze can't be reached, cached data is served as is, see the `offline` module.
 
 use bytes::Bytes;
+use std::future::Future;
 use std::io::{self, ErrorKind};
 use std::path;
 
@@ -34,11 +54,16 @@ use super::api::tv_maze::{series_information::SeriesMainInformation, ApiError};
 use super::paths;
 use crate::core::api::tv_maze::{self, deserialize_json};
 use lazy_static::lazy_static;
-use tokio::fs;
 use tracing::{error, info};
 
+pub mod cache_manifest;
+pub mod cache_store;
 pub mod cache_updating;
+pub mod cache_usage;
 pub mod episode_list;
+pub mod in_flight;
+pub mod memory_cache;
+pub mod offline;
 pub mod series_info_and_episode_list;
 pub mod series_information;
 pub mod series_list;
@@ -55,6 +80,8 @@ const SERIES_IMAGE_LIST_FILENAME: &str = "image-list";
 
 lazy_static! {
     pub static ref CACHER: Cacher = Cacher::init();
+    static ref JSON_LOADS: in_flight::InFlightRequests<String> = in_flight::InFlightRequests::new();
+    static ref IMAGE_LOADS: in_flight::InFlightRequests<Bytes> = in_flight::InFlightRequests::new();
 }
 
 pub enum CacheFolderType {
@@ -135,67 +162,88 @@ impl Cacher {
     }
 }
 
-/// Loads the image from the provided url
-pub async fn load_image(image_url: String, image_type: ImageResolution) -> Option<Bytes> {
+/// The path the image at the given url is cached at
+fn get_image_cache_path(image_url: &str) -> path::PathBuf {
     // Hashing the image url as a file name as the forward slashes in web urls
     // mimic paths
     use sha2::{Digest, Sha256};
 
     let mut hasher = Sha256::new();
-    hasher.update(&image_url);
+    hasher.update(image_url);
     let image_hash = format!("{:x}", hasher.finalize());
 
     let mut image_path = CACHER.get_cache_folder_path(CacheFolderType::Images);
     image_path.push(&image_hash);
+    image_path
+}
 
-    match fs::read(&image_path).await {
-        Ok(image_bytes) => Some(Bytes::from(image_bytes)),
-        Err(err) => {
-            if err.kind() == ErrorKind::NotFound {
-                info!("falling back online for image with link {}", image_url);
-                if let Some(image_bytes) = tv_maze::image::load_image(image_url, image_type).await {
+/// Loads the image from the provided url
+pub async fn load_image(image_url: String, image_type: ImageResolution) -> Option<Bytes> {
+    let image_path = get_image_cache_path(&image_url);
+
+    IMAGE_LOADS
+        .coalesce(&image_url, || async {
+            match cache_store::read_bytes(&image_path).await {
+                Ok(image_bytes) => {
+                    cache_manifest::MANIFEST.record_access(&image_path);
+                    Ok(image_bytes)
+                }
+                Err(err) if err.kind() == ErrorKind::NotFound => {
+                    info!("falling back online for image with link {}", image_url);
+                    let image_bytes = tv_maze::image::load_image(image_url.clone(), image_type)
+                        .await
+                        .ok_or(())?;
                     write_cache(&image_bytes, &image_path).await;
-                    Some(image_bytes)
-                } else {
-                    None
+                    Ok(image_bytes)
                 }
-            } else {
-                None
+                Err(_) => Err(()),
             }
-        }
-    }
+        })
+        .await
+        .ok()
 }
 
 pub async fn read_cache(cache_filepath: impl AsRef<path::Path>) -> io::Result<String> {
-    fs::read_to_string(cache_filepath).await
+    let cache = cache_store::read(cache_filepath.as_ref()).await?;
+    cache_manifest::MANIFEST.record_access(cache_filepath.as_ref());
+    Ok(cache)
 }
 
-pub async fn write_cache(cache_data: impl AsRef<[u8]>, cache_filepath: &path::Path) {
-    loop {
-        if let Err(err) = fs::write(cache_filepath, &cache_data).await {
-            if err.kind() == ErrorKind::NotFound {
-                let mut cache_folder = path::PathBuf::from(cache_filepath);
-                cache_folder.pop();
-                match fs::create_dir_all(&cache_folder).await {
-                    Err(err) => {
-                        error!(
-                            "failed to create cache directory '{}': {}",
-                            cache_folder.display(),
-                            err
-                        );
-                        break;
+/// Reads the cache file falling back to `load_online` when it's missing, concurrent loads of
+/// the same cache file sharing a single fetch
+async fn read_cache_or_load<F, Fut>(
+    cache_filepath: &path::Path,
+    load_online: F,
+) -> Result<String, ApiError>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<String, ApiError>>,
+{
+    JSON_LOADS
+        .coalesce(&cache_filepath.to_string_lossy(), || async {
+            match read_cache(cache_filepath).await {
+                Ok(json_string) => Ok(json_string),
+                Err(err) => {
+                    let json_string = load_online().await?;
+                    if err.kind() == ErrorKind::NotFound {
+                        write_cache(&json_string, cache_filepath).await;
                     }
-                    Ok(_) => continue,
-                };
-            } else {
-                error!(
-                    "failed to write cache '{}': {}",
-                    cache_filepath.display(),
-                    err
-                );
-                break;
+                    Ok(json_string)
+                }
             }
-        }
-        break;
+        })
+        .await
+}
+
+pub async fn write_cache(cache_data: impl AsRef<[u8]>, cache_filepath: &path::Path) {
+    if let Err(err) = cache_store::write(cache_filepath, cache_data.as_ref()).await {
+        error!(
+            "failed to write cache '{}': {}",
+            cache_filepath.display(),
+            err
+        );
+        return;
     }
+    cache_manifest::MANIFEST.record_fetch(cache_filepath);
+    cache_usage::schedule_cache_limit_enforcement();
 }
@@ -0,0 +1,269 @@
+//! # Cache manifest
+//!
+//! A record of when every cache file was fetched from TVmaze, kept in a json file at the
+//! root of the cache directory. Cache updates compare these fetch times with the TVmaze
+//! updates index to find the stale files, file system timestamps not being reliable for
+//! that as many file systems don't record creation times.
+//!
+//! Recording a fetch only updates the manifest in memory, saving it is delayed a little so
+//! that caching many files at once results in a single write. Files missing from the
+//! manifest i.e. when the program exits before saving fall back to their modification times.
+//!
+//! The last time every cache file was read is recorded as well, deciding which files are
+//! evicted first when the cache grows past it's size limit.
+//!
+//! Files known to be outdated that could not be fetched again i.e. while offline are marked
+//! stale until they are fetched.
+
+use std::collections::{HashMap, HashSet};
+use std::path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use tracing::{error, warn};
+
+use super::CACHER;
+
+const MANIFEST_FILENAME: &str = "cache-manifest.json";
+/// How long to wait for more changes before saving the manifest
+const SAVE_DELAY: Duration = Duration::from_secs(3);
+
+lazy_static! {
+    pub static ref MANIFEST: CacheManifest = CacheManifest::load();
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ManifestData {
+    /// The last time the whole cache was checked for updates
+    last_update: Option<DateTime<Utc>>,
+    /// The fetch times of the cache files keyed by their paths relative to the cache root
+    files: HashMap<String, DateTime<Utc>>,
+    /// The cache files served while known to be outdated
+    #[serde(default)]
+    stale: HashSet<String>,
+    /// The last times the cache files were read
+    #[serde(default)]
+    accesses: HashMap<String, DateTime<Utc>>,
+}
+
+pub struct CacheManifest {
+    data: RwLock<ManifestData>,
+    save_pending: AtomicBool,
+    /// Keeps the delayed and explicit saves from writing the file at the same time
+    save_lock: tokio::sync::Mutex<()>,
+}
+
+impl CacheManifest {
+    fn load() -> Self {
+        let manifest_path = get_manifest_path();
+
+        let data = match std::fs::read_to_string(&manifest_path) {
+            Ok(manifest_str) => serde_json::from_str(&manifest_str).unwrap_or_else(|err| {
+                error!(
+                    "failed to parse the cache manifest, starting afresh: {}",
+                    err
+                );
+                ManifestData::default()
+            }),
+            Err(err) => {
+                if err.kind() != std::io::ErrorKind::NotFound {
+                    error!(
+                        "failed to read the cache manifest, starting afresh: {}",
+                        err
+                    );
+                }
+                ManifestData::default()
+            }
+        };
+
+        Self {
+            data: RwLock::new(data),
+            save_pending: AtomicBool::new(false),
+            save_lock: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Records the cache file at the given path as just fetched
+    pub fn record_fetch(&self, cache_filepath: &path::Path) {
+        let Some(key) = get_key(cache_filepath) else {
+            return;
+        };
+        let mut data = self
+            .data
+            .write()
+            .expect("failed to write the cache manifest");
+        data.stale.remove(&key);
+        data.files.insert(key, Utc::now());
+        drop(data);
+        self.schedule_save();
+    }
+
+    /// Records the cache file at the given path as just read
+    pub fn record_access(&self, cache_filepath: &path::Path) {
+        let Some(key) = get_key(cache_filepath) else {
+            return;
+        };
+        self.data
+            .write()
+            .expect("failed to write the cache manifest")
+            .accesses
+            .insert(key, Utc::now());
+        self.schedule_save();
+    }
+
+    /// The last time the cache file was read, falling back to it's fetch time when it was
+    /// never read
+    pub fn get_last_use(&self, cache_filepath: &path::Path) -> Option<DateTime<Utc>> {
+        let last_access = get_key(cache_filepath).and_then(|key| {
+            self.data
+                .read()
+                .expect("failed to read the cache manifest")
+                .accesses
+                .get(&key)
+                .copied()
+        });
+        let fetch_time = self.get_fetch_time(cache_filepath);
+        last_access.max(fetch_time)
+    }
+
+    /// Marks the cache file at the given path as outdated, it's still served until fetched again
+    pub fn mark_stale(&self, cache_filepath: &path::Path) {
+        let Some(key) = get_key(cache_filepath) else {
+            return;
+        };
+        let is_newly_stale = self
+            .data
+            .write()
+            .expect("failed to write the cache manifest")
+            .stale
+            .insert(key);
+        if is_newly_stale {
+            self.schedule_save();
+        }
+    }
+
+    pub fn is_stale(&self, cache_filepath: &path::Path) -> bool {
+        get_key(cache_filepath)
+            .map(|key| {
+                self.data
+                    .read()
+                    .expect("failed to read the cache manifest")
+                    .stale
+                    .contains(&key)
+            })
+            .unwrap_or(false)
+    }
+
+    /// Forgets the cache file or all the cache files in the directory at the given path
+    pub fn remove(&self, cache_path: &path::Path) {
+        let Some(key) = get_key(cache_path) else {
+            return;
+        };
+        let directory_prefix = format!("{}/", key);
+        let is_removed =
+            |file_key: &String| *file_key == key || file_key.starts_with(&directory_prefix);
+
+        let mut data = self
+            .data
+            .write()
+            .expect("failed to write the cache manifest");
+        data.files.retain(|file_key, _| !is_removed(file_key));
+        data.stale.retain(|file_key| !is_removed(file_key));
+        data.accesses.retain(|file_key, _| !is_removed(file_key));
+        drop(data);
+        self.schedule_save();
+    }
+
+    /// The time the cache file was fetched, falling back to it's modification time when it's
+    /// not in the manifest
+    pub fn get_fetch_time(&self, cache_filepath: &path::Path) -> Option<DateTime<Utc>> {
+        let fetch_time = get_key(cache_filepath).and_then(|key| {
+            self.data
+                .read()
+                .expect("failed to read the cache manifest")
+                .files
+                .get(&key)
+                .copied()
+        });
+
+        fetch_time.or_else(|| {
+            std::fs::metadata(cache_filepath)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .map(DateTime::<Utc>::from)
+        })
+    }
+
+    pub fn get_last_update(&self) -> Option<DateTime<Utc>> {
+        self.data
+            .read()
+            .expect("failed to read the cache manifest")
+            .last_update
+    }
+
+    pub fn set_last_update(&self, time: DateTime<Utc>) {
+        self.data
+            .write()
+            .expect("failed to write the cache manifest")
+            .last_update = Some(time);
+        self.schedule_save();
+    }
+
+    /// Writes the manifest into it's file right away
+    pub async fn save(&self) -> std::io::Result<()> {
+        let _save_guard = self.save_lock.lock().await;
+        self.save_pending.store(false, Ordering::SeqCst);
+
+        let manifest_str =
+            serde_json::to_string(&*self.data.read().expect("failed to read the cache manifest"))
+                .expect("cache manifest should be serializable");
+
+        // Writing to a temporary file first so that an interrupted save can't corrupt it
+        let manifest_path = get_manifest_path();
+        let temporary_path = manifest_path.with_extension("json.tmp");
+        tokio::fs::create_dir_all(CACHER.get_root_cache_path()).await?;
+        tokio::fs::write(&temporary_path, manifest_str).await?;
+        tokio::fs::rename(temporary_path, manifest_path).await
+    }
+
+    fn schedule_save(&self) {
+        if self.save_pending.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => {
+                handle.spawn(async {
+                    tokio::time::sleep(SAVE_DELAY).await;
+                    if let Err(err) = MANIFEST.save().await {
+                        error!("failed to save the cache manifest: {}", err);
+                    }
+                });
+            }
+            Err(_) => {
+                warn!("no runtime to save the cache manifest on");
+                self.save_pending.store(false, Ordering::SeqCst);
+            }
+        }
+    }
+}
+
+fn get_manifest_path() -> path::PathBuf {
+    CACHER.get_root_cache_path().join(MANIFEST_FILENAME)
+}
+
+/// The path relative to the cache root with forward slashes, `None` when it's not in the cache
+pub(super) fn get_key(cache_path: &path::Path) -> Option<String> {
+    let relative_path = cache_path.strip_prefix(CACHER.get_root_cache_path()).ok()?;
+    Some(
+        relative_path
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/"),
+    )
+}
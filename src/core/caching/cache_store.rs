@@ -0,0 +1,554 @@
+//! # Cache store
+//!
+//! Where the series data of the cache lives, chosen by the cache backend of the settings.
+//! The files backend keeps a json file for every piece of series data in a directory per
+//! series while the database backend keeps the same json in a single tree of the database,
+//! sparing the thousands of file reads when loading many series at once. The data is stored
+//! as it is, so the json is still parsed when read whatever the backend.
+//!
+//! The cache files are still addressed by their paths, those in the series cache directory
+//! being mapped to the keys of the tree when the database backend is used, so that the rest
+//! of the cache does not have to care about the backend. Images and the full schedule are
+//! always kept as files.
+//!
+//! Recently read cache files are kept in memory whatever the backend, see the `memory_cache`
+//! module.
+//!
+//! Changing the backend moves the series data from the other one on the next start, in
+//! `open` which is called at startup. The cache files are moved into a staging tree first,
+//! every file being removed once it's safely in the database, and the staging tree is then
+//! swapped in at once. When moving fails, the staged files are written back so that the cache
+//! is left as it was, and a move interrupted by a crash is resumed on the next start.
+//!
+//! `benchmark` compares loading the series data from both backends, it's run by the
+//! `cache benchmark` command.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::path;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use bytes::Bytes;
+use sled::{Batch, Tree};
+use tokio::fs;
+use tracing::{error, info, warn};
+
+use super::cache_manifest::get_key;
+use super::memory_cache::MEMORY_CACHE;
+use super::{CacheFolderType, CACHER, EPISODE_LIST_FILENAME, SERIES_MAIN_INFORMATION_FILENAME};
+use crate::core::api::tv_maze::deserialize_json;
+use crate::core::api::tv_maze::episodes_information::Episode;
+use crate::core::api::tv_maze::series_information::SeriesMainInformation;
+use crate::core::database::DB;
+use crate::core::settings_config::{CacheBackend, SETTINGS};
+
+const CACHE_TREE_NAME: &str = "series-cache";
+/// Where the cache files are moved before being swapped into the cache tree
+const STAGING_TREE_NAME: &str = "series-cache-staging";
+const BENCHMARK_TREE_NAME: &str = "series-cache-benchmark";
+
+/// The tree holding the series data, `None` when the files backend is used
+static STORE: OnceLock<Option<Tree>> = OnceLock::new();
+
+/// Opens the cache store of the backend in the settings, moving the series data from the
+/// other backend when there is any
+///
+/// This has to be called at startup once the database is opened, before the cache is used.
+pub fn open() {
+    STORE.get_or_init(open_store);
+}
+
+fn open_store() -> Option<Tree> {
+    let backend = SETTINGS
+        .read()
+        .expect("failed to read settings")
+        .get_current_settings()
+        .cache
+        .backend;
+
+    let tree = match DB.open_shared_tree(CACHE_TREE_NAME) {
+        Ok(tree) => tree,
+        Err(err) => {
+            error!(
+                "failed to open the cache store, using files instead: {}",
+                err
+            );
+            return None;
+        }
+    };
+
+    match backend {
+        CacheBackend::Database => {
+            if let Err(err) = migrate_files_into_store(&tree) {
+                error!("failed to move the series cache into the database: {}", err);
+                // Serving the cache files that could not be moved
+                return None;
+            }
+            Some(tree)
+        }
+        CacheBackend::Files => {
+            match DB.open_shared_tree(STAGING_TREE_NAME) {
+                Ok(staging_tree) => rollback_staged_files(staging_tree),
+                Err(err) => error!("failed to open the staging tree: {}", err),
+            }
+            if !tree.is_empty() {
+                if let Err(err) = migrate_store_into_files(&tree) {
+                    error!(
+                        "failed to move the series cache out of the database: {}",
+                        err
+                    );
+                }
+            }
+            None
+        }
+    }
+}
+
+/// Moves the series cache files into the tree, removing the series cache directory
+///
+/// The files go through the staging tree, which is only swapped into the tree once all of
+/// them are moved. The files left in the staging tree by an interrupted move are kept, their
+/// files being already removed.
+fn migrate_files_into_store(tree: &Tree) -> anyhow::Result<()> {
+    let series_cache_path = CACHER.get_cache_folder_path(CacheFolderType::Series);
+    let staging_tree = DB.open_shared_tree(STAGING_TREE_NAME)?;
+
+    if series_cache_path.exists() {
+        info!("moving the series cache into the database");
+        match stage_cache_files(&series_cache_path, &staging_tree) {
+            Ok(staged_files) => info!("staged {} series cache files", staged_files),
+            Err(err) => {
+                rollback_staged_files(staging_tree);
+                return Err(err);
+            }
+        }
+        // Kept when there are files not mapped to keys
+        let _ = std::fs::remove_dir(&series_cache_path);
+    }
+
+    if staging_tree.is_empty() {
+        return Ok(());
+    }
+
+    // Swapping all the staged files in at once, the batch being applied atomically
+    let mut batch = Batch::default();
+    let mut swapped_files = 0;
+    for record in staging_tree.iter() {
+        let (key, cache_data) = record?;
+        batch.insert(key, cache_data);
+        swapped_files += 1;
+    }
+    tree.apply_batch(batch)?;
+    tree.flush()?;
+    drop(staging_tree);
+    DB.drop_shared_tree(STAGING_TREE_NAME)?;
+
+    info!(
+        "moved {} series cache files into the database",
+        swapped_files
+    );
+    Ok(())
+}
+
+/// Moves the cache files into the staging tree, removing every file once the staging tree
+/// holding it is flushed
+fn stage_cache_files(series_cache_path: &path::Path, staging_tree: &Tree) -> anyhow::Result<usize> {
+    let mut staged_files = 0;
+    for series_directory in
+        std::fs::read_dir(series_cache_path).context("failed to read the series cache directory")?
+    {
+        let series_directory = series_directory?.path();
+        if !series_directory.is_dir() {
+            continue;
+        }
+
+        let mut cache_filepaths = vec![];
+        for cache_file in std::fs::read_dir(&series_directory)? {
+            let cache_filepath = cache_file?.path();
+            let Some(key) = get_key(&cache_filepath) else {
+                continue;
+            };
+            let cache_data = std::fs::read(&cache_filepath)
+                .with_context(|| format!("failed to read '{}'", cache_filepath.display()))?;
+            staging_tree.insert(key, cache_data)?;
+            cache_filepaths.push(cache_filepath);
+        }
+        staging_tree.flush()?;
+
+        for cache_filepath in &cache_filepaths {
+            std::fs::remove_file(cache_filepath)
+                .with_context(|| format!("failed to remove '{}'", cache_filepath.display()))?;
+        }
+        staged_files += cache_filepaths.len();
+
+        // Other files i.e. the ones not mapped to keys are kept with their directory
+        if let Err(err) = std::fs::remove_dir(&series_directory) {
+            warn!("failed to remove '{}': {}", series_directory.display(), err);
+        }
+    }
+    Ok(staged_files)
+}
+
+/// Writes the staged files back to where they came from, after a failed move or when the
+/// files backend is used again
+fn rollback_staged_files(staging_tree: Tree) {
+    if staging_tree.is_empty() {
+        return;
+    }
+
+    let rollback_result = migrate_store_into_files(&staging_tree);
+    drop(staging_tree);
+    match rollback_result {
+        Ok(()) => {
+            if let Err(err) = DB.drop_shared_tree(STAGING_TREE_NAME) {
+                warn!("failed to remove the staging tree: {}", err);
+            }
+        }
+        Err(err) => error!(
+            "failed to write the staged series cache back into files, it's kept in the \
+             database until the next start: {}",
+            err
+        ),
+    }
+}
+
+/// Writes the series data in the tree back into files, clearing the tree
+fn migrate_store_into_files(tree: &Tree) -> anyhow::Result<()> {
+    info!("moving the series cache out of the database");
+    let mut migrated_files = 0;
+    for record in tree.iter() {
+        let (key, cache_data) = record?;
+        let cache_filepath = get_path(&String::from_utf8_lossy(&key));
+        if let Some(parent_path) = cache_filepath.parent() {
+            std::fs::create_dir_all(parent_path)?;
+        }
+        std::fs::write(&cache_filepath, cache_data)
+            .with_context(|| format!("failed to write '{}'", cache_filepath.display()))?;
+        migrated_files += 1;
+    }
+    tree.clear()?;
+    tree.flush()?;
+
+    info!(
+        "moved {} series cache files out of the database",
+        migrated_files
+    );
+    Ok(())
+}
+
+/// The key of the cache file in the tree, `None` when it's kept as a file
+fn get_store_key(cache_path: &path::Path) -> Option<(&'static Tree, String)> {
+    if !cache_path.starts_with(CACHER.get_cache_folder_path(CacheFolderType::Series)) {
+        return None;
+    }
+    let tree = STORE
+        .get()
+        .expect("the cache store should be opened at startup")
+        .as_ref()?;
+    get_key(cache_path).map(|key| (tree, key))
+}
+
+/// The path of the cache file with the given key, the opposite of `get_key`
+fn get_path(key: &str) -> path::PathBuf {
+    let mut cache_path = CACHER.get_root_cache_path().to_path_buf();
+    cache_path.extend(key.split('/'));
+    cache_path
+}
+
+/// Reads the cache file, from memory when it was read recently
+pub async fn read_bytes(cache_filepath: &path::Path) -> io::Result<Bytes> {
+    if let Some(cache_data) = MEMORY_CACHE.get(cache_filepath) {
+        return Ok(cache_data);
+    }
+
+    let cache_data = match get_store_key(cache_filepath) {
+        Some((tree, key)) => {
+            let cache_data = tree
+                .get(key)
+                .map_err(io::Error::from)?
+                .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))?;
+            Bytes::copy_from_slice(&cache_data)
+        }
+        None => Bytes::from(fs::read(cache_filepath).await?),
+    };
+    MEMORY_CACHE.insert(cache_filepath, cache_data.clone());
+    Ok(cache_data)
+}
+
+pub async fn read(cache_filepath: &path::Path) -> io::Result<String> {
+    let cache_data = read_bytes(cache_filepath).await?;
+    String::from_utf8(cache_data.to_vec())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// Writes the cache file, creating it's directory when it does not exist
+pub async fn write(cache_filepath: &path::Path, cache_data: &[u8]) -> io::Result<()> {
+    if let Some((tree, key)) = get_store_key(cache_filepath) {
+        tree.insert(key, cache_data).map_err(io::Error::from)?;
+    } else if let Err(err) = fs::write(cache_filepath, cache_data).await {
+        if err.kind() != io::ErrorKind::NotFound {
+            return Err(err);
+        }
+        if let Some(cache_folder) = cache_filepath.parent() {
+            fs::create_dir_all(cache_folder).await?;
+        }
+        fs::write(cache_filepath, cache_data).await?;
+    }
+
+    MEMORY_CACHE.insert(cache_filepath, Bytes::copy_from_slice(cache_data));
+    Ok(())
+}
+
+pub async fn exists(cache_filepath: &path::Path) -> io::Result<bool> {
+    match get_store_key(cache_filepath) {
+        Some((tree, key)) => tree.contains_key(key).map_err(io::Error::from),
+        None => fs::try_exists(cache_filepath).await,
+    }
+}
+
+/// Removes the cache file or the directory and all of it's cache files
+pub async fn remove(cache_path: &path::Path) -> io::Result<()> {
+    MEMORY_CACHE.remove(cache_path);
+
+    if let Some((tree, key)) = get_store_key(cache_path) {
+        tree.remove(&key).map_err(io::Error::from)?;
+        for record in tree.scan_prefix(format!("{}/", key)).keys() {
+            tree.remove(record.map_err(io::Error::from)?)
+                .map_err(io::Error::from)?;
+        }
+        return Ok(());
+    }
+
+    if fs::metadata(cache_path).await?.is_dir() {
+        fs::remove_dir_all(cache_path).await
+    } else {
+        fs::remove_file(cache_path).await
+    }
+}
+
+/// Lists the cache files at the given path recursively together with their sizes, the path
+/// being a file or a directory
+pub async fn list_files(cache_path: &path::Path) -> io::Result<Vec<(path::PathBuf, u64)>> {
+    if let Some((tree, key)) = get_store_key(cache_path) {
+        let mut cache_files = vec![];
+        if let Some(cache_data) = tree.get(&key).map_err(io::Error::from)? {
+            cache_files.push((cache_path.to_path_buf(), cache_data.len() as u64));
+        }
+        for record in tree.scan_prefix(format!("{}/", key)) {
+            let (file_key, cache_data) = record.map_err(io::Error::from)?;
+            cache_files.push((
+                get_path(&String::from_utf8_lossy(&file_key)),
+                cache_data.len() as u64,
+            ));
+        }
+        return Ok(cache_files);
+    }
+
+    let mut cache_files = vec![];
+    let mut pending_paths = vec![cache_path.to_path_buf()];
+    while let Some(path) = pending_paths.pop() {
+        let metadata = match fs::metadata(&path).await {
+            Ok(metadata) => metadata,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err),
+        };
+
+        if metadata.is_dir() {
+            let mut read_dir = fs::read_dir(&path).await?;
+            while let Some(dir_entry) = read_dir.next_entry().await? {
+                pending_paths.push(dir_entry.path());
+            }
+        } else {
+            cache_files.push((path, metadata.len()));
+        }
+    }
+    Ok(cache_files)
+}
+
+pub struct BenchmarkReport {
+    pub series: usize,
+    pub files_duration: Duration,
+    pub database_duration: Duration,
+}
+
+/// Times loading the main information and episode list of all the cached series from both
+/// backends, the way the watchlist and statistics tabs load them at startup
+///
+/// Both backends are filled with a copy of the current series cache, in a temporary
+/// directory and a temporary tree, which are removed afterwards.
+pub async fn benchmark() -> anyhow::Result<BenchmarkReport> {
+    let series_cache_path = CACHER.get_cache_folder_path(CacheFolderType::Series);
+
+    // The cache data keyed by the series ids then by the file names
+    let mut series_caches: BTreeMap<String, Vec<(String, Vec<u8>)>> = BTreeMap::new();
+    for (cache_filepath, _) in list_files(&series_cache_path).await? {
+        let file_name = cache_filepath
+            .file_name()
+            .map(|file_name| file_name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if file_name != SERIES_MAIN_INFORMATION_FILENAME && file_name != EPISODE_LIST_FILENAME {
+            continue;
+        }
+        let Some(series_id) = cache_filepath
+            .parent()
+            .and_then(|parent_path| parent_path.file_name())
+            .map(|series_id| series_id.to_string_lossy().to_string())
+        else {
+            continue;
+        };
+
+        let cache_data = read(&cache_filepath).await?.into_bytes();
+        series_caches
+            .entry(series_id)
+            .or_default()
+            .push((file_name, cache_data));
+    }
+
+    let benchmark_path = std::env::temp_dir().join(format!(
+        "series-troxide-cache-benchmark-{}",
+        std::process::id()
+    ));
+    let tree = DB.open_shared_tree(BENCHMARK_TREE_NAME)?;
+    for (series_id, cache_files) in &series_caches {
+        let series_path = benchmark_path.join(series_id);
+        fs::create_dir_all(&series_path).await?;
+        for (file_name, cache_data) in cache_files {
+            fs::write(series_path.join(file_name), cache_data).await?;
+            tree.insert(
+                format!("{}/{}", series_id, file_name),
+                cache_data.as_slice(),
+            )?;
+        }
+    }
+
+    let result = time_backends(&series_caches, &benchmark_path, &tree).await;
+
+    fs::remove_dir_all(&benchmark_path)
+        .await
+        .context("failed to remove the benchmark directory")?;
+    drop(tree);
+    DB.drop_shared_tree(BENCHMARK_TREE_NAME)?;
+
+    let (files_duration, database_duration) = result?;
+    Ok(BenchmarkReport {
+        series: series_caches.len(),
+        files_duration,
+        database_duration,
+    })
+}
+
+async fn time_backends(
+    series_caches: &BTreeMap<String, Vec<(String, Vec<u8>)>>,
+    benchmark_path: &path::Path,
+    tree: &Tree,
+) -> anyhow::Result<(Duration, Duration)> {
+    let start_time = Instant::now();
+    for series_id in series_caches.keys() {
+        let series_path = benchmark_path.join(series_id);
+        let main_info =
+            fs::read_to_string(series_path.join(SERIES_MAIN_INFORMATION_FILENAME)).await;
+        let episode_list = fs::read_to_string(series_path.join(EPISODE_LIST_FILENAME)).await;
+        parse_series_data(main_info.ok(), episode_list.ok())?;
+    }
+    let files_duration = start_time.elapsed();
+
+    let start_time = Instant::now();
+    for series_id in series_caches.keys() {
+        let get_cache_data = |file_name: &str| -> anyhow::Result<Option<String>> {
+            Ok(tree
+                .get(format!("{}/{}", series_id, file_name))?
+                .map(|cache_data| String::from_utf8_lossy(&cache_data).into_owned()))
+        };
+        parse_series_data(
+            get_cache_data(SERIES_MAIN_INFORMATION_FILENAME)?,
+            get_cache_data(EPISODE_LIST_FILENAME)?,
+        )?;
+    }
+    let database_duration = start_time.elapsed();
+
+    Ok((files_duration, database_duration))
+}
+
+fn parse_series_data(
+    main_info: Option<String>,
+    episode_list: Option<String>,
+) -> anyhow::Result<()> {
+    if let Some(main_info) = main_info {
+        deserialize_json::<SeriesMainInformation>(&main_info)?;
+    }
+    if let Some(episode_list) = episode_list {
+        deserialize_json::<Vec<Episode>>(&episode_list)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testing;
+
+    /// A series cache directory of it's own, away from the one of the other tests
+    fn create_series_cache(name: &str) -> path::PathBuf {
+        let series_cache_path = CACHER.get_root_cache_path().join(name);
+        let series_path = series_cache_path.join("1");
+        std::fs::create_dir_all(&series_path).expect("failed to create the series cache");
+        std::fs::write(
+            series_path.join(SERIES_MAIN_INFORMATION_FILENAME),
+            "{\n  \"id\": 1\n}",
+        )
+        .expect("failed to write the main information");
+        std::fs::write(series_path.join(EPISODE_LIST_FILENAME), "[\n  1\n]")
+            .expect("failed to write the episode list");
+        series_cache_path
+    }
+
+    #[tokio::test]
+    async fn failed_migration_is_rolled_back_into_files() {
+        let _database = testing::lock_database().await;
+        let series_cache_path = create_series_cache("staging-test");
+        // A cache file that can't be read
+        std::fs::create_dir_all(series_cache_path.join("2").join(EPISODE_LIST_FILENAME))
+            .expect("failed to create the unreadable cache file");
+
+        let staging_tree = DB
+            .open_shared_tree(STAGING_TREE_NAME)
+            .expect("failed to open the staging tree");
+        assert!(stage_cache_files(&series_cache_path, &staging_tree).is_err());
+        rollback_staged_files(staging_tree);
+
+        let series_path = series_cache_path.join("1");
+        let main_info = std::fs::read_to_string(series_path.join(SERIES_MAIN_INFORMATION_FILENAME))
+            .expect("main information not rolled back");
+        assert_eq!(main_info, "{\n  \"id\": 1\n}");
+        assert!(series_path.join(EPISODE_LIST_FILENAME).is_file());
+        assert!(DB
+            .open_shared_tree(STAGING_TREE_NAME)
+            .expect("failed to open the staging tree")
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn staged_files_are_removed() {
+        let _database = testing::lock_database().await;
+        let series_cache_path = create_series_cache("removal-test");
+        let staging_tree = DB
+            .open_shared_tree("series-cache-removal-test")
+            .expect("failed to open the staging tree");
+
+        let staged_files = stage_cache_files(&series_cache_path, &staging_tree)
+            .expect("failed to stage the cache files");
+
+        assert_eq!(staged_files, 2);
+        assert!(!series_cache_path.join("1").exists());
+        let main_info = staging_tree
+            .get(format!(
+                "removal-test/1/{}",
+                SERIES_MAIN_INFORMATION_FILENAME
+            ))
+            .expect("failed to read the staging tree")
+            .expect("main information not staged");
+        assert_eq!(&*main_info, b"{\n  \"id\": 1\n}");
+    }
+}
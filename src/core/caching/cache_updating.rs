@@ -2,90 +2,175 @@
 //!
 //! Since the program keeps cache to avoid performing too many requests,
 //! we need some form of cache updating so that we stay up to date.
+//!
+//! The fetch times of the cache files are compared with the TVmaze updates index, fetching
+//! only the part of the index covering the oldest cache file when possible i.e. the updates
+//! of the past day when the whole cache was fetched less than a day ago.
+//!
+//! While offline, outdated files are kept and marked stale instead of being removed so that
+//! they can still be served.
 
+use std::collections::BTreeMap;
 use std::path;
-use std::time;
 
 use anyhow::Context;
-use tokio::fs;
+use chrono::{DateTime, Duration, TimeZone, Utc};
 use tracing::{error, info, warn};
 
+use super::cache_manifest::MANIFEST;
+use super::cache_store;
+use super::offline;
 use super::series_info_and_episode_list::SeriesInfoAndEpisodeList;
 use super::{CacheFolderType, CACHER};
-use crate::core::api::tv_maze::updates::get_shows_updates_index;
+use crate::core::api::tv_maze::updates::{get_shows_updates_index, LastUpdated};
 use crate::core::database::DB;
 
-async fn get_all_series_cache_directories(
-) -> anyhow::Result<Vec<(String, path::PathBuf, time::Duration)>> {
-    let series_cache_folder = CACHER.get_cache_folder_path(CacheFolderType::Series);
+/// How often the cache is checked for updates
+const UPDATE_INTERVAL_HOURS: i64 = 24;
+/// Kept off the edges of the update windows so that a file fetched right at the start of
+/// a window is still covered by it
+const UPDATE_WINDOW_MARGIN_HOURS: i64 = 1;
+
+/// The cache files of a series together with their fetch times
+struct SeriesCache {
+    series_id: String,
+    path: path::PathBuf,
+    files: Vec<(path::PathBuf, DateTime<Utc>)>,
+}
 
-    let mut read_dir = fs::read_dir(series_cache_folder)
-        .await
-        .context("failed to read series cache directory")?;
+impl SeriesCache {
+    fn get_oldest_fetch_time(&self) -> Option<DateTime<Utc>> {
+        self.files.iter().map(|(_, fetch_time)| *fetch_time).min()
+    }
+}
 
-    let mut series_dirs = vec![];
-    while let Some(dir_entry) = read_dir
-        .next_entry()
+async fn get_all_series_caches() -> anyhow::Result<Vec<SeriesCache>> {
+    let series_cache_folder = CACHER.get_cache_folder_path(CacheFolderType::Series);
+    let cache_files = cache_store::list_files(&series_cache_folder)
         .await
-        .context("failed to read a series directory entry")?
-    {
-        let dir_path = dir_entry.path();
-        let duration = dir_path
-            .metadata()
-            .context("failed to get series cache directory metadata")?
-            .created()
-            .context("failed to get creating time of a series cache directory")?
-            .duration_since(time::SystemTime::UNIX_EPOCH)
-            .context("system clock failure when determining series cache folder creation")?;
-
-        let series_id = dir_path
-            .file_name()
-            .expect("invalid series cache path")
-            .to_string_lossy()
-            .to_string();
-        series_dirs.push((series_id, dir_path, duration))
+        .context("failed to read series cache")?;
+
+    // Grouping the cache files by their series directories
+    let mut series_caches: BTreeMap<path::PathBuf, SeriesCache> = BTreeMap::new();
+    for (file_path, _) in cache_files {
+        let Some(dir_path) = file_path.parent().map(path::Path::to_path_buf) else {
+            continue;
+        };
+        if dir_path == series_cache_folder {
+            continue;
+        }
+
+        // Files without any known fetch time are treated as the oldest possible
+        let fetch_time = MANIFEST
+            .get_fetch_time(&file_path)
+            .unwrap_or(DateTime::<Utc>::MIN_UTC);
+
+        series_caches
+            .entry(dir_path.clone())
+            .or_insert_with(|| SeriesCache {
+                series_id: dir_path
+                    .file_name()
+                    .expect("invalid series cache path")
+                    .to_string_lossy()
+                    .to_string(),
+                path: dir_path,
+                files: vec![],
+            })
+            .files
+            .push((file_path, fetch_time));
+    }
+    Ok(series_caches.into_values().collect())
+}
+
+/// The smallest updates window covering everything fetched since the given time and not
+/// checked by the last update, `None` when only the full updates index covers it
+///
+/// The files fetched before the last update were already compared with the updates index
+/// then, so only the updates since the later of the two times are needed.
+fn get_updates_window(
+    oldest_fetch_time: DateTime<Utc>,
+    last_update: Option<DateTime<Utc>>,
+) -> Option<LastUpdated> {
+    let unchecked_since = last_update.map_or(oldest_fetch_time, |last_update| {
+        oldest_fetch_time.max(last_update)
+    });
+    let cache_age = Utc::now() - unchecked_since + Duration::hours(UPDATE_WINDOW_MARGIN_HOURS);
+
+    if cache_age <= Duration::days(1) {
+        Some(LastUpdated::Day)
+    } else if cache_age <= Duration::weeks(1) {
+        Some(LastUpdated::Week)
+    } else if cache_age <= Duration::days(30) {
+        Some(LastUpdated::Month)
+    } else {
+        None
     }
-    Ok(series_dirs)
 }
 
+/// Updates the cache when a day has passed since the last update
 pub async fn update_cache() -> anyhow::Result<()> {
-    if !should_update().await? {
+    if !should_update() {
         return Ok(());
     }
+    refresh_cache().await
+}
 
+/// Updates the cache regardless of when it was last updated
+pub async fn refresh_cache() -> anyhow::Result<()> {
     info!("updating series cache...");
 
-    let updates_index = get_shows_updates_index(None).await?;
+    let update_start_time = Utc::now();
+    let series_caches = get_all_series_caches().await?;
 
-    let series_cache_directories = get_all_series_cache_directories().await?;
+    let updates_window = match series_caches
+        .iter()
+        .filter_map(SeriesCache::get_oldest_fetch_time)
+        .min()
+    {
+        Some(oldest_fetch_time) => {
+            get_updates_window(oldest_fetch_time, MANIFEST.get_last_update())
+        }
+        // Nothing to update
+        None => Some(LastUpdated::Day),
+    };
+    let is_full_index = updates_window.is_none();
+
+    info!(
+        "fetching the {} updates index",
+        updates_window
+            .as_ref()
+            .map(|window| format!("past {}", window))
+            .unwrap_or_else(|| "full".to_owned())
+    );
+    let updates_index = match get_shows_updates_index(updates_window).await {
+        Ok(updates_index) => updates_index,
+        Err(err) if offline::is_offline() => {
+            warn!("can't update series cache while offline: {}", err);
+            mark_outdated_files_stale(&series_caches);
+            return Ok(());
+        }
+        Err(err) => return Err(err.into()),
+    };
 
-    let mut handles = Vec::with_capacity(series_cache_directories.len());
-    for (series_id, path, cache_timestamp) in series_cache_directories {
-        let time_stamp = updates_index.get(&series_id).copied();
+    let mut handles = Vec::with_capacity(series_caches.len());
+    for series_cache in series_caches {
+        let update_time = updates_index
+            .get(&series_cache.series_id)
+            .and_then(|update_time| Utc.timestamp_opt(*update_time, 0).single());
 
         let handle = tokio::spawn(async move {
-            if let Some(time_stamp) = time_stamp {
-                let update_timestamp = time::Duration::from_secs(time_stamp as u64);
-
-                if update_timestamp > cache_timestamp {
-                    clean_cache_directory(&path).await;
-
-                    // Caching the series if it's in the database
-                    let series_id: u32 = series_id.parse().expect("series id should be parsable");
-                    if DB.get_series(series_id).is_some() {
-                        SeriesInfoAndEpisodeList::cache_series(series_id)
-                            .await
-                            .unwrap_or_else(|err| {
-                                error!("failed to cache series with id '{}': {}", series_id, err)
-                            });
-                    }
+            match update_time {
+                Some(update_time) => update_series_cache(series_cache, update_time).await,
+                // Series missing from the full index no longer exist on TVmaze while the
+                // ones missing from a partial index just have not changed
+                None if is_full_index => {
+                    warn!(
+                        "series cache with id '{}' not in updates, cleaning it anyways",
+                        series_cache.series_id
+                    );
+                    clean_cache(&series_cache.path).await
                 }
-            } else {
-                warn!(
-                    "series cache with id '{}' not in updates, cleaning it anyways",
-                    series_id
-                );
-                clean_cache_directory(&path).await
+                None => {}
             }
         });
 
@@ -96,71 +181,120 @@ pub async fn update_cache() -> anyhow::Result<()> {
         handle.await.expect("failed to join cache updates handles");
     }
 
-    record_last_update().await?;
+    MANIFEST.set_last_update(update_start_time);
+    MANIFEST
+        .save()
+        .await
+        .context("failed to save the cache manifest")?;
 
     info!("updating series cache complete!");
 
     Ok(())
 }
 
-const LAST_UPDATE_FILENAME: &str = "last-cache-update";
-
-fn get_last_update_filepath() -> path::PathBuf {
-    let mut last_update_file = CACHER.get_root_cache_path().to_owned();
-    last_update_file.push(LAST_UPDATE_FILENAME);
-    last_update_file
-}
-
-fn duration_since_epoch() -> anyhow::Result<time::Duration> {
-    time::SystemTime::now()
-        .duration_since(time::UNIX_EPOCH)
-        .context("system clock failure when determining current time")
+/// Refetches the cache of the series regardless of the updates index
+pub async fn refresh_series_cache(series_id: u32) -> anyhow::Result<()> {
+    clean_cache(&CACHER.get_series_cache_folder_path(series_id)).await;
+    SeriesInfoAndEpisodeList::cache_series(series_id).await?;
+    MANIFEST
+        .save()
+        .await
+        .context("failed to save the cache manifest")
 }
 
-/// Whether cache should be updated or not
+/// Removes the cache files fetched before the series was last updated, caching the series
+/// again when it's in the database
 ///
-/// Checks if a day has passed since the last cache update and returns `true`,
-/// Otherwise the opposite
-async fn should_update() -> anyhow::Result<bool> {
-    let last_update_file = get_last_update_filepath();
-
-    let current_timestamp = duration_since_epoch()?;
-
-    let last_update_timestamp: u64 = match fs::read_to_string(last_update_file).await {
-        Ok(content) => match content.parse() {
-            Ok(val) => val,
-            Err(err) => {
-                error!("failed to parse 'last-cache-update' file: {}", err);
-                warn!("assuming at least a day has passed since last cache update");
-                return Ok(true);
+/// The outdated files are only marked stale while offline.
+async fn update_series_cache(series_cache: SeriesCache, update_time: DateTime<Utc>) {
+    let mut is_stale = false;
+    for (file_path, fetch_time) in &series_cache.files {
+        if *fetch_time < update_time {
+            is_stale = true;
+            if offline::is_offline() {
+                MANIFEST.mark_stale(file_path);
+            } else {
+                clean_cache(file_path).await;
             }
-        },
-        Err(err) => {
-            error!("could not read 'last-cache-update' file: {}", err);
-            warn!("assuming at least a day has passed since last cache update");
-            return Ok(true);
         }
-    };
+    }
 
-    let last_update_timestamp = time::Duration::from_secs(last_update_timestamp);
+    if !is_stale || offline::is_offline() {
+        return;
+    }
 
-    Ok((current_timestamp - last_update_timestamp) > time::Duration::from_secs(60 * 60 * 24))
+    let series_id: u32 = series_cache
+        .series_id
+        .parse()
+        .expect("series id should be parsable");
+    if let Ok(Some(_)) = DB.get_series(series_id) {
+        SeriesInfoAndEpisodeList::cache_series(series_id)
+            .await
+            .unwrap_or_else(|err| {
+                error!("failed to cache series with id '{}': {}", series_id, err)
+            });
+    }
 }
 
-async fn record_last_update() -> anyhow::Result<()> {
-    let last_update_file = get_last_update_filepath();
-
-    let current_timestamp = duration_since_epoch()?;
+/// Marks the files that have not been checked for updates for longer than the update interval
+/// stale, used when the updates index can't be fetched
+fn mark_outdated_files_stale(series_caches: &[SeriesCache]) {
+    let outdated_time = Utc::now() - Duration::hours(UPDATE_INTERVAL_HOURS);
+    series_caches
+        .iter()
+        .flat_map(|series_cache| &series_cache.files)
+        .filter(|(_, fetch_time)| *fetch_time < outdated_time)
+        .for_each(|(file_path, _)| MANIFEST.mark_stale(file_path));
+}
 
-    fs::write(last_update_file, current_timestamp.as_secs().to_string())
-        .await
-        .context("failed to write 'last-cache-update' file")
+/// Whether cache should be updated or not
+///
+/// Checks if a day has passed since the last cache update and returns `true`,
+/// Otherwise the opposite
+fn should_update() -> bool {
+    match MANIFEST.get_last_update() {
+        Some(last_update) => Utc::now() - last_update > Duration::hours(UPDATE_INTERVAL_HOURS),
+        None => {
+            warn!("no cache update recorded, assuming at least a day has passed since last cache update");
+            true
+        }
+    }
 }
 
-/// Removes the directory and it's contents at the given path
-async fn clean_cache_directory(path: &path::Path) {
+/// Removes the cache file or the directory and it's contents at the given path
+async fn clean_cache(path: &path::Path) {
     info!("cleaning cache: {}", path.display());
-    fs::remove_dir_all(path)
+    cache_store::remove(path)
         .await
         .unwrap_or_else(|err| error!("failed to clean cache for path {}: {}", path.display(), err));
+    MANIFEST.remove(path);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn updates_window_starts_at_the_last_update() {
+        let days_ago = |days| Utc::now() - Duration::days(days);
+
+        assert_eq!(
+            get_updates_window(days_ago(3), None),
+            Some(LastUpdated::Week)
+        );
+        assert_eq!(
+            get_updates_window(days_ago(60), Some(days_ago(2))),
+            Some(LastUpdated::Week)
+        );
+        assert_eq!(
+            get_updates_window(days_ago(60), Some(Utc::now())),
+            Some(LastUpdated::Day)
+        );
+        // Files fetched after the last update are not covered by it
+        assert_eq!(
+            get_updates_window(days_ago(20), Some(days_ago(40))),
+            Some(LastUpdated::Month)
+        );
+        assert_eq!(get_updates_window(days_ago(60), Some(days_ago(45))), None);
+    }
 }
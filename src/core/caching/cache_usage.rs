@@ -0,0 +1,324 @@
+//! # Cache usage
+//!
+//! Reports how much space every category of the cache takes, purges them and keeps the
+//! whole cache under the size limit of the settings.
+//!
+//! When the cache grows past the limit, the least recently used files are evicted first
+//! going by the last use times in the cache manifest. The cache of tracked series and the
+//! images they reference i.e. their posters and cast photos are never evicted as they would
+//! be fetched back right away.
+
+use std::collections::HashSet;
+use std::path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use tokio::fs;
+use tracing::{error, info, warn};
+
+use super::cache_manifest::MANIFEST;
+use super::cache_store;
+use super::tv_schedule::full_schedule;
+use super::{get_image_cache_path, CacheFolderType, CACHER};
+use crate::core::database::DB;
+use crate::core::settings_config::SETTINGS;
+
+/// How long to wait for more cache writes before checking the size limit
+const ENFORCEMENT_DELAY: Duration = Duration::from_secs(30);
+
+static ENFORCEMENT_PENDING: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CacheCategory {
+    /// The json files of the series i.e. their main information and episode lists
+    Series,
+    Images,
+    /// The schedule of all the future episodes known to TVmaze
+    FullSchedule,
+}
+
+pub const ALL_CACHE_CATEGORIES: [CacheCategory; 3] = [
+    CacheCategory::Series,
+    CacheCategory::Images,
+    CacheCategory::FullSchedule,
+];
+
+impl CacheCategory {
+    fn get_path(&self) -> path::PathBuf {
+        match self {
+            CacheCategory::Series => CACHER.get_cache_folder_path(CacheFolderType::Series),
+            CacheCategory::Images => CACHER.get_cache_folder_path(CacheFolderType::Images),
+            CacheCategory::FullSchedule => full_schedule::get_cache_path(),
+        }
+    }
+}
+
+impl std::fmt::Display for CacheCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str = match self {
+            CacheCategory::Series => "Series data",
+            CacheCategory::Images => "Images",
+            CacheCategory::FullSchedule => "Full schedule",
+        };
+
+        write!(f, "{}", str)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CategoryUsage {
+    pub category: CacheCategory,
+    /// The size of all the files of the category in bytes
+    pub size: u64,
+    pub files: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct EvictionReport {
+    pub evicted_files: usize,
+    pub freed_bytes: u64,
+}
+
+struct CacheFile {
+    path: path::PathBuf,
+    size: u64,
+    last_use: DateTime<Utc>,
+}
+
+/// The size of the cache limit in bytes, `None` when there is no limit
+pub fn get_cache_limit() -> Option<u64> {
+    let max_size_mb = SETTINGS
+        .read()
+        .expect("failed to read settings")
+        .get_current_settings()
+        .cache
+        .max_size_mb;
+
+    (max_size_mb != 0).then(|| max_size_mb * 1024 * 1024)
+}
+
+pub async fn get_cache_usage() -> std::io::Result<Vec<CategoryUsage>> {
+    let mut cache_usage = Vec::with_capacity(ALL_CACHE_CATEGORIES.len());
+    for category in ALL_CACHE_CATEGORIES {
+        let files = get_cache_files(&category.get_path()).await?;
+        cache_usage.push(CategoryUsage {
+            category,
+            size: files.iter().map(|file| file.size).sum(),
+            files: files.len(),
+        });
+    }
+    Ok(cache_usage)
+}
+
+/// Removes all the cache of the given category
+pub async fn purge_cache(category: CacheCategory) -> anyhow::Result<()> {
+    let cache_path = category.get_path();
+    info!("purging cache: {}", cache_path.display());
+
+    match cache_store::remove(&cache_path).await {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+            return Err(err)
+                .with_context(|| format!("failed to purge cache at {}", cache_path.display()))
+        }
+        _ => {}
+    }
+
+    MANIFEST.remove(&cache_path);
+    MANIFEST
+        .save()
+        .await
+        .context("failed to save the cache manifest")
+}
+
+/// Checks the size limit of the cache a little after the cache was written, so that caching
+/// many files at once results in a single check
+pub fn schedule_cache_limit_enforcement() {
+    if ENFORCEMENT_PENDING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => {
+            handle.spawn(async {
+                tokio::time::sleep(ENFORCEMENT_DELAY).await;
+                ENFORCEMENT_PENDING.store(false, Ordering::SeqCst);
+                if let Err(err) = enforce_cache_limit().await {
+                    error!("failed to enforce the cache size limit: {}", err);
+                }
+            });
+        }
+        Err(_) => {
+            warn!("no runtime to enforce the cache size limit on");
+            ENFORCEMENT_PENDING.store(false, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Evicts the least recently used cache files until the cache is under the size limit
+pub async fn enforce_cache_limit() -> anyhow::Result<EvictionReport> {
+    let mut report = EvictionReport::default();
+    let Some(cache_limit) = get_cache_limit() else {
+        return Ok(report);
+    };
+
+    let mut cache_files = vec![];
+    for category in ALL_CACHE_CATEGORIES {
+        cache_files.extend(
+            get_cache_files(&category.get_path())
+                .await
+                .context("failed to read the cache")?,
+        );
+    }
+
+    let mut cache_size: u64 = cache_files.iter().map(|file| file.size).sum();
+    if cache_size <= cache_limit {
+        return Ok(report);
+    }
+
+    info!(
+        "cache size of {} exceeds the limit of {}, evicting least recently used files",
+        format_size(cache_size),
+        format_size(cache_limit)
+    );
+
+    let protected_paths = get_protected_paths().await?;
+    let mut evictable_files: Vec<CacheFile> = cache_files
+        .into_iter()
+        .filter(|file| {
+            // Series cache files are protected by their directories
+            !(protected_paths.contains(&file.path)
+                || file
+                    .path
+                    .parent()
+                    .map(|parent_path| protected_paths.contains(parent_path))
+                    .unwrap_or(false))
+        })
+        .collect();
+    evictable_files.sort_by_key(|file| file.last_use);
+
+    for file in evictable_files {
+        if cache_size <= cache_limit {
+            break;
+        }
+
+        if let Err(err) = cache_store::remove(&file.path).await {
+            error!("failed to evict cache '{}': {}", file.path.display(), err);
+            continue;
+        }
+        MANIFEST.remove(&file.path);
+
+        // Cleaning up the directory of a series when it's last file is evicted
+        if let Some(parent_path) = file.path.parent() {
+            if parent_path.starts_with(CacheCategory::Series.get_path()) {
+                let _ = fs::remove_dir(parent_path).await;
+            }
+        }
+
+        cache_size -= file.size;
+        report.evicted_files += 1;
+        report.freed_bytes += file.size;
+    }
+
+    if cache_size > cache_limit {
+        warn!(
+            "cache size of {} still exceeds the limit after evicting everything but the tracked series",
+            format_size(cache_size)
+        );
+    }
+
+    MANIFEST
+        .save()
+        .await
+        .context("failed to save the cache manifest")?;
+
+    info!(
+        "evicted {} cache files freeing {}",
+        report.evicted_files,
+        format_size(report.freed_bytes)
+    );
+    Ok(report)
+}
+
+/// The cache directories of the tracked series and the paths of the images they reference
+async fn get_protected_paths() -> anyhow::Result<HashSet<path::PathBuf>> {
+    let tracked_series_ids: Vec<u32> = DB
+        .get_ids_and_series()?
+        .into_iter()
+        .filter(|(_, series)| series.is_tracked())
+        .filter_map(|(series_id, _)| series_id.parse().ok())
+        .collect();
+
+    let mut protected_paths = HashSet::new();
+    for series_id in tracked_series_ids {
+        let series_cache_path = CACHER.get_series_cache_folder_path(series_id);
+
+        for file in get_cache_files(&series_cache_path).await? {
+            // Reading the files from the store directly as reading them through the cache
+            // would count as a use
+            let Ok(json_string) = cache_store::read(&file.path).await else {
+                continue;
+            };
+            let Ok(json) = serde_json::from_str::<serde_json::Value>(&json_string) else {
+                continue;
+            };
+            let mut image_urls = vec![];
+            collect_urls(&json, &mut image_urls);
+            protected_paths.extend(image_urls.into_iter().map(get_image_cache_path));
+        }
+
+        protected_paths.insert(series_cache_path);
+    }
+    Ok(protected_paths)
+}
+
+/// Collects all the urls in the json value, the image urls being spread across many fields
+/// i.e. the posters of the main information and the photos of the cast
+fn collect_urls<'a>(json: &'a serde_json::Value, urls: &mut Vec<&'a str>) {
+    match json {
+        serde_json::Value::String(string) if string.starts_with("http") => urls.push(string),
+        serde_json::Value::Array(values) => {
+            values.iter().for_each(|value| collect_urls(value, urls))
+        }
+        serde_json::Value::Object(map) => map.values().for_each(|value| collect_urls(value, urls)),
+        _ => {}
+    }
+}
+
+/// Lists all the cache files at the given path together with their last use times
+async fn get_cache_files(cache_path: &path::Path) -> std::io::Result<Vec<CacheFile>> {
+    Ok(cache_store::list_files(cache_path)
+        .await?
+        .into_iter()
+        .map(|(path, size)| {
+            let last_use = MANIFEST
+                .get_last_use(&path)
+                .unwrap_or(DateTime::<Utc>::MIN_UTC);
+            CacheFile {
+                path,
+                size,
+                last_use,
+            }
+        })
+        .collect())
+}
+
+/// Formats the amount of bytes in the largest unit it has at least one of i.e. `1.5 MB`
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+
+    let mut size = bytes as f64;
+    let mut unit_index = 0;
+    while size >= 1024.0 && unit_index < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit_index += 1;
+    }
+
+    if unit_index == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit_index])
+    }
+}
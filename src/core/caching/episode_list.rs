@@ -1,9 +1,10 @@
-use std::{collections::HashSet, io::ErrorKind};
+use std::collections::HashSet;
 
 use chrono::{Local, Utc};
 use tracing::info;
 
-use super::{read_cache, write_cache, CacheFilePath};
+use super::cache_manifest::MANIFEST;
+use super::{read_cache_or_load, CacheFilePath};
 use crate::core::api::tv_maze::deserialize_json;
 pub use crate::core::api::tv_maze::episodes_information::EpisodeReleaseTime;
 use crate::core::api::tv_maze::episodes_information::{get_episode_list, Episode};
@@ -14,6 +15,8 @@ use crate::core::{caching::CACHER, database};
 pub struct EpisodeList {
     series_id: u32,
     episodes: Vec<Episode>,
+    /// Whether the episodes come from an outdated cache that could not be refreshed
+    stale: bool,
 }
 
 impl EpisodeList {
@@ -21,38 +24,39 @@ impl EpisodeList {
         let episodes_list_path =
             CACHER.get_cache_file_path(CacheFilePath::SeriesEpisodeList(series_id));
 
-        let json_string = match read_cache(&episodes_list_path).await {
-            Ok(json_string) => json_string,
-            Err(err) => {
-                info!("falling back online for 'episode list' for series id: {series_id}");
-                let (episodes, json_string) = get_episode_list(series_id).await?;
-
-                if err.kind() == ErrorKind::NotFound {
-                    write_cache(&json_string, &episodes_list_path).await;
-                }
-                return Ok(Self {
-                    series_id,
-                    episodes,
-                });
-            }
-        };
+        let json_string = read_cache_or_load(&episodes_list_path, || async {
+            info!("falling back online for 'episode list' for series id: {series_id}");
+            get_episode_list(series_id)
+                .await
+                .map(|(_, json_string)| json_string)
+        })
+        .await?;
 
         let episodes = deserialize_json::<Vec<Episode>>(&json_string)?;
         Ok(Self {
             series_id,
             episodes,
+            stale: MANIFEST.is_stale(&episodes_list_path),
         })
     }
 
     /// Constructs `EpisodeList` from it's cache file contents directly
     pub fn with_cache(series_id: u32, cache_str: &str) -> Result<Self, ApiError> {
         let episodes = deserialize_json::<Vec<Episode>>(cache_str)?;
+        let episodes_list_path =
+            CACHER.get_cache_file_path(CacheFilePath::SeriesEpisodeList(series_id));
         Ok(Self {
             series_id,
             episodes,
+            stale: MANIFEST.is_stale(&episodes_list_path),
         })
     }
 
+    /// Whether the episodes are outdated, being served from the cache while offline
+    pub fn is_stale(&self) -> bool {
+        self.stale
+    }
+
     pub fn get_episode(&self, season_number: u32, episode_number: u32) -> Option<&Episode> {
         self.episodes.iter().find(|episode| {
             (episode.season == season_number) && (episode.number == Some(episode_number))
@@ -148,6 +152,14 @@ impl EpisodeList {
             .find(|episode| Self::is_episode_watchable(episode) == Some(false))
     }
 
+    /// Returns all the episodes airing after the current time
+    pub fn get_future_episodes(&self) -> Vec<&Episode> {
+        self.episodes
+            .iter()
+            .filter(|episode| Self::is_episode_watchable(episode) == Some(false))
+            .collect()
+    }
+
     /// Returns the next episode to air and it's release time
     pub fn get_next_episode_to_air_and_time(&self) -> Option<(&Episode, EpisodeReleaseTime)> {
         let next_episode = self.get_next_episode_to_air()?;
@@ -156,9 +168,7 @@ impl EpisodeList {
     }
 
     pub fn get_next_episode_to_watch(&self) -> Option<&Episode> {
-        let series = database::DB
-            .get_series(self.series_id)
-            .expect("series not in the database");
+        let series = database::DB.get_series(self.series_id).ok().flatten()?;
 
         self.get_all_episodes()
             .iter()
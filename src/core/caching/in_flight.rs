@@ -0,0 +1,116 @@
+//! # In-flight requests
+//!
+//! Many widgets load the same data at once i.e. the posters and the series information of
+//! the same series, each of them falling back online on a cache miss. The loads are keyed by
+//! their cache file paths or urls so that concurrent callers wait for the load already in
+//! flight instead of requesting the same data again.
+//!
+//! A failed load is not shared as the errors can't be cloned, the waiting callers loading the
+//! data themselves instead.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+
+use tokio::sync::watch;
+
+/// The outcome of a load, `None` while it's in flight and `Some(None)` when it failed
+type LoadOutcome<T> = Option<Option<T>>;
+
+enum Role<T> {
+    /// Runs the load and shares it's outcome
+    Loader(watch::Sender<LoadOutcome<T>>),
+    /// Waits for the outcome of the load in flight
+    Waiter(watch::Receiver<LoadOutcome<T>>),
+}
+
+pub struct InFlightRequests<T> {
+    requests: Mutex<HashMap<String, watch::Receiver<LoadOutcome<T>>>>,
+}
+
+impl<T: Clone> InFlightRequests<T> {
+    pub fn new() -> Self {
+        Self {
+            requests: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Runs the load unless a load with the same key is in flight, sharing it's outcome instead
+    pub async fn coalesce<E, F, Fut>(&self, key: &str, load: F) -> Result<T, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let role = {
+            let mut requests = self
+                .requests
+                .lock()
+                .expect("failed to lock in-flight requests");
+            match requests.get(key) {
+                Some(receiver) => Role::Waiter(receiver.clone()),
+                None => {
+                    let (sender, receiver) = watch::channel(None);
+                    requests.insert(key.to_owned(), receiver);
+                    Role::Loader(sender)
+                }
+            }
+        };
+
+        match role {
+            Role::Loader(sender) => self.run_load(key, sender, load).await,
+            Role::Waiter(mut receiver) => {
+                // The sender is dropped without an outcome when the load was cancelled
+                let shared_value = receiver
+                    .wait_for(Option::is_some)
+                    .await
+                    .ok()
+                    .and_then(|outcome| outcome.clone().flatten());
+                match shared_value {
+                    Some(value) => Ok(value),
+                    None => load().await,
+                }
+            }
+        }
+    }
+
+    async fn run_load<E, F, Fut>(
+        &self,
+        key: &str,
+        sender: watch::Sender<LoadOutcome<T>>,
+        load: F,
+    ) -> Result<T, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        // Removing the request even when the load is cancelled midway
+        let _request_guard = RequestGuard {
+            requests: &self.requests,
+            key,
+        };
+
+        let result = load().await;
+        sender.send_replace(Some(result.as_ref().ok().cloned()));
+        result
+    }
+}
+
+impl<T: Clone> Default for InFlightRequests<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct RequestGuard<'a, T> {
+    requests: &'a Mutex<HashMap<String, watch::Receiver<LoadOutcome<T>>>>,
+    key: &'a str,
+}
+
+impl<T> Drop for RequestGuard<'_, T> {
+    fn drop(&mut self) {
+        self.requests
+            .lock()
+            .expect("failed to lock in-flight requests")
+            .remove(self.key);
+    }
+}
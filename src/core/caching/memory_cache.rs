@@ -0,0 +1,92 @@
+//! # Memory cache
+//!
+//! A least recently used layer in memory on top of the cache store, sparing reading and
+//! decoding the same cache files again when the same series are shown in many places. The
+//! layer is bounded by the total size of the cached data rather than the amount of files
+//! as images are far larger than the json files.
+
+use std::path;
+use std::sync::Mutex;
+
+use bytes::Bytes;
+use lazy_static::lazy_static;
+use lru::LruCache;
+
+/// The total size of the data kept in memory
+const MEMORY_CACHE_CAPACITY_BYTES: usize = 64 * 1024 * 1024;
+
+lazy_static! {
+    pub static ref MEMORY_CACHE: MemoryCache = MemoryCache::new(MEMORY_CACHE_CAPACITY_BYTES);
+}
+
+pub struct MemoryCache {
+    inner: Mutex<Inner>,
+    capacity_bytes: usize,
+}
+
+struct Inner {
+    entries: LruCache<path::PathBuf, Bytes>,
+    size_bytes: usize,
+}
+
+impl MemoryCache {
+    fn new(capacity_bytes: usize) -> Self {
+        Self {
+            inner: Mutex::new(Inner {
+                entries: LruCache::unbounded(),
+                size_bytes: 0,
+            }),
+            capacity_bytes,
+        }
+    }
+
+    pub fn get(&self, cache_filepath: &path::Path) -> Option<Bytes> {
+        self.inner
+            .lock()
+            .expect("failed to lock the memory cache")
+            .entries
+            .get(cache_filepath)
+            .cloned()
+    }
+
+    /// Keeps the cache data in memory, evicting the least recently used data when over the
+    /// capacity
+    pub fn insert(&self, cache_filepath: &path::Path, cache_data: Bytes) {
+        // Data this large would evict most of the memory cache by itself
+        if cache_data.len() > self.capacity_bytes / 4 {
+            self.remove(cache_filepath);
+            return;
+        }
+
+        let mut inner = self.inner.lock().expect("failed to lock the memory cache");
+        inner.size_bytes += cache_data.len();
+        if let Some(replaced_data) = inner.entries.put(cache_filepath.to_path_buf(), cache_data) {
+            inner.size_bytes -= replaced_data.len();
+        }
+
+        while inner.size_bytes > self.capacity_bytes {
+            match inner.entries.pop_lru() {
+                Some((_, evicted_data)) => inner.size_bytes -= evicted_data.len(),
+                None => break,
+            }
+        }
+    }
+
+    /// Forgets the cache file or all the cache files in the directory at the given path
+    pub fn remove(&self, cache_path: &path::Path) {
+        let mut inner = self.inner.lock().expect("failed to lock the memory cache");
+        let removed_paths: Vec<path::PathBuf> = inner
+            .entries
+            .iter()
+            .map(|(cache_filepath, _)| cache_filepath)
+            .filter(|cache_filepath| cache_filepath.starts_with(cache_path))
+            .cloned()
+            .collect();
+
+        for removed_path in removed_paths {
+            if let Some(removed_data) = inner.entries.pop(&removed_path) {
+                inner.size_bytes -= removed_data.len();
+            }
+        }
+    }
+}
@@ -0,0 +1,219 @@
+//! # Offline mode
+//!
+//! The connectivity to TVmaze is tracked from the outcome of the requests of the shared http
+//! client. While offline, requests fail right away instead of being retried, whatever is
+//! cached keeps being served, the cache files that could not be updated being marked stale in
+//! the cache manifest, and a probe checks for the connectivity to come back.
+//!
+//! Watching episodes requires checking that they are released, which can't be done offline
+//! when the episode list is not cached. Such episodes are queued in the database and
+//! validated once the connectivity is back.
+
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use tokio::sync::watch;
+use tracing::{error, info, warn};
+
+use super::episode_list::EpisodeList;
+use crate::core::api::tv_maze;
+use crate::core::database::{get_episode_key, parse_episode_key, DatabaseError, DB};
+
+const PENDING_VALIDATIONS_TREE_NAME: &str = "pending-validations";
+/// How often the connectivity is checked while offline
+const PROBE_INTERVAL: Duration = Duration::from_secs(30);
+/// A small api resource requested to check the connectivity
+const PROBE_PATH: &str = "/shows/1";
+
+const UNKNOWN: u8 = 0;
+const ONLINE: u8 = 1;
+const OFFLINE: u8 = 2;
+
+static CONNECTIVITY: AtomicU8 = AtomicU8::new(UNKNOWN);
+
+lazy_static! {
+    static ref STATUS_SENDER: watch::Sender<ConnectivityStatus> =
+        watch::channel(ConnectivityStatus::default()).0;
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConnectivityStatus {
+    pub is_offline: bool,
+    /// The amount of watched episodes waiting for the connectivity to be validated
+    pub pending_validations: usize,
+}
+
+pub fn is_offline() -> bool {
+    CONNECTIVITY.load(Ordering::SeqCst) == OFFLINE
+}
+
+/// Receives the connectivity status whenever it changes
+pub fn subscribe() -> watch::Receiver<ConnectivityStatus> {
+    let receiver = STATUS_SENDER.subscribe();
+    send_status();
+    receiver
+}
+
+/// Records a request that reached TVmaze
+///
+/// Coming back online, or making the first request, validates the queued episodes.
+pub fn report_online() {
+    let previous_connectivity = CONNECTIVITY.swap(ONLINE, Ordering::SeqCst);
+    if previous_connectivity == ONLINE {
+        return;
+    }
+
+    if previous_connectivity == OFFLINE {
+        info!("connectivity is back, leaving offline mode");
+    }
+    send_status();
+
+    if let Ok(handle) = tokio::runtime::Handle::try_current() {
+        handle.spawn(async {
+            if let Err(err) = validate_pending_episodes().await {
+                error!("failed to validate the queued episodes: {}", err);
+            }
+        });
+    }
+}
+
+/// Records a request that could not reach TVmaze
+pub fn report_offline() {
+    if CONNECTIVITY.swap(OFFLINE, Ordering::SeqCst) == OFFLINE {
+        return;
+    }
+
+    warn!("could not reach TVmaze, entering offline mode");
+    send_status();
+
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => {
+            handle.spawn(probe_connectivity());
+        }
+        Err(_) => warn!("no runtime to check the connectivity on"),
+    }
+}
+
+/// Requests TVmaze until the connectivity is back, the http client reporting it
+async fn probe_connectivity() {
+    while is_offline() {
+        tokio::time::sleep(PROBE_INTERVAL).await;
+        let url = format!("{}{}", tv_maze::get_base_url(), PROBE_PATH);
+        // The outcome is reported by the http client itself
+        let _ = tv_maze::http_client::HTTP_CLIENT.get(&url).await;
+    }
+}
+
+fn send_status() {
+    let status = ConnectivityStatus {
+        is_offline: is_offline(),
+        pending_validations: get_pending_episodes()
+            .map(|episodes| episodes.len())
+            .unwrap_or(0),
+    };
+    STATUS_SENDER.send_if_modified(|current_status| {
+        let is_modified = *current_status != status;
+        *current_status = status;
+        is_modified
+    });
+}
+
+/// Queues the episode to be marked watched once it can be validated
+pub fn queue_episode_validation(series_id: u32, season: u32, episode: u32) {
+    let result = DB
+        .open_profile_tree(PENDING_VALIDATIONS_TREE_NAME)
+        .and_then(|tree| {
+            let watched_at =
+                bincode::serialize(&Utc::now()).map_err(DatabaseError::Serialization)?;
+            tree.insert(get_episode_key(series_id, season, episode), watched_at)
+                .map_err(DatabaseError::Sled)
+        });
+
+    match result {
+        Ok(_) => info!(
+            "queued episode {} of season {} of series with id '{}' for validation",
+            episode, season, series_id
+        ),
+        Err(err) => error!("failed to queue an episode for validation: {}", err),
+    }
+    send_status();
+}
+
+/// A watched episode waiting to be validated
+struct PendingEpisode {
+    series_id: u32,
+    season: u32,
+    episode: u32,
+    watched_at: DateTime<Utc>,
+}
+
+fn get_pending_episodes() -> Result<Vec<PendingEpisode>, DatabaseError> {
+    let tree = DB.open_profile_tree(PENDING_VALIDATIONS_TREE_NAME)?;
+
+    let mut pending_episodes = vec![];
+    for record in tree.iter() {
+        let (key, watched_at) = record.map_err(DatabaseError::Sled)?;
+        let Some((series_id, season, episode)) = parse_episode_key(&key) else {
+            continue;
+        };
+        pending_episodes.push(PendingEpisode {
+            series_id,
+            season,
+            episode,
+            watched_at: bincode::deserialize(&watched_at).map_err(DatabaseError::Serialization)?,
+        });
+    }
+    Ok(pending_episodes)
+}
+
+/// Marks the queued episodes watched when they are released, stopping at the first episode
+/// list that still can't be fetched
+async fn validate_pending_episodes() -> Result<(), DatabaseError> {
+    let pending_episodes = get_pending_episodes()?;
+    if pending_episodes.is_empty() {
+        return Ok(());
+    }
+
+    let tree = DB.open_profile_tree(PENDING_VALIDATIONS_TREE_NAME)?;
+    for pending_episode in pending_episodes {
+        let episode_list = match EpisodeList::new(pending_episode.series_id).await {
+            Ok(episode_list) => episode_list,
+            Err(err) => {
+                warn!("queued episodes still can't be validated: {}", err);
+                break;
+            }
+        };
+
+        let is_watchable = episode_list
+            .get_episode(pending_episode.season, pending_episode.episode)
+            .and_then(EpisodeList::is_episode_watchable)
+            == Some(true);
+
+        if is_watchable {
+            if let Some(mut series) = DB.get_series(pending_episode.series_id)? {
+                series.add_episode_unchecked_with_time(
+                    pending_episode.season,
+                    pending_episode.episode,
+                    pending_episode.watched_at,
+                );
+            }
+        } else {
+            warn!(
+                "dropping queued episode {} of season {} of series with id '{}' as it's not released",
+                pending_episode.episode, pending_episode.season, pending_episode.series_id
+            );
+        }
+
+        tree.remove(get_episode_key(
+            pending_episode.series_id,
+            pending_episode.season,
+            pending_episode.episode,
+        ))
+        .map_err(DatabaseError::Sled)?;
+    }
+
+    send_status();
+    Ok(())
+}
@@ -1,12 +1,26 @@
-use tokio::fs;
-use tokio::sync::mpsc;
+use std::sync::Arc;
+
+use anyhow::Context;
+use lazy_static::lazy_static;
+use tokio::sync::{mpsc, Semaphore};
 use tracing::info;
 
+use super::cache_manifest::MANIFEST;
 use super::episode_list::EpisodeList;
+use super::in_flight::InFlightRequests;
 use super::series_information::get_series_main_info_with_id;
 use super::CACHER;
+use super::{cache_store, cache_usage};
 use crate::core::api::tv_maze::series_information::get_series_info_and_episode_list;
 
+/// The maximum amount of series cached at the same time
+const MAX_CONCURRENT_CACHING: usize = 10;
+
+lazy_static! {
+    /// The series being cached keyed by their cache directories
+    static ref SERIES_CACHING: InFlightRequests<()> = InFlightRequests::new();
+}
+
 #[derive(Copy, Clone)]
 enum MissingCache {
     Both,
@@ -38,23 +52,26 @@ impl SeriesInfoAndEpisodeList {
 
     /// Caches `SeriesMainInformation` and `EpisodeList` for all the series supplied via their ids
     pub async fn run_full_caching(&self, report_progress: bool) -> anyhow::Result<()> {
-        let handles: Vec<_> = self
-            .series_ids
-            .iter()
-            .map(|series_id| {
-                let series_id = *series_id;
-                let sender = self.completion_signal_sender.clone();
-                tokio::spawn(async move {
-                    let res = Self::cache_series(series_id).await;
-                    if report_progress {
-                        sender
-                            .send(res)
-                            .await
-                            .expect("failed to send completion signal to the receiver");
-                    }
-                })
-            })
-            .collect();
+        // Bounding the amount of series being cached at once, the requests themselves are
+        // paced by the shared TVmaze http client
+        let semaphore = Arc::new(Semaphore::new(MAX_CONCURRENT_CACHING));
+
+        let mut handles = Vec::with_capacity(self.series_ids.len());
+        for series_id in self.series_ids.iter().copied() {
+            let permit = semaphore.clone().acquire_owned().await?;
+            let sender = self.completion_signal_sender.clone();
+
+            handles.push(tokio::spawn(async move {
+                let res = Self::cache_series(series_id).await;
+                drop(permit);
+                if report_progress {
+                    sender
+                        .send(res)
+                        .await
+                        .expect("failed to send completion signal to the receiver");
+                }
+            }));
+        }
 
         for handle in handles {
             handle.await?;
@@ -63,6 +80,15 @@ impl SeriesInfoAndEpisodeList {
     }
 
     pub async fn cache_series(series_id: u32) -> anyhow::Result<()> {
+        let series_cache_folder = CACHER.get_series_cache_folder_path(series_id);
+        SERIES_CACHING
+            .coalesce(&series_cache_folder.to_string_lossy(), || {
+                Self::cache_missing(series_id)
+            })
+            .await
+    }
+
+    async fn cache_missing(series_id: u32) -> anyhow::Result<()> {
         match Self::get_missing_cache(series_id).await? {
             MissingCache::None => {}
             MissingCache::Series => Self::cache_series_information(series_id).await?,
@@ -73,49 +99,31 @@ impl SeriesInfoAndEpisodeList {
                     .get_episode_list()
                     .expect("series info should have embedded episode list");
 
-                let handle_1 = tokio::spawn({
-                    let series_cache_path = CACHER.get_cache_file_path(
-                        super::CacheFilePath::SeriesMainInformation(series_id),
-                    );
-
-                    let mut series_cache_folder = series_cache_path.clone();
-                    series_cache_folder.pop();
-
-                    fs::create_dir_all(&series_cache_folder)
-                        .await
-                        .expect("failed to create series cache directory");
-
-                    info!("caching 'series information' for series id {}", series_id);
-
-                    fs::write(
-                        series_cache_path,
-                        serde_json::to_string_pretty(&series_info)
-                            .expect("series information should be serializable"),
-                    )
-                });
-
-                let handle_2 = tokio::spawn({
-                    let episode_cache_path = CACHER
-                        .get_cache_file_path(super::CacheFilePath::SeriesEpisodeList(series_id));
-
-                    let mut episodes_cache_folder = episode_cache_path.clone();
-                    episodes_cache_folder.pop();
-
-                    fs::create_dir_all(&episodes_cache_folder)
-                        .await
-                        .expect("failed to create series cache directory");
-
-                    info!("caching 'episode list' for series id {}", series_id);
-
-                    fs::write(
-                        episode_cache_path,
-                        serde_json::to_string_pretty(&episode_list)
-                            .expect("series information should be serializable"),
-                    )
-                });
-
-                handle_1.await??;
-                handle_2.await??;
+                let series_cache_path = CACHER
+                    .get_cache_file_path(super::CacheFilePath::SeriesMainInformation(series_id));
+                let episode_cache_path =
+                    CACHER.get_cache_file_path(super::CacheFilePath::SeriesEpisodeList(series_id));
+
+                info!(
+                    "caching 'series information' and 'episode list' for series id {}",
+                    series_id
+                );
+
+                let series_info = serde_json::to_string_pretty(&series_info)
+                    .expect("series information should be serializable");
+                let episode_list = serde_json::to_string_pretty(&episode_list)
+                    .expect("episode list should be serializable");
+
+                let (series_info_result, episode_list_result) = tokio::join!(
+                    cache_store::write(&series_cache_path, series_info.as_bytes()),
+                    cache_store::write(&episode_cache_path, episode_list.as_bytes())
+                );
+                series_info_result.context("failed to cache series information")?;
+                episode_list_result.context("failed to cache episode list")?;
+
+                MANIFEST.record_fetch(&series_cache_path);
+                MANIFEST.record_fetch(&episode_cache_path);
+                cache_usage::schedule_cache_limit_enforcement();
             }
         }
         Ok(())
@@ -129,10 +137,10 @@ impl SeriesInfoAndEpisodeList {
 
         let missing_cache = &mut [None; 2];
 
-        if !fs::try_exists(episode_list_cache_path).await? {
+        if !cache_store::exists(&episode_list_cache_path).await? {
             missing_cache[0] = Some(MissingCache::EpisodeList);
         }
-        if !fs::try_exists(series_info_cache_path).await? {
+        if !cache_store::exists(&series_info_cache_path).await? {
             missing_cache[1] = Some(MissingCache::Series);
         }
 
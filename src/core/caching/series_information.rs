@@ -1,8 +1,6 @@
 use super::tv_maze::series_information;
 use super::*;
 
-use std::io::ErrorKind;
-
 pub async fn get_series_main_info_with_url(url: String) -> Result<SeriesMainInformation, ApiError> {
     let id = url
         .split('/')
@@ -20,18 +18,11 @@ pub async fn get_series_main_info_with_id(
     let series_information_path =
         CACHER.get_cache_file_path(CacheFilePath::SeriesMainInformation(series_id));
 
-    let series_information_json = match read_cache(&series_information_path).await {
-        Ok(json_string) => json_string,
-        Err(err) => {
-            info!("falling back online for 'series information' for series id: {series_id}");
-            let json_string = series_information::get_series_main_info_with_id(series_id).await?;
-
-            if err.kind() == ErrorKind::NotFound {
-                write_cache(&json_string, &series_information_path).await;
-            }
-            json_string
-        }
-    };
+    let series_information_json = read_cache_or_load(&series_information_path, || async {
+        info!("falling back online for 'series information' for series id: {series_id}");
+        series_information::get_series_main_info_with_id(series_id).await
+    })
+    .await?;
     deserialize_json(&series_information_json)
 }
 
@@ -39,7 +30,10 @@ pub async fn get_series_main_info_with_id(
 pub async fn cache_series_information(series_id: u32, series_info_str: &str) {
     let series_information_path =
         CACHER.get_cache_file_path(CacheFilePath::SeriesMainInformation(series_id));
-    if !series_information_path.exists() {
+    if !cache_store::exists(&series_information_path)
+        .await
+        .unwrap_or(false)
+    {
         write_cache(series_info_str, &series_information_path).await;
     }
 }
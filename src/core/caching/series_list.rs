@@ -5,13 +5,6 @@ use crate::core::{
     api::tv_maze::{episodes_information::Episode, series_information::SeriesMainInformation},
     database::{self, Series},
 };
-use lazy_static::lazy_static;
-
-lazy_static! {
-    static ref TRACKED_SERIES_INFORMATION_REQUEST_LOCK: tokio::sync::Mutex<()> =
-        tokio::sync::Mutex::new(());
-}
-
 pub struct SeriesList {
     series_list: Vec<(String, Series)>,
 }
@@ -19,7 +12,10 @@ pub struct SeriesList {
 impl SeriesList {
     pub fn new() -> Self {
         Self {
-            series_list: database::DB.get_ids_and_series(),
+            series_list: database::DB.get_ids_and_series().unwrap_or_else(|err| {
+                tracing::error!("failed to get series from the database: {}", err);
+                vec![]
+            }),
         }
     }
 
@@ -72,12 +68,6 @@ impl SeriesList {
     pub async fn get_tracked_series_information(
         &self,
     ) -> anyhow::Result<Vec<SeriesMainInformation>> {
-        // Since different methods can end up calling this same method, they will end up doing
-        // multiple unnecessary api request if the data is not cached, this lock makes the first
-        // code to call this method to do all the work first and the other methods will eventually
-        // read from the cache
-        let _ = TRACKED_SERIES_INFORMATION_REQUEST_LOCK.lock().await;
-
         let tracked_ids: Vec<u32> = self
             .get_tracked_series_ids()
             .into_iter()
@@ -105,8 +95,6 @@ impl SeriesList {
 
     /// Gets the series information of all the series in the database
     pub async fn get_all_series_information(&self) -> anyhow::Result<Vec<SeriesMainInformation>> {
-        let _ = TRACKED_SERIES_INFORMATION_REQUEST_LOCK.lock().await;
-
         let handles: Vec<_> = self
             .series_list
             .iter()
@@ -196,6 +184,33 @@ impl SeriesList {
         }
         Ok(waiting_releases_series_infos)
     }
+
+    /// Gets all the known future episodes of the running tracked series
+    pub async fn get_upcoming_release_series_information_and_all_episodes(
+        &self,
+    ) -> anyhow::Result<Vec<(SeriesMainInformation, Vec<Episode>)>> {
+        let series_infos = self.get_running_tracked_series_information().await?;
+        let mut upcoming_releases = Vec::with_capacity(series_infos.len());
+
+        let handles: Vec<_> = series_infos
+            .iter()
+            .map(|series_info| tokio::spawn(super::episode_list::EpisodeList::new(series_info.id)))
+            .collect();
+
+        for (handle, series_info) in handles.into_iter().zip(series_infos) {
+            let episode_list = handle.await??;
+            let future_episodes: Vec<Episode> = episode_list
+                .get_future_episodes()
+                .into_iter()
+                .cloned()
+                .collect();
+
+            if !future_episodes.is_empty() {
+                upcoming_releases.push((series_info, future_episodes))
+            }
+        }
+        Ok(upcoming_releases)
+    }
 }
 
 impl Default for SeriesList {
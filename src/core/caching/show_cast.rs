@@ -1,30 +1,44 @@
-use std::io::ErrorKind;
-
 use tracing::info;
 
-use super::{CacheFilePath, CACHER};
-use crate::core::{
-    api::tv_maze::{
-        deserialize_json,
-        show_cast::{self, Cast},
-        ApiError,
-    },
-    caching::{read_cache, write_cache},
+use super::{read_cache_or_load, CacheFilePath, CACHER};
+use crate::core::api::tv_maze::{
+    deserialize_json,
+    show_cast::{self, Cast},
+    ApiError,
 };
 
 pub async fn get_show_cast(series_id: u32) -> Result<Vec<Cast>, ApiError> {
     let series_cast_filepath = CACHER.get_cache_file_path(CacheFilePath::SeriesShowCast(series_id));
 
-    let json_string = match read_cache(&series_cast_filepath).await {
-        Ok(json_string) => json_string,
-        Err(err) => {
-            info!("falling back online for 'show cast' for series id: {series_id}");
-            let json_string = show_cast::get_show_cast(series_id).await?;
-            if err.kind() == ErrorKind::NotFound {
-                write_cache(&json_string, &series_cast_filepath).await;
-            }
-            json_string
-        }
-    };
+    let json_string = read_cache_or_load(&series_cast_filepath, || async {
+        info!("falling back online for 'show cast' for series id: {series_id}");
+        show_cast::get_show_cast(series_id).await
+    })
+    .await?;
     deserialize_json(&json_string)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testing;
+
+    #[tokio::test]
+    async fn show_cast_is_cached_after_the_first_fetch() {
+        let environment = testing::environment();
+
+        let cast = get_show_cast(1)
+            .await
+            .expect("failed to fetch the show cast");
+        assert!(!cast.is_empty());
+        assert!(CACHER
+            .get_cache_file_path(CacheFilePath::SeriesShowCast(1))
+            .is_file());
+
+        let cached_cast = get_show_cast(1)
+            .await
+            .expect("failed to read the show cast");
+        assert_eq!(cached_cast.len(), cast.len());
+        assert_eq!(environment.get_requests("GET", "/shows/1/cast").len(), 1);
+    }
+}
@@ -1,8 +1,4 @@
-use std::io::ErrorKind;
-
-use super::{
-    load_image, read_cache, write_cache, CacheFilePath, ImageKind, ImageResolution, CACHER,
-};
+use super::{load_image, read_cache_or_load, CacheFilePath, ImageKind, ImageResolution, CACHER};
 use crate::core::api::tv_maze::{
     deserialize_json,
     show_images::{get_show_images as get_show_images_api, Image, ImageType},
@@ -14,20 +10,14 @@ pub async fn get_show_images(series_id: u32) -> Result<Vec<Image>, ApiError> {
     let series_image_list_path =
         CACHER.get_cache_file_path(CacheFilePath::SeriesImageList(series_id));
 
-    let image_list_json = match read_cache(&series_image_list_path).await {
-        Ok(info) => info,
-        Err(err) => {
-            info!(
-                "falling back online for 'series image list' for series id {}",
-                series_id
-            );
-            let json_string = get_show_images_api(series_id).await?;
-            if err.kind() == ErrorKind::NotFound {
-                write_cache(&json_string, &series_image_list_path).await;
-            }
-            json_string
-        }
-    };
+    let image_list_json = read_cache_or_load(&series_image_list_path, || async {
+        info!(
+            "falling back online for 'series image list' for series id {}",
+            series_id
+        );
+        get_show_images_api(series_id).await
+    })
+    .await?;
 
     deserialize_json(&image_list_json)
 }
@@ -149,3 +149,21 @@ async fn get_hidden_series_ids() -> HashSet<u32> {
         .await
         .unwrap_or_default()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testing;
+
+    #[tokio::test]
+    async fn series_are_taken_from_the_schedule_episodes() {
+        testing::environment();
+
+        let series_infos = get_series_with_date(Some("2030-01-01"))
+            .await
+            .expect("failed to load the schedule");
+
+        assert_eq!(series_infos.len(), 1);
+        assert_eq!(series_infos[0].id, 1);
+    }
+}
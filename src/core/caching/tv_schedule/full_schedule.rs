@@ -19,6 +19,13 @@ const FULL_SCHEDULE_CACHE_FILENAME: &str = "full-schedule";
 static FULL_SCHEDULE: OnceCell<FullSchedule> = OnceCell::const_new();
 static HIDDEN_SERIES_IDS: RwLock<Option<HashSet<u32>>> = RwLock::const_new(None);
 
+/// The path the full schedule is cached at
+pub fn get_cache_path() -> std::path::PathBuf {
+    CACHER
+        .get_root_cache_path()
+        .join(FULL_SCHEDULE_CACHE_FILENAME)
+}
+
 fn is_hidden(id: u32) -> bool {
     HIDDEN_SERIES_IDS
         .blocking_read()
@@ -58,8 +65,7 @@ impl FullSchedule {
     }
 
     async fn load() -> anyhow::Result<Self> {
-        let mut cache_path = CACHER.get_root_cache_path().to_owned();
-        cache_path.push(FULL_SCHEDULE_CACHE_FILENAME);
+        let cache_path = get_cache_path();
 
         match cache_path.metadata() {
             Ok(metadata) => match metadata.created() {
@@ -480,3 +486,21 @@ impl FullSchedule {
         series_infos.into_iter().take(amount).collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testing;
+
+    #[tokio::test]
+    async fn full_schedule_is_downloaded_and_cached() {
+        testing::environment();
+
+        let full_schedule = FullSchedule::new()
+            .await
+            .expect("failed to load the full schedule");
+
+        assert_eq!(full_schedule.episodes.len(), 2);
+        assert!(get_cache_path().is_file());
+    }
+}
@@ -0,0 +1,187 @@
+//! Export upcoming episodes of tracked series as an iCalendar ([RFC 5545](https://www.rfc-editor.org/rfc/rfc5545)) file
+//!
+//! Every event gets a UID derived from the series id and the episode's season and number,
+//! so importing or subscribing to a newer export updates the existing events in calendar
+//! apps instead of duplicating them.
+
+use std::path;
+
+use anyhow::Context;
+use chrono::{DateTime, Duration, Utc};
+use tracing::warn;
+
+use super::api::tv_maze::episodes_information::Episode;
+use super::api::tv_maze::series_information::SeriesMainInformation;
+use super::caching::series_list::SeriesList;
+use super::{paths, profiles};
+
+const CALENDAR_FEED_FILENAME: &str = "upcoming-episodes.ics";
+const PRODUCT_IDENTIFIER: &str = "-//Series Troxide//Upcoming Episodes//EN";
+const UID_DOMAIN: &str = "series-troxide";
+/// Duration of events of episodes without a known runtime
+const DEFAULT_EPISODE_RUNTIME_MINUTES: u32 = 30;
+/// Maximum length of a content line in octets, excluding the line break
+const MAX_LINE_LENGTH: usize = 75;
+
+/// The path of the active profile's calendar feed kept in the data directory, calendar apps
+/// can subscribe to it
+pub fn get_calendar_feed_path() -> path::PathBuf {
+    paths::PATHS
+        .read()
+        .expect("failed to read paths")
+        .get_data_dir_path()
+        .join(profiles::get_profile_storage_name(
+            CALENDAR_FEED_FILENAME,
+            &profiles::get_active_profile(),
+        ))
+}
+
+/// Writes all the known future episodes of tracked series to an iCalendar file at the given path
+///
+/// Returns the amount of episodes written.
+pub async fn export_upcoming_episodes(path: impl AsRef<path::Path>) -> anyhow::Result<usize> {
+    let upcoming_releases = SeriesList::new()
+        .get_upcoming_release_series_information_and_all_episodes()
+        .await
+        .context("failed to get upcoming episodes")?;
+
+    let (calendar, total_events) = build_calendar(&upcoming_releases, Utc::now());
+
+    tokio::fs::write(path, calendar)
+        .await
+        .context("failed to write the calendar file")?;
+
+    Ok(total_events)
+}
+
+/// Builds the calendar returning it together with the amount of events in it
+fn build_calendar(
+    upcoming_releases: &[(SeriesMainInformation, Vec<Episode>)],
+    timestamp: DateTime<Utc>,
+) -> (String, usize) {
+    let mut calendar = String::new();
+    let mut total_events = 0;
+
+    push_line(&mut calendar, "BEGIN:VCALENDAR");
+    push_line(&mut calendar, "VERSION:2.0");
+    push_line(&mut calendar, &format!("PRODID:{}", PRODUCT_IDENTIFIER));
+    push_line(&mut calendar, "CALSCALE:GREGORIAN");
+    push_line(&mut calendar, "METHOD:PUBLISH");
+    push_line(&mut calendar, "X-WR-CALNAME:Series Troxide");
+
+    for (series_info, episodes) in upcoming_releases {
+        for episode in episodes {
+            if push_event(&mut calendar, series_info, episode, timestamp) {
+                total_events += 1;
+            }
+        }
+    }
+
+    push_line(&mut calendar, "END:VCALENDAR");
+
+    (calendar, total_events)
+}
+
+/// Adds an event of the given episode to the calendar
+///
+/// Returns false when the episode has no number or release time and is hence skipped.
+fn push_event(
+    calendar: &mut String,
+    series_info: &SeriesMainInformation,
+    episode: &Episode,
+    timestamp: DateTime<Utc>,
+) -> bool {
+    let (Some(episode_number), Ok(release_time)) = (episode.number, episode.local_date_time())
+    else {
+        warn!(
+            "skipping an episode of '{}' without a number or release time in the calendar",
+            series_info.name
+        );
+        return false;
+    };
+    let release_time = release_time.with_timezone(&Utc);
+
+    let runtime = episode
+        .runtime
+        .or(series_info.average_runtime)
+        .unwrap_or(DEFAULT_EPISODE_RUNTIME_MINUTES);
+
+    let summary = format!(
+        "{} S{:02}E{:02}: {}",
+        series_info.name, episode.season, episode_number, episode.name
+    );
+
+    push_line(calendar, "BEGIN:VEVENT");
+    push_line(
+        calendar,
+        &format!(
+            "UID:series-{}-s{}-e{}@{}",
+            series_info.id, episode.season, episode_number, UID_DOMAIN
+        ),
+    );
+    push_line(
+        calendar,
+        &format!("DTSTAMP:{}", format_date_time(timestamp)),
+    );
+    push_line(
+        calendar,
+        &format!("DTSTART:{}", format_date_time(release_time)),
+    );
+    push_line(
+        calendar,
+        &format!(
+            "DTEND:{}",
+            format_date_time(release_time + Duration::minutes(runtime as i64))
+        ),
+    );
+    push_line(calendar, &format!("SUMMARY:{}", escape_text(&summary)));
+    if let Some(network) = series_info
+        .network
+        .as_ref()
+        .map(|network| &network.name)
+        .or(series_info
+            .web_channel
+            .as_ref()
+            .map(|web_channel| &web_channel.name))
+    {
+        push_line(calendar, &format!("LOCATION:{}", escape_text(network)));
+    }
+    push_line(calendar, "END:VEVENT");
+
+    true
+}
+
+fn format_date_time(date_time: DateTime<Utc>) -> String {
+    date_time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Escapes the characters with special meaning in text values
+fn escape_text(text: &str) -> String {
+    let mut escaped_text = String::with_capacity(text.len());
+    for character in text.chars() {
+        match character {
+            '\\' => escaped_text.push_str("\\\\"),
+            ';' => escaped_text.push_str("\\;"),
+            ',' => escaped_text.push_str("\\,"),
+            '\n' => escaped_text.push_str("\\n"),
+            '\r' => {}
+            character => escaped_text.push(character),
+        }
+    }
+    escaped_text
+}
+
+/// Adds a content line to the calendar, folding it when it's longer than `MAX_LINE_LENGTH` octets
+fn push_line(calendar: &mut String, line: &str) {
+    let mut line_length = 0;
+    for character in line.chars() {
+        // The continuation lines start with a space which counts towards their length
+        if line_length + character.len_utf8() > MAX_LINE_LENGTH {
+            calendar.push_str("\r\n ");
+            line_length = 1;
+        }
+        calendar.push(character);
+        line_length += character.len_utf8();
+    }
+    calendar.push_str("\r\n");
+}
@@ -1,5 +1,7 @@
 //! Series Troxide module for handling command-line arguments
 
+pub mod tracking_commands;
+
 pub mod cli_handler {
     //! Handlers for command-line argument parsing
 
@@ -7,8 +9,19 @@ pub mod cli_handler {
     use std::process::exit;
 
     use super::cli_data::*;
+    use super::tracking_commands;
+    use crate::core::api::trakt;
+    use crate::core::caching::cache_store;
+    use crate::core::caching::cache_updating;
+    use crate::core::caching::cache_usage;
+    use crate::core::calendar_export;
+    use crate::core::daemon;
     use crate::core::database;
+    use crate::core::database::transfer_formats::{self, TransferFormat};
+    use crate::core::external_import::{self, ImportSource};
+    use crate::core::instance_lock::{InstanceKind, InstanceLock};
     use crate::core::paths;
+    use crate::core::profiles;
     use crate::core::settings_config;
 
     /// Handles all the logic for the command line arguments
@@ -16,30 +29,318 @@ pub mod cli_handler {
         let mut cli = Cli::parse();
 
         let command = cli.command.take();
+        let output = cli.output;
+        let profile = cli.profile.take();
+        let create_profile = cli.create_profile;
 
         setup_custom_paths(cli);
 
+        if let Some(profile) = profile {
+            profiles::set_profile_override(profile, create_profile)?;
+        }
+
         if let Some(command) = command {
+            // The daemon locks and opens the database itself
+            let _instance_lock = if matches!(command, Command::Daemon) {
+                None
+            } else {
+                let instance_lock = InstanceLock::acquire(InstanceKind::Cli)?;
+                database::open()?;
+                cache_store::open();
+                Some(instance_lock)
+            };
+
             match command {
-                Command::ImportData { file_path } => {
-                    database::database_transfer::TransferData::blocking_import_to_db(file_path)?;
-                    println!("data imported successfully!");
+                Command::ImportData {
+                    file_path,
+                    format,
+                    dry_run,
+                } => {
+                    let format = get_transfer_format(&file_path, format);
+                    if dry_run {
+                        let import_changes = tokio::runtime::Runtime::new()?
+                            .block_on(transfer_formats::dry_run_import(file_path, format))?;
+                        print_import_changes(&import_changes);
+                        exit(0);
+                    }
+                    let report = tokio::runtime::Runtime::new()?
+                        .block_on(transfer_formats::import_from_file(file_path, format))?;
+                    for (series_id, reason) in &report.invalid_series {
+                        println!("skipped series with invalid id '{}': {}", series_id, reason);
+                    }
+                    for (series_id, reason) in &report.unchecked_series {
+                        println!(
+                            "skipped series with id '{}' as it could not be checked: {}",
+                            series_id, reason
+                        );
+                    }
+                    println!(
+                        "imported {} series with {} episodes",
+                        report.imported_series, report.imported_episodes
+                    );
                     exit(0);
                 }
                 Command::ExportData {
                     file_path: path_to_data,
+                    format,
                 } => {
-                    database::database_transfer::TransferData::blocking_export_from_db(
-                        path_to_data,
-                    )?;
+                    let format = get_transfer_format(&path_to_data, format);
+                    tokio::runtime::Runtime::new()?
+                        .block_on(transfer_formats::export_to_file(path_to_data, format))?;
                     println!("data exported successfully!");
                     exit(0);
                 }
+                Command::ImportFrom { source, file_path } => {
+                    let (imported, failed) =
+                        tokio::runtime::Runtime::new()?.block_on(import_from(source, file_path))?;
+                    for failed_import in &failed {
+                        println!("{}", failed_import);
+                    }
+                    println!(
+                        "imported {} series from {}, {} failed",
+                        imported,
+                        source,
+                        failed.len()
+                    );
+                    exit(0);
+                }
+                Command::Cache { command } => {
+                    match command {
+                        CacheCommand::Refresh { series: None } => {
+                            tokio::runtime::Runtime::new()?
+                                .block_on(cache_updating::refresh_cache())?;
+                            println!("cache refreshed");
+                        }
+                        CacheCommand::Refresh {
+                            series: Some(series_id),
+                        } => {
+                            tokio::runtime::Runtime::new()?
+                                .block_on(cache_updating::refresh_series_cache(series_id))?;
+                            println!("cache of series with id '{}' refreshed", series_id);
+                        }
+                        CacheCommand::Usage => {
+                            let cache_usage = tokio::runtime::Runtime::new()?
+                                .block_on(cache_usage::get_cache_usage())?;
+                            print_cache_usage(&cache_usage);
+                        }
+                        CacheCommand::Purge { category } => {
+                            tokio::runtime::Runtime::new()?
+                                .block_on(cache_usage::purge_cache(category))?;
+                            println!("{} cache purged", category);
+                        }
+                        CacheCommand::Benchmark => {
+                            let report = tokio::runtime::Runtime::new()?
+                                .block_on(cache_store::benchmark())?;
+                            println!("loaded {} series", report.series);
+                            println!("files: {:?}", report.files_duration);
+                            println!("database: {:?}", report.database_duration);
+                            if !report.database_duration.is_zero() {
+                                println!(
+                                    "the database is {:.1} times as fast",
+                                    report.files_duration.as_secs_f64()
+                                        / report.database_duration.as_secs_f64()
+                                );
+                            }
+                        }
+                    }
+                    exit(0);
+                }
+                Command::RepairDatabase => {
+                    let report = database::DB.repair()?;
+                    println!("checked {} series", report.checked_series);
+                    for series_id in &report.quarantined_series {
+                        println!("quarantined corrupt series with id '{}'", series_id);
+                    }
+                    println!(
+                        "{} series quarantined in total",
+                        report.total_quarantined_series
+                    );
+                    exit(0);
+                }
+                Command::SyncTrakt => {
+                    let report = tokio::runtime::Runtime::new()?.block_on(sync_trakt())?;
+                    println!("{} episodes pulled from trakt", report.pulled_episodes);
+                    println!("{} episodes pushed to trakt", report.pushed_episodes);
+                    println!("{} episodes removed from trakt", report.removed_episodes);
+                    for show_title in &report.unmatched_shows {
+                        println!("could not find '{}' on TVmaze", show_title);
+                    }
+                    exit(0);
+                }
+                Command::ExportCalendar { file_path } => {
+                    let file_path =
+                        file_path.unwrap_or_else(calendar_export::get_calendar_feed_path);
+                    let total_episodes = tokio::runtime::Runtime::new()?
+                        .block_on(calendar_export::export_upcoming_episodes(&file_path))?;
+                    println!(
+                        "exported {} upcoming episodes to '{}'",
+                        total_episodes,
+                        file_path.display()
+                    );
+                    exit(0);
+                }
+                Command::Search { query } => {
+                    tokio::runtime::Runtime::new()?
+                        .block_on(tracking_commands::search(query, output))?;
+                    exit(0);
+                }
+                Command::Track { series_id } => {
+                    tokio::runtime::Runtime::new()?
+                        .block_on(tracking_commands::track(series_id, output))?;
+                    exit(0);
+                }
+                Command::Untrack { series_id } => {
+                    tracking_commands::untrack(series_id, output)?;
+                    exit(0);
+                }
+                Command::Mark {
+                    series_id,
+                    season,
+                    episodes,
+                } => {
+                    tokio::runtime::Runtime::new()?
+                        .block_on(tracking_commands::mark(series_id, season, episodes, output))?;
+                    exit(0);
+                }
+                Command::List => {
+                    tokio::runtime::Runtime::new()?.block_on(tracking_commands::list(output))?;
+                    exit(0);
+                }
+                Command::Next { series_id } => {
+                    tokio::runtime::Runtime::new()?
+                        .block_on(tracking_commands::next(series_id, output))?;
+                    exit(0);
+                }
+                Command::UpNext { command } => {
+                    let change = command.map(|command| match command {
+                        UpNextCommand::Move {
+                            series_id,
+                            position,
+                        } => (series_id, tracking_commands::UpNextChange::Move(position)),
+                        UpNextCommand::Pin { series_id } => {
+                            (series_id, tracking_commands::UpNextChange::Pin)
+                        }
+                        UpNextCommand::Unpin { series_id } => {
+                            (series_id, tracking_commands::UpNextChange::Unpin)
+                        }
+                        UpNextCommand::Snooze { series_id, days } => {
+                            (series_id, tracking_commands::UpNextChange::Snooze(days))
+                        }
+                        UpNextCommand::Unsnooze { series_id } => {
+                            (series_id, tracking_commands::UpNextChange::Unsnooze)
+                        }
+                    });
+                    tokio::runtime::Runtime::new()?
+                        .block_on(tracking_commands::up_next(change, output))?;
+                    exit(0);
+                }
+                Command::Daemon => {
+                    daemon::run()?;
+                    exit(0);
+                }
             }
         }
         Ok(())
     }
 
+    /// The given format or the one guessed from the file's extension, RON when unknown
+    fn get_transfer_format(
+        file_path: &std::path::Path,
+        format: Option<TransferFormat>,
+    ) -> TransferFormat {
+        format
+            .or_else(|| TransferFormat::from_path(file_path))
+            .unwrap_or(TransferFormat::Ron)
+    }
+
+    fn print_import_changes(import_changes: &database::database_transfer::ImportChanges) {
+        let print_series_changes = |series_changes: &database::database_transfer::SeriesChanges| {
+            println!(
+                "  {} ({}): {} episodes added, {} episodes removed",
+                series_changes.series_name,
+                series_changes.series_id,
+                series_changes.added_episodes.len(),
+                series_changes.removed_episodes.len()
+            );
+            for (season, episode) in &series_changes.removed_episodes {
+                println!("    S{:02}E{:02} is unwatched", season, episode);
+            }
+            for conflict in &series_changes.conflicts {
+                println!("    {}", conflict);
+            }
+        };
+
+        if !import_changes.new_series.is_empty() {
+            println!("new series:");
+            import_changes
+                .new_series
+                .iter()
+                .for_each(print_series_changes);
+        }
+        if !import_changes.changed_series.is_empty() {
+            println!("changed series:");
+            import_changes
+                .changed_series
+                .iter()
+                .for_each(print_series_changes);
+        }
+        println!("{} series unchanged", import_changes.unchanged_series);
+        println!("dry run, nothing was imported");
+    }
+
+    fn print_cache_usage(cache_usage: &[cache_usage::CategoryUsage]) {
+        for category_usage in cache_usage {
+            println!(
+                "{}: {} in {} files",
+                category_usage.category,
+                cache_usage::format_size(category_usage.size),
+                category_usage.files
+            );
+        }
+
+        let total_size = cache_usage
+            .iter()
+            .map(|category_usage| category_usage.size)
+            .sum();
+        match cache_usage::get_cache_limit() {
+            Some(cache_limit) => println!(
+                "total: {} of {}",
+                cache_usage::format_size(total_size),
+                cache_usage::format_size(cache_limit)
+            ),
+            None => println!("total: {}, no limit", cache_usage::format_size(total_size)),
+        }
+    }
+
+    /// Imports the export of another tracker returning the amount of imported series and the
+    /// shows and export entries that could not be imported
+    async fn import_from(
+        source: ImportSource,
+        file_path: std::path::PathBuf,
+    ) -> anyhow::Result<(usize, Vec<external_import::FailedImport>)> {
+        let (progress_sender, mut progress_receiver) = tokio::sync::mpsc::channel(100);
+        // The progress is only needed by the gui
+        tokio::spawn(async move { while progress_receiver.recv().await.is_some() {} });
+
+        let (imports, failed) = external_import::import(source, file_path, progress_sender).await?;
+        let total_imports = imports.len();
+        external_import::save_imports(imports);
+        database::DB.flush()?;
+
+        Ok((total_imports, failed))
+    }
+
+    /// Syncs the watched episodes with the trakt account of the logged in user
+    async fn sync_trakt() -> anyhow::Result<trakt::sync::SyncReport> {
+        let credentials = trakt::user_credentials::Credentials::load_from_file().await?;
+        let (_, token) = credentials
+            .get_data()
+            .ok_or(trakt::user_credentials::CredentialsError::TokenNotFound)?;
+        let client = trakt::user_credentials::Client::new()?;
+
+        Ok(trakt::sync::sync(&client.client_id, token.get_access_token()?).await?)
+    }
+
     fn setup_custom_paths(cli: Cli) {
         // Setting the config file path first before we read other custom paths from the settings
         if let Some(config_dir_path) = cli.config_dir {
@@ -79,9 +380,16 @@ pub mod cli_handler {
 pub mod cli_data {
     //! Data structures for command-line argument parsing
 
-    use clap::{Parser, Subcommand};
+    use clap::{Parser, Subcommand, ValueEnum};
+    use std::ops::RangeInclusive;
     use std::path::PathBuf;
 
+    use super::tracking_commands::parse_episode_range;
+
+    use crate::core::caching::cache_usage::CacheCategory;
+    use crate::core::database::transfer_formats::TransferFormat;
+    use crate::core::external_import::ImportSource;
+
     #[derive(Parser)]
     #[command(author, version, about)]
     pub struct Cli {
@@ -97,22 +405,197 @@ pub mod cli_data {
         #[clap(short, long)]
         pub data_dir: Option<PathBuf>,
 
+        /// Profile to use instead of the active one
+        #[clap(short, long)]
+        pub profile: Option<String>,
+
+        /// Create the profile given with --profile when it does not exist
+        #[clap(long, requires = "profile")]
+        pub create_profile: bool,
+
+        /// Output format of the commands
+        #[clap(short, long, value_enum, global = true, default_value_t)]
+        pub output: OutputFormat,
+
         #[clap(subcommand)]
         pub command: Option<Command>,
     }
 
+    #[derive(Clone, Copy, Default, ValueEnum)]
+    pub enum OutputFormat {
+        /// Human readable table
+        #[default]
+        Table,
+        /// Json for scripts
+        Json,
+    }
+
     #[derive(Subcommand)]
     pub enum Command {
         /// Import series data
         ImportData {
             /// Import filepath
             file_path: PathBuf,
+
+            /// Format of the file, guessed from it's extension by default
+            #[clap(short, long, value_enum)]
+            format: Option<TransferFormat>,
+
+            /// Print what the import would change without importing anything
+            #[clap(long)]
+            dry_run: bool,
         },
 
         /// Export series data
         ExportData {
             /// Export filepath
             file_path: PathBuf,
+
+            /// Format of the file, guessed from it's extension by default
+            #[clap(short, long, value_enum)]
+            format: Option<TransferFormat>,
+        },
+
+        /// Import the watch history exported from another tracker
+        ImportFrom {
+            /// Tracker the export comes from
+            #[clap(value_enum)]
+            source: ImportSource,
+
+            /// Export filepath
+            file_path: PathBuf,
+        },
+
+        /// Manage the cache of TVmaze data
+        Cache {
+            #[clap(subcommand)]
+            command: CacheCommand,
+        },
+
+        /// Quarantine corrupt series data in the database
+        RepairDatabase,
+
+        /// Sync watched episodes with the logged in trakt account
+        SyncTrakt,
+
+        /// Export upcoming episodes of tracked series as an iCalendar file
+        ExportCalendar {
+            /// Export filepath, defaults to the calendar feed in the data directory
+            file_path: Option<PathBuf>,
+        },
+
+        /// Search TVmaze for series by name
+        Search {
+            /// Series name
+            query: String,
+        },
+
+        /// Start tracking a series
+        Track {
+            /// TVmaze id of the series
+            series_id: u32,
+        },
+
+        /// Stop tracking a series
+        Untrack {
+            /// TVmaze id of the series
+            series_id: u32,
+        },
+
+        /// Mark episodes of a season as watched
+        Mark {
+            /// TVmaze id of the series
+            series_id: u32,
+
+            /// Season number
+            season: u32,
+
+            /// Episode number or range of episode numbers i.e. 3 or 1-5
+            #[clap(value_parser = parse_episode_range)]
+            episodes: RangeInclusive<u32>,
+        },
+
+        /// List tracked series with their watch progress
+        List,
+
+        /// Print the next episode to watch
+        Next {
+            /// TVmaze id of the series, all the tracked series when omitted
+            series_id: Option<u32>,
+        },
+
+        /// Print or change the up next queue of series to watch
+        UpNext {
+            #[clap(subcommand)]
+            command: Option<UpNextCommand>,
+        },
+
+        /// Run the cache updater and release notifications without the gui
+        ///
+        /// Runs until SIGTERM or an interrupt is received, see
+        /// `assets/systemd/series-troxide.service` for running it as a systemd user service.
+        /// The gui and the other commands can't be used while the daemon runs.
+        Daemon,
+    }
+
+    #[derive(Subcommand)]
+    pub enum CacheCommand {
+        /// Check the cache for TVmaze updates right away
+        Refresh {
+            /// Refetch the cache of this series instead, TVmaze id of the series
+            #[clap(long)]
+            series: Option<u32>,
+        },
+
+        /// Show how much space every category of the cache takes
+        Usage,
+
+        /// Remove all the cache of a category
+        Purge {
+            #[clap(value_enum)]
+            category: CacheCategory,
+        },
+
+        /// Compare loading the cached series from files and from the database
+        Benchmark,
+    }
+
+    #[derive(Subcommand)]
+    pub enum UpNextCommand {
+        /// Move a series to a position in the queue
+        Move {
+            /// TVmaze id of the series
+            series_id: u32,
+
+            /// Position in the queue starting from 1
+            position: usize,
+        },
+
+        /// Keep a series at the front of the queue
+        Pin {
+            /// TVmaze id of the series
+            series_id: u32,
+        },
+
+        /// Stop keeping a series at the front of the queue
+        Unpin {
+            /// TVmaze id of the series
+            series_id: u32,
+        },
+
+        /// Leave a series out of the queue for a number of days
+        Snooze {
+            /// TVmaze id of the series
+            series_id: u32,
+
+            /// Number of days
+            days: u32,
+        },
+
+        /// Bring a snoozed series back into the queue
+        Unsnooze {
+            /// TVmaze id of the series
+            series_id: u32,
         },
     }
 }
@@ -0,0 +1,564 @@
+//! Headless commands for searching, tracking and watching series
+//!
+//! Every command prints it's results either as a human readable table or as json
+//! depending on the chosen `OutputFormat`.
+
+use std::ops::RangeInclusive;
+
+use anyhow::{bail, Context};
+use serde::Serialize;
+use tracing::warn;
+
+use super::cli_data::OutputFormat;
+use crate::core::api::tv_maze::episodes_information::Episode;
+use crate::core::api::tv_maze::series_searching::search_series;
+use crate::core::caching::episode_list::EpisodeList;
+use crate::core::caching::series_information::get_series_main_info_with_id;
+use crate::core::database::{AddResult, Series, DB};
+use crate::core::up_next::{self, UpNextQueue};
+
+#[derive(Serialize)]
+struct SearchResult {
+    id: u32,
+    name: String,
+    premiered: Option<String>,
+    status: String,
+}
+
+#[derive(Serialize)]
+struct TrackingStatus {
+    id: u32,
+    name: String,
+    tracked: bool,
+}
+
+#[derive(Serialize)]
+struct MarkResult {
+    id: u32,
+    name: String,
+    season: u32,
+    first_episode: u32,
+    last_episode: u32,
+    /// Whether all, some or none of the episodes were newly marked watched
+    marked: &'static str,
+}
+
+#[derive(Serialize)]
+struct SeriesProgress {
+    id: u32,
+    name: String,
+    watched_episodes: usize,
+    watchable_episodes: usize,
+    next_episode: Option<NextEpisode>,
+}
+
+#[derive(Serialize)]
+struct NextEpisode {
+    season: u32,
+    episode: u32,
+    name: String,
+    airdate: Option<String>,
+}
+
+impl NextEpisode {
+    fn new(episode: &Episode) -> Option<Self> {
+        Some(Self {
+            season: episode.season,
+            episode: episode.number?,
+            name: episode.name.clone(),
+            airdate: episode.airdate.clone(),
+        })
+    }
+
+    fn code(&self) -> String {
+        format!("S{:02}E{:02}", self.season, self.episode)
+    }
+}
+
+#[derive(Serialize)]
+struct SeriesNextEpisode {
+    id: u32,
+    name: String,
+    #[serde(flatten)]
+    next_episode: NextEpisode,
+}
+
+/// Searches TVmaze for series matching the given name
+pub async fn search(query: String, output: OutputFormat) -> anyhow::Result<()> {
+    let search_results: Vec<_> = search_series(query)
+        .await
+        .context("failed to search series")?
+        .into_iter()
+        .map(|search_result| SearchResult {
+            id: search_result.show.id,
+            name: search_result.show.name,
+            premiered: search_result.show.premiered,
+            status: search_result.show.status,
+        })
+        .collect();
+
+    print_output(output, &search_results, || {
+        print_table(
+            &["ID", "NAME", "PREMIERED", "STATUS"],
+            search_results
+                .iter()
+                .map(|search_result| {
+                    vec![
+                        search_result.id.to_string(),
+                        search_result.name.clone(),
+                        search_result.premiered.clone().unwrap_or_default(),
+                        search_result.status.clone(),
+                    ]
+                })
+                .collect(),
+        )
+    })
+}
+
+/// Starts tracking the series with the given id
+pub async fn track(series_id: u32, output: OutputFormat) -> anyhow::Result<()> {
+    let series_name = match DB.get_series(series_id)? {
+        Some(mut series) => {
+            series.mark_tracked();
+            series.get_name().to_owned()
+        }
+        None => {
+            let series_info = get_series_main_info_with_id(series_id)
+                .await
+                .with_context(|| format!("failed to get series with id '{}'", series_id))?;
+            let mut series = Series::new(series_info.name, series_id);
+            series.mark_tracked();
+            series.get_name().to_owned()
+        }
+    };
+    DB.flush()?;
+
+    print_tracking_status(
+        TrackingStatus {
+            id: series_id,
+            name: series_name,
+            tracked: true,
+        },
+        output,
+    )
+}
+
+/// Stops tracking the series with the given id
+pub fn untrack(series_id: u32, output: OutputFormat) -> anyhow::Result<()> {
+    let Some(mut series) = DB.get_series(series_id)? else {
+        bail!("series with id '{}' is not in the database", series_id);
+    };
+    series.mark_untracked();
+    let series_name = series.get_name().to_owned();
+    drop(series);
+    DB.flush()?;
+
+    print_tracking_status(
+        TrackingStatus {
+            id: series_id,
+            name: series_name,
+            tracked: false,
+        },
+        output,
+    )
+}
+
+fn print_tracking_status(
+    tracking_status: TrackingStatus,
+    output: OutputFormat,
+) -> anyhow::Result<()> {
+    print_output(output, &tracking_status, || {
+        if tracking_status.tracked {
+            println!("tracking '{}'", tracking_status.name);
+        } else {
+            println!("stopped tracking '{}'", tracking_status.name);
+        }
+    })
+}
+
+/// Marks the given range of episodes of a season as watched
+///
+/// Episodes that have not been released yet are not marked.
+pub async fn mark(
+    series_id: u32,
+    season_number: u32,
+    episodes: RangeInclusive<u32>,
+    output: OutputFormat,
+) -> anyhow::Result<()> {
+    let mut series = match DB.get_series(series_id)? {
+        Some(series) => series,
+        None => {
+            let series_info = get_series_main_info_with_id(series_id)
+                .await
+                .with_context(|| format!("failed to get series with id '{}'", series_id))?;
+            Series::new(series_info.name, series_id)
+        }
+    };
+
+    let add_result = series
+        .add_episodes(season_number, episodes.clone())
+        .await
+        .with_context(|| {
+            format!(
+                "failed to get the episodes of series with id '{}'",
+                series_id
+            )
+        })?;
+
+    let mark_result = MarkResult {
+        id: series_id,
+        name: series.get_name().to_owned(),
+        season: season_number,
+        first_episode: *episodes.start(),
+        last_episode: *episodes.end(),
+        marked: match add_result {
+            AddResult::Full => "all",
+            AddResult::Partial => "some",
+            AddResult::None => "none",
+        },
+    };
+    drop(series);
+    DB.flush()?;
+
+    print_output(output, &mark_result, || {
+        let episodes = format!(
+            "S{:02}E{:02}-E{:02}",
+            mark_result.season, mark_result.first_episode, mark_result.last_episode
+        );
+        match add_result {
+            AddResult::Full => println!("marked {} of '{}' watched", episodes, mark_result.name),
+            AddResult::Partial => println!(
+                "marked {} of '{}' watched, some were already watched or not released",
+                episodes, mark_result.name
+            ),
+            AddResult::None => println!(
+                "nothing marked, {} of '{}' are already watched or not released",
+                episodes, mark_result.name
+            ),
+        }
+    })
+}
+
+/// Logs a series left out of a listing as it's episode list failed to load, one broken series
+/// not making the listing of the others fail
+fn warn_left_out(series: &Series, err: impl std::fmt::Display) {
+    warn!(
+        "failed to get the episode list of '{}', leaving it out: {}",
+        series.get_name(),
+        err
+    );
+}
+
+/// Lists the tracked series with their watch progress
+pub async fn list(output: OutputFormat) -> anyhow::Result<()> {
+    let mut tracked_series: Vec<_> = DB
+        .get_series_collection()?
+        .into_iter()
+        .filter(|series| series.is_tracked())
+        .collect();
+    tracked_series.sort_by(|a, b| a.get_name().cmp(b.get_name()));
+
+    let mut series_progresses = Vec::with_capacity(tracked_series.len());
+    for series in tracked_series {
+        let episode_list = match EpisodeList::new(series.id()).await {
+            Ok(episode_list) => episode_list,
+            Err(err) => {
+                warn_left_out(&series, err);
+                continue;
+            }
+        };
+
+        series_progresses.push(SeriesProgress {
+            id: series.id(),
+            name: series.get_name().to_owned(),
+            watched_episodes: series.get_total_episodes(),
+            watchable_episodes: episode_list.get_total_watchable_episodes(),
+            next_episode: episode_list
+                .get_next_episode_to_watch()
+                .and_then(NextEpisode::new),
+        });
+    }
+
+    print_output(output, &series_progresses, || {
+        print_table(
+            &["ID", "NAME", "PROGRESS", "NEXT"],
+            series_progresses
+                .iter()
+                .map(|series_progress| {
+                    vec![
+                        series_progress.id.to_string(),
+                        series_progress.name.clone(),
+                        format!(
+                            "{}/{}",
+                            series_progress.watched_episodes, series_progress.watchable_episodes
+                        ),
+                        series_progress
+                            .next_episode
+                            .as_ref()
+                            .map(NextEpisode::code)
+                            .unwrap_or_else(|| "-".to_owned()),
+                    ]
+                })
+                .collect(),
+        )
+    })
+}
+
+/// Prints the next episode to watch of the given series or of all the tracked series
+pub async fn next(series_id: Option<u32>, output: OutputFormat) -> anyhow::Result<()> {
+    let series_collection = match series_id {
+        Some(series_id) => match DB.get_series(series_id)? {
+            Some(series) => vec![series],
+            None => bail!("series with id '{}' is not in the database", series_id),
+        },
+        None => DB
+            .get_series_collection()?
+            .into_iter()
+            .filter(|series| series.is_tracked())
+            .collect(),
+    };
+
+    let mut next_episodes = vec![];
+    for series in series_collection {
+        let episode_list = match EpisodeList::new(series.id()).await {
+            Ok(episode_list) => episode_list,
+            // The series asked for can't be left out
+            Err(err) if series_id.is_some() => {
+                return Err(err).with_context(|| {
+                    format!("failed to get episode list of '{}'", series.get_name())
+                })
+            }
+            Err(err) => {
+                warn_left_out(&series, err);
+                continue;
+            }
+        };
+
+        if let Some(next_episode) = episode_list
+            .get_next_episode_to_watch()
+            .and_then(NextEpisode::new)
+        {
+            next_episodes.push(SeriesNextEpisode {
+                id: series.id(),
+                name: series.get_name().to_owned(),
+                next_episode,
+            });
+        }
+    }
+    next_episodes.sort_by(|a, b| a.name.cmp(&b.name));
+
+    print_output(output, &next_episodes, || {
+        print_table(
+            &["ID", "NAME", "EPISODE", "TITLE", "AIRDATE"],
+            next_episodes
+                .iter()
+                .map(|series_next_episode| {
+                    let next_episode = &series_next_episode.next_episode;
+                    vec![
+                        series_next_episode.id.to_string(),
+                        series_next_episode.name.clone(),
+                        next_episode.code(),
+                        next_episode.name.clone(),
+                        next_episode.airdate.clone().unwrap_or_default(),
+                    ]
+                })
+                .collect(),
+        )
+    })
+}
+
+#[derive(Serialize)]
+struct UpNextEntry {
+    /// The position in the queue starting from 1, snoozed series have none
+    position: Option<usize>,
+    id: u32,
+    name: String,
+    pinned: bool,
+    snoozed_until: Option<String>,
+    next_episode: Option<NextEpisode>,
+}
+
+/// Changes of the up next queue
+pub enum UpNextChange {
+    /// Move to the given position starting from 1
+    Move(usize),
+    Pin,
+    Unpin,
+    /// Snooze for the given number of days
+    Snooze(u32),
+    Unsnooze,
+}
+
+/// Applies the change to the up next queue of the given series and prints the queue
+pub async fn up_next(
+    change: Option<(u32, UpNextChange)>,
+    output: OutputFormat,
+) -> anyhow::Result<()> {
+    let (mut queue, items) = up_next::load_synced_queue()
+        .await
+        .context("failed to load the up next queue")?;
+
+    if let Some((series_id, change)) = change {
+        match change {
+            UpNextChange::Move(position) => queue.move_to(series_id, position.saturating_sub(1))?,
+            UpNextChange::Pin => queue.set_pinned(series_id, true)?,
+            UpNextChange::Unpin => queue.set_pinned(series_id, false)?,
+            UpNextChange::Snooze(days) => queue.snooze(series_id, days)?,
+            UpNextChange::Unsnooze => queue.unsnooze(series_id)?,
+        }
+        queue.save()?;
+        DB.flush()?;
+    }
+
+    let up_next_entries = get_up_next_entries(&queue, &items);
+
+    print_output(output, &up_next_entries, || {
+        print_table(
+            &["POS", "ID", "NAME", "EPISODE", "TITLE", "STATUS"],
+            up_next_entries
+                .iter()
+                .map(|up_next_entry| {
+                    let status = match (&up_next_entry.snoozed_until, up_next_entry.pinned) {
+                        (Some(snoozed_until), _) => format!("snoozed until {}", snoozed_until),
+                        (None, true) => "pinned".to_owned(),
+                        (None, false) => String::new(),
+                    };
+                    vec![
+                        up_next_entry
+                            .position
+                            .map(|position| position.to_string())
+                            .unwrap_or_else(|| "-".to_owned()),
+                        up_next_entry.id.to_string(),
+                        up_next_entry.name.clone(),
+                        up_next_entry
+                            .next_episode
+                            .as_ref()
+                            .map(NextEpisode::code)
+                            .unwrap_or_else(|| "-".to_owned()),
+                        up_next_entry
+                            .next_episode
+                            .as_ref()
+                            .map(|next_episode| next_episode.name.clone())
+                            .unwrap_or_default(),
+                        status,
+                    ]
+                })
+                .collect(),
+        )
+    })
+}
+
+/// The queued series in the queue's order followed by the snoozed ones
+fn get_up_next_entries(queue: &UpNextQueue, items: &[up_next::UpNextItem]) -> Vec<UpNextEntry> {
+    let queued_entries = queue
+        .get_queued_entries()
+        .enumerate()
+        .map(|(index, entry)| (Some(index + 1), entry));
+    let snoozed_entries = queue
+        .get_snoozed_entries()
+        .into_iter()
+        .map(|entry| (None, entry));
+
+    queued_entries
+        .chain(snoozed_entries)
+        .filter_map(|(position, entry)| {
+            let item = items
+                .iter()
+                .find(|item| item.series_info.id == entry.get_series_id())?;
+            Some(UpNextEntry {
+                position,
+                id: item.series_info.id,
+                name: item.series_info.name.clone(),
+                pinned: entry.is_pinned(),
+                snoozed_until: entry
+                    .get_snoozed_until()
+                    .map(|snoozed_until| snoozed_until.date_naive().to_string()),
+                next_episode: NextEpisode::new(&item.next_episode),
+            })
+        })
+        .collect()
+}
+
+/// Parses an episode range in the form of `3` or `1-5`
+pub fn parse_episode_range(range: &str) -> Result<RangeInclusive<u32>, String> {
+    let parse_episode = |episode: &str| {
+        episode
+            .trim()
+            .parse::<u32>()
+            .map_err(|err| format!("invalid episode number '{}': {}", episode, err))
+    };
+
+    let (start, end) = match range.split_once('-') {
+        Some((start, end)) => (parse_episode(start)?, parse_episode(end)?),
+        None => {
+            let episode = parse_episode(range)?;
+            (episode, episode)
+        }
+    };
+
+    if start == 0 || start > end {
+        return Err(format!("invalid episode range '{}'", range));
+    }
+    Ok(start..=end)
+}
+
+/// Prints the json of the given value or calls the given function to print the table
+fn print_output<T: Serialize>(
+    output: OutputFormat,
+    value: &T,
+    print_table: impl FnOnce(),
+) -> anyhow::Result<()> {
+    match output {
+        OutputFormat::Table => print_table(),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(value)?),
+    }
+    Ok(())
+}
+
+/// Prints the rows with their columns aligned under the given headers
+fn print_table(headers: &[&str], rows: Vec<Vec<String>>) {
+    let mut column_widths: Vec<_> = headers
+        .iter()
+        .map(|header| header.chars().count())
+        .collect();
+    for row in &rows {
+        for (column_width, cell) in column_widths.iter_mut().zip(row) {
+            *column_width = (*column_width).max(cell.chars().count());
+        }
+    }
+
+    let format_row = |cells: Vec<&str>| {
+        cells
+            .iter()
+            .zip(&column_widths)
+            .map(|(cell, column_width)| format!("{:<width$}", cell, width = column_width))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_owned()
+    };
+
+    println!("{}", format_row(headers.to_vec()));
+    for row in &rows {
+        println!("{}", format_row(row.iter().map(String::as_str).collect()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testing;
+
+    #[tokio::test]
+    async fn series_failing_to_load_are_left_out_of_the_listings() {
+        let _database = testing::lock_database().await;
+        // The mock server has no episode list for this series
+        let mut series = Series::new("Removed from TVmaze".to_owned(), 999_999);
+        series.mark_tracked();
+        drop(series);
+
+        assert!(list(OutputFormat::Json).await.is_ok());
+        assert!(next(None, OutputFormat::Json).await.is_ok());
+        assert!(next(Some(999_999), OutputFormat::Json).await.is_err());
+    }
+}
@@ -0,0 +1,85 @@
+//! Running the cache updater and release notifications without the gui
+//!
+//! The daemon is meant to be run as a service i.e. a systemd user service, it keeps running
+//! until it receives SIGTERM or an interrupt and then shuts down gracefully by flushing the
+//! database and releasing the instance lock.
+
+use std::time::Duration;
+
+use tracing::{error, info};
+
+use super::caching::cache_manifest::MANIFEST;
+use super::caching::cache_store;
+use super::caching::cache_updating::update_cache;
+use super::database::{self, DB};
+use super::instance_lock::{InstanceKind, InstanceLock};
+use super::notifications::{self, TroxideNotify};
+
+/// How often to check whether the cache is due for an update, `update_cache` itself only
+/// updates once a day
+const CACHE_UPDATE_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Runs until a shutdown signal is received
+pub fn run() -> anyhow::Result<()> {
+    let _instance_lock = InstanceLock::acquire(InstanceKind::Daemon)?;
+    database::open()?;
+    cache_store::open();
+
+    info!("starting daemon");
+
+    std::thread::spawn(|| {
+        if let Err(err) = TroxideNotify::new().and_then(|notifier| notifier.run()) {
+            error!("release notifications stopped: {}", err);
+        }
+    });
+
+    tokio::runtime::Runtime::new()?.block_on(async {
+        tokio::select! {
+            _ = cache_updater() => Ok(()),
+            result = shutdown_signal() => result,
+        }?;
+        MANIFEST.save().await
+    })?;
+
+    info!("shutting down daemon");
+    DB.flush()?;
+
+    Ok(())
+}
+
+async fn cache_updater() {
+    let mut interval = tokio::time::interval(CACHE_UPDATE_CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        match update_cache().await {
+            // Rescheduling in case the update brought in new releases
+            Ok(()) => notifications::refresh_notifications(),
+            Err(err) => error!("failed to update cache: {}", err),
+        }
+    }
+}
+
+/// Waits for SIGTERM or an interrupt
+async fn shutdown_signal() -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut terminate = signal(SignalKind::terminate())?;
+        tokio::select! {
+            _ = terminate.recv() => info!("received SIGTERM"),
+            result = tokio::signal::ctrl_c() => {
+                result?;
+                info!("received an interrupt");
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        tokio::signal::ctrl_c().await?;
+        info!("received an interrupt");
+    }
+
+    Ok(())
+}
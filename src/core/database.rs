@@ -1,144 +1,452 @@
+use chrono::{DateTime, Utc};
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
-use sled::Db;
+use sled::{Db, Tree};
 use std::{
-    collections::{HashMap, HashSet},
-    ops::RangeInclusive,
+    collections::HashMap,
+    ops::{RangeBounds, RangeInclusive},
+    sync::{Mutex, OnceLock, RwLock},
 };
-use tracing::info;
+use thiserror::Error;
+use tracing::{error, info, warn};
 
-use super::{api::tv_maze::series_information::SeriesMainInformation, caching};
-use crate::core::paths;
+use super::{
+    api::tv_maze::{series_information::SeriesMainInformation, ApiError},
+    caching,
+};
+use crate::core::{paths, profiles};
 
-// The last digit represents the version of the database.
-const DATABASE_FOLDER_NAME: &str = "series-troxide-db-1";
+pub mod migration;
+pub mod transfer_formats;
 
 lazy_static! {
-    pub static ref DB: Database = Database::init();
+    /// The database opened at startup with `open`
+    pub static ref DB: &'static Database = DATABASE
+        .get()
+        .expect("the database should be opened at startup");
+    static ref OPENING: Mutex<()> = Mutex::new(());
+}
+
+static DATABASE: OnceLock<Database> = OnceLock::new();
+
+/// Opens the database, running any pending migrations
+///
+/// This has to be called at startup before `DB` is used, calling it again returns the
+/// already opened database.
+pub fn open() -> Result<&'static Database, DatabaseError> {
+    let _opening = OPENING.lock().expect("failed to lock database opening");
+    if let Some(database) = DATABASE.get() {
+        return Ok(database);
+    }
+    let database = Database::init()?;
+    Ok(DATABASE.get_or_init(|| database))
+}
+
+const QUARANTINE_TREE_NAME: &str = "quarantine";
+/// Profiles other than the default one have their series in trees named with this prefix
+const PROFILE_TREE_PREFIX: &str = "profile-";
+
+#[derive(Debug, Error)]
+pub enum DatabaseError {
+    #[error("database error: {0}")]
+    Sled(sled::Error),
+
+    #[error("failed to serialize series: {0}")]
+    Serialization(bincode::Error),
+
+    #[error("corrupt series with id '{0}': {1}")]
+    CorruptSeries(String, bincode::Error),
+
+    #[error("database migration error: {0}")]
+    Migration(migration::MigrationError),
 }
 
 pub struct Database {
     db: Db,
+    /// The trees of the active profile
+    profile_trees: RwLock<ProfileTrees>,
+}
+
+#[derive(Clone)]
+struct ProfileTrees {
+    profile_name: String,
+    series: Tree,
+    quarantine: Tree,
+}
+
+impl ProfileTrees {
+    fn open(db: &Db, profile_name: &str) -> sled::Result<Self> {
+        let series = open_series_tree(db, profile_name)?;
+        let quarantine = db.open_tree(profiles::get_profile_storage_name(
+            QUARANTINE_TREE_NAME,
+            profile_name,
+        ))?;
+
+        Ok(Self {
+            profile_name: profile_name.to_owned(),
+            series,
+            quarantine,
+        })
+    }
+
+    /// Moves the given series record from the series records into the quarantine
+    fn quarantine(&self, series_id: &[u8], series_bytes: &[u8]) -> Result<(), DatabaseError> {
+        self.quarantine
+            .insert(series_id, series_bytes)
+            .map_err(DatabaseError::Sled)?;
+        self.series.remove(series_id).map_err(DatabaseError::Sled)?;
+        Ok(())
+    }
+}
+
+/// Opens the series tree of the given profile, the default profile has it's series in the
+/// default tree
+fn open_series_tree(db: &Db, profile_name: &str) -> sled::Result<Tree> {
+    if profile_name == profiles::DEFAULT_PROFILE {
+        Ok(Tree::clone(db))
+    } else {
+        db.open_tree(get_profile_tree_name(profile_name))
+    }
+}
+
+fn get_profile_tree_name(profile_name: &str) -> String {
+    format!("{}{}", PROFILE_TREE_PREFIX, profile_name)
 }
 
 impl Database {
-    fn init() -> Self {
-        let mut database_path = paths::PATHS
+    fn init() -> Result<Self, DatabaseError> {
+        let data_dir_path = paths::PATHS
             .read()
             .expect("failed to read paths")
             .get_data_dir_path()
             .to_path_buf();
 
-        info!("initializing database at {}", database_path.display());
+        info!("initializing database at {}", data_dir_path.display());
+
+        let db = migration::open_database(&data_dir_path).map_err(DatabaseError::Migration)?;
+
+        let profile_name = profiles::get_active_profile();
+        info!("loading series of profile '{}'", profile_name);
+        let profile_trees = ProfileTrees::open(&db, &profile_name).map_err(DatabaseError::Sled)?;
+
+        Ok(Self {
+            db,
+            profile_trees: RwLock::new(profile_trees),
+        })
+    }
+
+    /// Makes the database use the series of the given profile, creating them when they
+    /// don't exist
+    pub fn switch_profile(&self, profile_name: &str) -> Result<(), DatabaseError> {
+        let profile_trees =
+            ProfileTrees::open(&self.db, profile_name).map_err(DatabaseError::Sled)?;
+        *self
+            .profile_trees
+            .write()
+            .expect("failed to write profile trees") = profile_trees;
+        Ok(())
+    }
+
+    /// Opens a tree shared by all the profiles i.e. for the cache store
+    pub fn open_shared_tree(&self, name: &str) -> Result<Tree, DatabaseError> {
+        self.db.open_tree(name).map_err(DatabaseError::Sled)
+    }
+
+    /// Removes a tree opened with `open_shared_tree`
+    pub fn drop_shared_tree(&self, name: &str) -> Result<(), DatabaseError> {
+        self.db
+            .drop_tree(name)
+            .map(|_| ())
+            .map_err(DatabaseError::Sled)
+    }
+
+    /// Opens a tree of the active profile used for storing other than series
+    pub fn open_profile_tree(&self, name: &str) -> Result<Tree, DatabaseError> {
+        self.db
+            .open_tree(profiles::get_profile_storage_name(
+                name,
+                &profiles::get_active_profile(),
+            ))
+            .map_err(DatabaseError::Sled)
+    }
+
+    /// The series tree of the active profile
+    fn series_tree(&self) -> Tree {
+        self.profile_trees
+            .read()
+            .expect("failed to read profile trees")
+            .series
+            .clone()
+    }
+
+    /// The trees of the active profile, the trees stay those of the same profile even when
+    /// the profile is switched while they are used
+    fn active_profile_trees(&self) -> ProfileTrees {
+        self.profile_trees
+            .read()
+            .expect("failed to read profile trees")
+            .clone()
+    }
 
-        database_path.push(DATABASE_FOLDER_NAME);
-        let db = sled::open(database_path).unwrap();
-        if !db.was_recovered() {
-            info!("created a fresh database as none was found");
+    /// The series tree of the given profile, the active profile's when `None`
+    fn get_profile_series_tree(&self, profile_name: Option<&str>) -> Result<Tree, DatabaseError> {
+        let active_profile_trees = self.active_profile_trees();
+        match profile_name {
+            Some(profile_name) if profile_name != active_profile_trees.profile_name => {
+                open_series_tree(&self.db, profile_name).map_err(DatabaseError::Sled)
+            }
+            _ => Ok(active_profile_trees.series),
         }
-        Self { db }
     }
 
     /// Adds the given series to the database.
     ///
     /// # Note
     /// This will overwrite any previous series with the same id.
-    pub fn add_series(&self, series_id: u32, series: &Series) {
-        self.db
-            .insert(series_id.to_string(), bincode::serialize(series).unwrap())
-            .unwrap();
+    pub fn add_series(&self, series_id: u32, series: &Series) -> Result<(), DatabaseError> {
+        self.add_profile_series(None, series_id, series)
+    }
+
+    /// Adds the given series to the series of the given profile, the active profile's when
+    /// `None`
+    fn add_profile_series(
+        &self,
+        profile_name: Option<&str>,
+        series_id: u32,
+        series: &Series,
+    ) -> Result<(), DatabaseError> {
+        let series_bytes = bincode::serialize(series).map_err(DatabaseError::Serialization)?;
+        self.get_profile_series_tree(profile_name)?
+            .insert(series_id.to_string(), series_bytes)
+            .map_err(DatabaseError::Sled)?;
+        Ok(())
     }
 
     /// Removes a series in the database.
     ///
     /// # Note
     /// Does nothing when the series does not exist
-    pub fn remove_series(&self, series_id: u32) {
-        self.db.remove(series_id.to_string()).unwrap();
+    pub fn remove_series(&self, series_id: u32) -> Result<(), DatabaseError> {
+        self.remove_profile_series(None, series_id)
     }
 
-    pub fn get_series(&self, series_id: u32) -> Option<Series> {
-        let series_bytes = self.db.get(series_id.to_string()).unwrap()?;
-        Some(bincode::deserialize(&series_bytes).unwrap())
+    /// Removes a series of the given profile, the active profile's when `None`
+    fn remove_profile_series(
+        &self,
+        profile_name: Option<&str>,
+        series_id: u32,
+    ) -> Result<(), DatabaseError> {
+        self.get_profile_series_tree(profile_name)?
+            .remove(series_id.to_string())
+            .map_err(DatabaseError::Sled)?;
+        Ok(())
     }
 
-    pub fn get_series_collection(&self) -> Vec<Series> {
-        self.db
-            .iter()
-            .values()
-            .map(|series| {
-                let series = series.unwrap();
-                bincode::deserialize(&series).unwrap()
-            })
-            .collect()
+    /// Writes all the pending changes to the disk
+    ///
+    /// The database is periodically flushed in the background, this is only needed when the
+    /// program is about to exit right after modifying the database.
+    pub fn flush(&self) -> Result<(), DatabaseError> {
+        self.db.flush().map_err(DatabaseError::Sled)?;
+        Ok(())
     }
 
-    pub fn get_series_id_collection(&self) -> Vec<String> {
-        self.db
+    /// Retrieves the series with the given id
+    ///
+    /// # Note
+    /// A corrupt series is moved into the quarantine and `DatabaseError::CorruptSeries` is returned.
+    pub fn get_series(&self, series_id: u32) -> Result<Option<Series>, DatabaseError> {
+        let series_id = series_id.to_string();
+        let profile_trees = self.active_profile_trees();
+        let Some(series_bytes) = profile_trees
+            .series
+            .get(&series_id)
+            .map_err(DatabaseError::Sled)?
+        else {
+            return Ok(None);
+        };
+        deserialize_series(&profile_trees, series_id.as_bytes(), &series_bytes).map(Some)
+    }
+
+    /// Retrieves the series with the given id or a new series when it's not in the database
+    ///
+    /// # Note
+    /// A series that fails to be retrieved is returned as an error rather than a new series, as
+    /// the new series would overwrite it's watch history once saved.
+    pub fn get_series_or_new(
+        &self,
+        series_id: u32,
+        series_name: String,
+    ) -> Result<Series, DatabaseError> {
+        Ok(self
+            .get_series(series_id)?
+            .unwrap_or_else(|| Series::new(series_name, series_id)))
+    }
+
+    /// Retrieves all the series in the database
+    ///
+    /// # Note
+    /// Corrupt series are moved into the quarantine and skipped.
+    pub fn get_series_collection(&self) -> Result<Vec<Series>, DatabaseError> {
+        Ok(self
+            .get_ids_and_series()?
+            .into_iter()
+            .map(|(_, series)| series)
+            .collect())
+    }
+
+    pub fn get_series_id_collection(&self) -> Result<Vec<String>, DatabaseError> {
+        self.series_tree()
             .iter()
             .keys()
-            .map(|series| {
-                let series = series.unwrap();
-                // bincode::deserialize(&series).unwrap()
-                String::from_utf8_lossy(&series).into_owned()
+            .map(|series_id| {
+                series_id
+                    .map(|series_id| String::from_utf8_lossy(&series_id).into_owned())
+                    .map_err(DatabaseError::Sled)
             })
             .collect()
     }
 
     /// get series ids and their corresponding series structures
-    pub fn get_ids_and_series(&self) -> Vec<(String, Series)> {
-        self.db
-            .iter()
-            .map(|tup| {
-                let (series_id, series) = tup.unwrap();
+    ///
+    /// # Note
+    /// Corrupt series are moved into the quarantine and skipped.
+    pub fn get_ids_and_series(&self) -> Result<Vec<(String, Series)>, DatabaseError> {
+        let profile_trees = self.active_profile_trees();
+        let mut ids_and_series = Vec::with_capacity(profile_trees.series.len());
+        for record in profile_trees.series.iter() {
+            let (series_id, series_bytes) = record.map_err(DatabaseError::Sled)?;
+            if let Ok(series) = deserialize_series(&profile_trees, &series_id, &series_bytes) {
                 let series_id = String::from_utf8_lossy(&series_id).into_owned();
-                let series = bincode::deserialize::<Series>(&series).unwrap();
-                (series_id, series)
-            })
-            .collect()
+                ids_and_series.push((series_id, series));
+            }
+        }
+        Ok(ids_and_series)
     }
 
     /// Returns the total number of series being tracked
     pub fn get_total_series(&self) -> usize {
-        self.db.len()
+        self.series_tree().len()
     }
 
     /// Get the total amount of seasons watched across all
     /// series in the database
-    pub fn get_total_seasons(&self) -> usize {
-        self.get_series_collection()
+    pub fn get_total_seasons(&self) -> Result<usize, DatabaseError> {
+        Ok(self
+            .get_series_collection()?
             .iter()
             .map(|series| series.get_total_seasons())
-            .sum()
+            .sum())
     }
 
     /// Get the total amount of episodes watched across all
     /// series in the database
-    pub fn get_total_episodes(&self) -> usize {
-        self.get_series_collection()
+    pub fn get_total_episodes(&self) -> Result<usize, DatabaseError> {
+        Ok(self
+            .get_series_collection()?
             .iter()
             .map(|series| series.get_total_episodes())
-            .sum()
+            .sum())
     }
 
-    pub fn export(&self) -> database_transfer::TransferData {
-        database_transfer::TransferData::new(self.get_series_collection())
+    pub fn export(&self) -> Result<database_transfer::TransferData, DatabaseError> {
+        Ok(database_transfer::TransferData::new(
+            self.get_series_collection()?,
+        ))
     }
 
-    pub fn import(&self, transfer_data: &database_transfer::TransferData) {
+    pub fn import(
+        &self,
+        transfer_data: &database_transfer::TransferData,
+    ) -> Result<(), DatabaseError> {
         for series in transfer_data.get_series() {
-            self.add_series(series.id, series);
+            self.add_series(series.id, series)?;
+        }
+        self.db.flush().map_err(DatabaseError::Sled)?;
+        Ok(())
+    }
+
+    /// Checks every series in the database, moving the corrupt ones into the quarantine
+    pub fn repair(&self) -> Result<RepairReport, DatabaseError> {
+        let mut report = RepairReport::default();
+        let profile_trees = self.active_profile_trees();
+
+        for record in profile_trees.series.iter() {
+            let (series_id, series_bytes) = record.map_err(DatabaseError::Sled)?;
+            report.checked_series += 1;
+
+            if let Err(err) = bincode::deserialize::<Series>(&series_bytes) {
+                let series_id = String::from_utf8_lossy(&series_id).into_owned();
+                error!(
+                    "quarantining corrupt series with id '{}': {}",
+                    series_id, err
+                );
+                profile_trees.quarantine(series_id.as_bytes(), &series_bytes)?;
+                report.quarantined_series.push(series_id);
+            }
         }
-        self.db.flush().expect("flushing database");
+
+        report.total_quarantined_series = profile_trees.quarantine.len();
+
+        self.db.flush().map_err(DatabaseError::Sled)?;
+        Ok(report)
     }
 }
 
+/// Reads the series record of the profile, moving it into the quarantine of the profile when
+/// it's corrupt
+fn deserialize_series(
+    profile_trees: &ProfileTrees,
+    series_id: &[u8],
+    series_bytes: &[u8],
+) -> Result<Series, DatabaseError> {
+    let mut series: Series = bincode::deserialize(series_bytes).map_err(|err| {
+        let series_id = String::from_utf8_lossy(series_id).into_owned();
+        error!(
+            "quarantining corrupt series with id '{}': {}",
+            series_id, err
+        );
+        if let Err(err) = profile_trees.quarantine(series_id.as_bytes(), series_bytes) {
+            error!(
+                "failed to quarantine series with id '{}': {}",
+                series_id, err
+            );
+        }
+        DatabaseError::CorruptSeries(series_id, err)
+    })?;
+    series.profile = Some(profile_trees.profile_name.clone());
+    Ok(series)
+}
+
+/// The outcome of `Database::repair`
+#[derive(Debug, Default, Clone)]
+pub struct RepairReport {
+    /// The amount of series checked
+    pub checked_series: usize,
+    /// The ids of the corrupt series quarantined during the repair
+    pub quarantined_series: Vec<String>,
+    /// The amount of series in the quarantine including previously quarantined ones
+    pub total_quarantined_series: usize,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Series {
     id: u32,
     name: String,
     is_tracked: bool,
     seasons: HashMap<u32, Season>,
+    #[serde(default)]
+    review: Review,
+    /// The reviews of episodes keyed by their season and episode numbers, kept apart from
+    /// the seasons as unwatched episodes can be reviewed too
+    #[serde(default)]
+    episode_reviews: HashMap<(u32, Episode), Review>,
+    #[serde(default)]
+    notification_preferences: NotificationPreferences,
+    /// The profile the series belongs to, the series keeps being written into it after
+    /// switching profiles. `None` for series written into whichever profile is active.
+    #[serde(skip)]
+    profile: Option<String>,
 }
 
 impl Series {
@@ -153,6 +461,10 @@ impl Series {
             name,
             is_tracked: false,
             seasons: HashMap::new(),
+            review: Review::default(),
+            episode_reviews: HashMap::new(),
+            notification_preferences: NotificationPreferences::default(),
+            profile: Some(profiles::get_active_profile()),
         }
     }
 
@@ -181,6 +493,44 @@ impl Series {
         self.is_tracked = false;
     }
 
+    pub fn get_review(&self) -> &Review {
+        &self.review
+    }
+
+    pub fn set_review(&mut self, review: Review) {
+        self.review = review;
+    }
+
+    pub fn get_episode_review(&self, season_number: u32, episode: Episode) -> Option<&Review> {
+        self.episode_reviews.get(&(season_number, episode))
+    }
+
+    /// Sets the review of an episode, an empty review removes the episode's review
+    pub fn set_episode_review(&mut self, season_number: u32, episode: Episode, review: Review) {
+        if review.is_empty() {
+            self.episode_reviews.remove(&(season_number, episode));
+        } else {
+            self.episode_reviews
+                .insert((season_number, episode), review);
+        }
+    }
+
+    pub fn get_notification_preferences(&self) -> &NotificationPreferences {
+        &self.notification_preferences
+    }
+
+    pub fn set_notification_preferences(
+        &mut self,
+        notification_preferences: NotificationPreferences,
+    ) {
+        self.notification_preferences = notification_preferences;
+    }
+
+    /// Whether the series or any of it's episodes has been rated or has notes
+    pub fn has_reviews(&self) -> bool {
+        !self.review.is_empty() || !self.episode_reviews.is_empty()
+    }
+
     /// Updates the database with the current Series
     ///    
     /// This method exists  because Series object once created,
@@ -192,16 +542,19 @@ impl Series {
     /// as self.update() is called in it's drop implementation, hence no need of
     /// calling it unless if you want immediate update i.e. there is some code that
     /// would take time to run before the object is dropped.
-    pub fn update(&self) {
-        DB.add_series(self.id, self);
+    pub fn update(&self) -> Result<(), DatabaseError> {
+        DB.add_profile_series(self.profile.as_deref(), self.id, self)
     }
 
     pub fn add_season(&mut self, season_number: u32) {
         self.seasons.insert(season_number, Season::new());
     }
 
+    /// Untracks all the episodes of the given season
     pub fn remove_season(&mut self, season_number: u32) {
-        self.seasons.remove(&season_number);
+        if let Some(season) = self.seasons.get_mut(&season_number) {
+            season.untrack_all_episodes();
+        }
     }
 
     /// adds an episode into the series
@@ -211,7 +564,11 @@ impl Series {
     /// # None
     /// tracks only when the supplied episode is watchable preventing allowing watched episodes that
     /// are released into the future.
-    pub async fn add_episode(&mut self, season_number: u32, episode: Episode) -> bool {
+    pub async fn add_episode(
+        &mut self,
+        season_number: u32,
+        episode: Episode,
+    ) -> Result<bool, ApiError> {
         loop {
             if let Some(season) = self.seasons.get_mut(&season_number) {
                 break season.track_episode(self.id, season_number, episode).await;
@@ -242,7 +599,7 @@ impl Series {
         &mut self,
         season_number: u32,
         episodes_range: RangeInclusive<u32>,
-    ) -> AddResult {
+    ) -> Result<AddResult, ApiError> {
         loop {
             if let Some(season) = self.seasons.get_mut(&season_number) {
                 break season
@@ -254,6 +611,37 @@ impl Series {
         }
     }
 
+    /// adds an episode into the series with the given watch time
+    ///
+    /// # Note
+    /// Does not check if the episode is watchable which is useful when importing episodes
+    /// whose watch time is already known
+    pub fn add_episode_unchecked_with_time(
+        &mut self,
+        season_number: u32,
+        episode: Episode,
+        watched_at: DateTime<Utc>,
+    ) {
+        loop {
+            if let Some(season) = self.seasons.get_mut(&season_number) {
+                season.track_episode_unchecked_with_time(episode, Some(watched_at));
+                break;
+            } else {
+                self.add_season(season_number);
+            }
+        }
+    }
+
+    /// Records a rewatch of an already watched episode
+    ///
+    /// returns false when the episode has not been watched before
+    pub fn rewatch_episode(&mut self, season_number: u32, episode: Episode) -> bool {
+        self.seasons
+            .get_mut(&season_number)
+            .map(|season| season.rewatch_episode(episode))
+            .unwrap_or(false)
+    }
+
     /// removes an episode from the series
     pub fn remove_episode(&mut self, season_number: u32, episode_number: Episode) {
         if let Some(season) = self.seasons.get_mut(&season_number) {
@@ -269,9 +657,19 @@ impl Series {
         self.seasons.get_mut(&season_number)
     }
 
+    /// Returns all the seasons of the series together with their numbers
+    pub fn get_seasons(&self) -> impl Iterator<Item = (u32, &Season)> {
+        self.seasons
+            .iter()
+            .map(|(season_number, season)| (*season_number, season))
+    }
+
     /// Get the total amount of seasons tracked
     pub fn get_total_seasons(&self) -> usize {
-        self.seasons.len()
+        self.seasons
+            .values()
+            .filter(|season| season.get_total_episodes() != 0)
+            .count()
     }
 
     /// Returns total tracked episodes of the season
@@ -293,46 +691,94 @@ impl Series {
             .map(|(season_number, season)| (*season_number, season))
     }
 
+    /// Returns every watch event of the series that happened in the given time range
+    ///
+    /// The events are sorted from the oldest to the newest. Episodes watched before watch
+    /// times were recorded have no timestamp and are therefore never part of the history.
+    pub fn get_watch_history(&self, range: impl RangeBounds<DateTime<Utc>>) -> Vec<WatchEvent> {
+        let mut watch_events: Vec<WatchEvent> =
+            self.seasons
+                .iter()
+                .flat_map(|(season_number, season)| {
+                    season
+                        .episodes
+                        .iter()
+                        .flat_map(move |(episode_number, episode_watch)| {
+                            episode_watch.watch_times().enumerate().map(
+                                move |(index, watched_at)| WatchEvent {
+                                    season_number: *season_number,
+                                    episode_number: *episode_number,
+                                    watched_at,
+                                    is_rewatch: index != 0 || episode_watch.watched_at.is_none(),
+                                },
+                            )
+                        })
+                })
+                .filter(|watch_event| range.contains(&watch_event.watched_at))
+                .collect();
+
+        watch_events.sort_by_key(|watch_event| watch_event.watched_at);
+        watch_events
+    }
+
+    /// Returns the time the series was last watched if known
+    pub fn get_last_watch_time(&self) -> Option<DateTime<Utc>> {
+        self.seasons
+            .values()
+            .flat_map(|season| season.episodes.values())
+            .filter_map(|episode_watch| episode_watch.last_watched_at())
+            .max()
+    }
+
     /// Get the total time that has been spent watching the series
     ///
     /// This method returns SeriesMainInformation associated with the Series
     /// together with it's total runtime
-    pub async fn get_total_average_watchtime(&self) -> (SeriesMainInformation, Option<u32>) {
-        let series_info = caching::series_information::get_series_main_info_with_id(self.id)
-            .await
-            .unwrap();
+    pub async fn get_total_average_watchtime(
+        &self,
+    ) -> Result<(SeriesMainInformation, Option<u32>), ApiError> {
+        let series_info =
+            caching::series_information::get_series_main_info_with_id(self.id).await?;
         let episode_average_watchtime = series_info.average_runtime;
 
-        (
+        Ok((
             series_info,
             episode_average_watchtime.map(|time| time * self.get_total_episodes() as u32),
-        )
+        ))
     }
 }
 
 impl Drop for Series {
     fn drop(&mut self) {
         // Making sure database series is updated
-        self.update();
+        if let Err(err) = self.update() {
+            error!("failed to update series with id '{}': {}", self.id, err);
+        }
 
         // Preventing unwatched and untracked series from cloggin up the database.
         // This can happen when a user adds a series for tracking and untracks the
-        // series without having any episodes checked.
-        if !self.is_tracked() && self.get_total_episodes() == 0 {
-            DB.remove_series(self.id);
+        // series without having any episodes checked. Reviewed series are kept.
+        if !self.is_tracked() && self.get_total_episodes() == 0 && !self.has_reviews() {
+            if let Err(err) = DB.remove_profile_series(self.profile.as_deref(), self.id) {
+                error!("failed to remove series with id '{}': {}", self.id, err);
+            }
         }
     }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Season {
-    episodes: HashSet<Episode>,
+    episodes: HashMap<Episode, EpisodeWatch>,
+    /// The times episodes were unwatched, kept so that unwatching can be synced
+    #[serde(default)]
+    unwatched: HashMap<Episode, DateTime<Utc>>,
 }
 
 impl Season {
     pub fn new() -> Self {
         Self {
-            episodes: HashSet::new(),
+            episodes: HashMap::new(),
+            unwatched: HashMap::new(),
         }
     }
 
@@ -341,22 +787,39 @@ impl Season {
     /// tracks only when the supplied episode is watchable preventing allowing watched episodes that
     /// are released into the future.
     /// This method returns true if the episode was newly added and vice versa is true
+    ///
+    /// When TVmaze can't be reached for the episode list i.e. while offline, the episode is
+    /// queued to be validated once the connectivity is back and false is returned. Other
+    /// failures to get the episode list are returned as errors.
     pub async fn track_episode(
         &mut self,
         series_id: u32,
         season_number: u32,
         episode_number: Episode,
-    ) -> bool {
-        let episode_list = caching::episode_list::EpisodeList::new(series_id)
-            .await
-            .expect("failed to get episode list");
+    ) -> Result<bool, ApiError> {
+        let episode_list = match caching::episode_list::EpisodeList::new(series_id).await {
+            Ok(episode_list) => episode_list,
+            Err(err) if caching::offline::is_offline() || err.is_connection_error() => {
+                warn!(
+                    "failed to get episode list of series with id '{}', queueing the episode for validation: {}",
+                    series_id, err
+                );
+                caching::offline::queue_episode_validation(
+                    series_id,
+                    season_number,
+                    episode_number,
+                );
+                return Ok(false);
+            }
+            Err(err) => return Err(err),
+        };
 
         if let Some(episode) = episode_list.get_episode(season_number, episode_number) {
             if caching::episode_list::EpisodeList::is_episode_watchable(episode) == Some(true) {
-                return self.episodes.insert(episode_number);
+                return Ok(self.insert_episode(episode_number, Some(Utc::now())));
             }
         }
-        false
+        Ok(false)
     }
 
     /// adds the given episode to tracking
@@ -364,7 +827,35 @@ impl Season {
     /// # Note
     /// Does not check if the episode is watchable which is useful when importing episodes
     pub fn track_episode_unchecked(&mut self, episode_number: Episode) {
-        self.episodes.insert(episode_number);
+        self.insert_episode(episode_number, Some(Utc::now()));
+    }
+
+    /// adds the given episode to tracking with the given watch time, `None` when the time
+    /// is unknown
+    ///
+    /// # Note
+    /// Does not check if the episode is watchable which is useful when importing episodes
+    pub fn track_episode_unchecked_with_time(
+        &mut self,
+        episode_number: Episode,
+        watched_at: Option<DateTime<Utc>>,
+    ) {
+        self.insert_episode(episode_number, watched_at);
+    }
+
+    /// Inserts the episode if it's not already present, returning true when newly inserted
+    fn insert_episode(
+        &mut self,
+        episode_number: Episode,
+        watched_at: Option<DateTime<Utc>>,
+    ) -> bool {
+        if self.episodes.contains_key(&episode_number) {
+            return false;
+        }
+        self.unwatched.remove(&episode_number);
+        self.episodes
+            .insert(episode_number, EpisodeWatch::new(watched_at));
+        true
     }
 
     /// adds a range of episode to be tracked
@@ -376,39 +867,87 @@ impl Season {
         series_id: u32,
         season_number: u32,
         episodes_range: RangeInclusive<u32>,
-    ) -> AddResult {
+    ) -> Result<AddResult, ApiError> {
         let mut already_added_items = 0;
         for episode_number in episodes_range.clone() {
             if !self
                 .track_episode(series_id, season_number, episode_number)
-                .await
+                .await?
             {
                 already_added_items += 1;
             };
         }
 
-        if already_added_items == 0 {
+        Ok(if already_added_items == 0 {
             AddResult::Full
         } else if already_added_items == episodes_range.count() {
             AddResult::None
         } else {
             AddResult::Partial
-        }
+        })
     }
 
     pub fn untrack_episode(&mut self, episode: Episode) {
-        self.episodes.remove(&episode);
+        if self.episodes.remove(&episode).is_some() {
+            self.unwatched.insert(episode, Utc::now());
+        }
+    }
+
+    /// Untracks all the tracked episodes of the season
+    pub fn untrack_all_episodes(&mut self) {
+        let unwatch_time = Utc::now();
+        self.unwatched.extend(
+            self.episodes
+                .drain()
+                .map(|(episode, _)| (episode, unwatch_time)),
+        );
+    }
+
+    /// Records a rewatch of the given episode at the current time
+    ///
+    /// returns false when the episode has not been watched before
+    pub fn rewatch_episode(&mut self, episode: Episode) -> bool {
+        if let Some(episode_watch) = self.episodes.get_mut(&episode) {
+            episode_watch.rewatches.push(Utc::now());
+            true
+        } else {
+            false
+        }
     }
 
     pub fn is_episode_watched(&self, episode: Episode) -> bool {
-        self.episodes.contains(&episode)
+        self.episodes.contains_key(&episode)
+    }
+
+    /// Returns the watch history of the given episode if it's watched
+    pub fn get_episode_watch(&self, episode: Episode) -> Option<&EpisodeWatch> {
+        self.episodes.get(&episode)
+    }
+
+    /// Returns all the watched episodes together with their watch histories
+    pub fn get_episode_watches(&self) -> impl Iterator<Item = (Episode, &EpisodeWatch)> {
+        self.episodes
+            .iter()
+            .map(|(episode, episode_watch)| (*episode, episode_watch))
+    }
+
+    /// Returns the episodes that were unwatched together with the time they were unwatched
+    pub fn get_unwatched_episodes(&self) -> impl Iterator<Item = (Episode, DateTime<Utc>)> + '_ {
+        self.unwatched
+            .iter()
+            .map(|(episode, unwatch_time)| (*episode, *unwatch_time))
+    }
+
+    /// Returns the time the given episode was unwatched if it's not watched again since
+    pub fn get_unwatch_time(&self, episode: Episode) -> Option<DateTime<Utc>> {
+        self.unwatched.get(&episode).copied()
     }
 
     /// Return the last watched episode
     ///
     /// This obviously skip any unwatched episode in between and just returns the highest
     pub fn get_last_episode(&self) -> Option<Episode> {
-        self.episodes.iter().max().copied()
+        self.episodes.keys().max().copied()
     }
 
     /// Get the total amount of episodes in the season
@@ -425,6 +964,175 @@ impl Default for Season {
 
 pub type Episode = u32;
 
+/// The watch history of a single episode
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EpisodeWatch {
+    /// The time the episode was first watched
+    ///
+    /// This is `None` for episodes watched before watch times were recorded
+    watched_at: Option<DateTime<Utc>>,
+    /// The times the episode was watched again after the first watch
+    rewatches: Vec<DateTime<Utc>>,
+}
+
+impl EpisodeWatch {
+    fn new(watched_at: Option<DateTime<Utc>>) -> Self {
+        Self {
+            watched_at,
+            rewatches: vec![],
+        }
+    }
+
+    /// The time the episode was first watched if known
+    pub fn get_watched_at(&self) -> Option<DateTime<Utc>> {
+        self.watched_at
+    }
+
+    /// The number of times the episode was watched after the first watch
+    pub fn get_rewatch_count(&self) -> usize {
+        self.rewatches.len()
+    }
+
+    /// The time the episode was last watched if known
+    pub fn last_watched_at(&self) -> Option<DateTime<Utc>> {
+        self.rewatches.last().copied().or(self.watched_at)
+    }
+
+    /// All the known watch times of the episode from the oldest to the newest
+    pub fn watch_times(&self) -> impl Iterator<Item = DateTime<Utc>> + '_ {
+        self.watched_at
+            .into_iter()
+            .chain(self.rewatches.iter().copied())
+    }
+}
+
+/// The lowest and highest personal ratings
+pub const RATING_RANGE: RangeInclusive<u8> = 1..=10;
+
+/// A personal rating and notes of a series or an episode
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct Review {
+    rating: Option<u8>,
+    notes: String,
+}
+
+impl Review {
+    pub fn new(rating: Option<u8>, notes: String) -> Self {
+        let mut review = Self::default();
+        review.set_rating(rating);
+        review.set_notes(notes);
+        review
+    }
+
+    /// The rating within `RATING_RANGE` if any
+    ///
+    /// Ratings outside the range i.e. from a hand edited import are treated as no rating.
+    pub fn get_rating(&self) -> Option<u8> {
+        self.rating.filter(|rating| RATING_RANGE.contains(rating))
+    }
+
+    /// Sets the rating, clamping it into `RATING_RANGE`
+    pub fn set_rating(&mut self, rating: Option<u8>) {
+        self.rating = rating.map(|rating| rating.clamp(*RATING_RANGE.start(), *RATING_RANGE.end()));
+    }
+
+    pub fn get_notes(&self) -> &str {
+        &self.notes
+    }
+
+    pub fn set_notes(&mut self, notes: String) {
+        self.notes = notes.trim().to_owned();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.get_rating().is_none() && self.notes.is_empty()
+    }
+}
+
+/// Per series overrides of the notification settings
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct NotificationPreferences {
+    /// Whether no notifications are sent for the series' releases
+    muted: bool,
+    /// The times in minutes before a release to send reminders at, sorted from the earliest
+    /// reminder. The global notification time is used when empty.
+    reminder_lead_times: Vec<u32>,
+}
+
+impl NotificationPreferences {
+    pub fn is_muted(&self) -> bool {
+        self.muted
+    }
+
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+    }
+
+    pub fn get_reminder_lead_times(&self) -> &[u32] {
+        &self.reminder_lead_times
+    }
+
+    /// Adds a reminder the given minutes before a release, returns false when there is
+    /// already one at that time
+    pub fn add_reminder(&mut self, lead_time: u32) -> bool {
+        if self.reminder_lead_times.contains(&lead_time) {
+            return false;
+        }
+        self.reminder_lead_times.push(lead_time);
+        self.reminder_lead_times.sort_unstable_by(|a, b| b.cmp(a));
+        true
+    }
+
+    pub fn remove_reminder(&mut self, lead_time: u32) {
+        self.reminder_lead_times
+            .retain(|reminder_lead_time| *reminder_lead_time != lead_time);
+    }
+
+    /// The times in minutes before a release the reminders should be sent at, taking the
+    /// global notification time when the series has no reminders of it's own
+    pub fn resolve_reminder_lead_times(&self, global_lead_time: u32) -> Vec<u32> {
+        if self.muted {
+            vec![]
+        } else if self.reminder_lead_times.is_empty() {
+            vec![global_lead_time]
+        } else {
+            self.reminder_lead_times.clone()
+        }
+    }
+}
+
+/// A single watch of an episode as returned by `Series::get_watch_history`
+#[derive(Debug, Clone)]
+pub struct WatchEvent {
+    pub season_number: u32,
+    pub episode_number: Episode,
+    pub watched_at: DateTime<Utc>,
+    pub is_rewatch: bool,
+}
+
+/// Gets the key of a record about an episode in a tree, the records of a series being kept
+/// together as the series id comes first
+pub fn get_episode_key(series_id: u32, season: u32, episode: u32) -> [u8; 12] {
+    let mut key = [0; 12];
+    key[..4].copy_from_slice(&series_id.to_be_bytes());
+    key[4..8].copy_from_slice(&season.to_be_bytes());
+    key[8..].copy_from_slice(&episode.to_be_bytes());
+    key
+}
+
+/// Parses a key made by `get_episode_key` into the series id, the season and the episode
+pub fn parse_episode_key(key: &[u8]) -> Option<(u32, u32, u32)> {
+    let key: &[u8; 12] = key.try_into().ok()?;
+    let read_u32 = |index: usize| {
+        u32::from_be_bytes(
+            key[index..index + 4]
+                .try_into()
+                .expect("slice of four bytes"),
+        )
+    };
+    Some((read_u32(0), read_u32(4), read_u32(8)))
+}
+
 /// Indicates if adding episodes has been fully added(when none of the episodes were present before adding) or
 /// partial(when some were already present) and none when all the added apisode where already present
 #[derive(Debug, Clone)]
@@ -440,16 +1148,18 @@ pub enum AddResult {
 pub mod database_transfer {
     //! Implementations of importing and exporting series tracking data
 
-    use std::{io, path};
+    use std::{io, mem::ManuallyDrop, path};
+
+    use chrono::{DateTime, Utc};
 
-    use super::Series;
-    use super::DB;
+    use super::migration::{v2, v3, v4};
+    use super::{DatabaseError, Episode, Series, DB};
 
     use ron::ser;
     use serde::{Deserialize, Serialize};
     use thiserror::Error;
 
-    const CURRENT_DATA_VERSION: u16 = 1;
+    pub const CURRENT_DATA_VERSION: u16 = 2;
 
     #[derive(Debug, Error)]
     pub enum ImportError {
@@ -457,8 +1167,23 @@ pub mod database_transfer {
         Io(io::Error),
         #[error("incompatible version. Expected version {0}, found {1}")]
         Version(u16, u16),
+        #[error(
+            "the data comes from a newer version of Series Troxide (data version {0}, \
+            supported up to {1}), update Series Troxide to import it"
+        )]
+        NewerVersion(u16, u16),
         #[error("deserialization error: {0}")]
         Deserialization(ron::de::SpannedError),
+        #[error("database error: {0}")]
+        Database(DatabaseError),
+    }
+
+    #[derive(Debug, Error)]
+    pub enum ExportError {
+        #[error("IO error: {0}")]
+        Io(io::Error),
+        #[error("database error: {0}")]
+        Database(DatabaseError),
     }
 
     #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -475,43 +1200,69 @@ pub mod database_transfer {
             }
         }
 
-        fn error_when_incompatible(import_data_version: u16) -> Result<(), ImportError> {
-            if import_data_version == CURRENT_DATA_VERSION {
-                Ok(())
-            } else {
-                Err(ImportError::Version(
-                    CURRENT_DATA_VERSION,
-                    import_data_version,
-                ))
+        /// Reads transfer data of any supported version, upgrading older versions to the
+        /// current one
+        fn from_ron_str(ron_str: &str) -> Result<Self, ImportError> {
+            let data_version = ron::from_str::<versions::DataVersion>(ron_str)
+                .map_err(ImportError::Deserialization)?
+                .version;
+
+            match data_version {
+                CURRENT_DATA_VERSION => ron::from_str::<Self>(ron_str),
+                1 => ron::from_str::<versions::v1::TransferData>(ron_str).map(Self::from),
+                version if version > CURRENT_DATA_VERSION => {
+                    return Err(ImportError::NewerVersion(version, CURRENT_DATA_VERSION))
+                }
+                version => return Err(ImportError::Version(CURRENT_DATA_VERSION, version)),
             }
+            .map_err(ImportError::Deserialization)
         }
 
         pub fn blocking_import(path: impl AsRef<path::Path>) -> Result<Self, ImportError> {
             let import = std::fs::read_to_string(path).map_err(ImportError::Io)?;
-            let imported_data =
-                ron::from_str::<Self>(&import).map_err(ImportError::Deserialization)?;
-
-            Self::error_when_incompatible(imported_data.version).map(|_| imported_data)
+            Self::from_ron_str(&import)
         }
 
         pub fn blocking_import_to_db(path: impl AsRef<path::Path>) -> Result<(), ImportError> {
-            DB.import(&Self::blocking_import(path)?);
-            Ok(())
+            DB.import(&Self::blocking_import(path)?)
+                .map_err(ImportError::Database)
         }
 
         pub async fn async_import(path: impl AsRef<path::Path>) -> Result<Self, ImportError> {
             let import = tokio::fs::read_to_string(path)
                 .await
                 .map_err(ImportError::Io)?;
-            let imported_data =
-                ron::from_str::<Self>(&import).map_err(ImportError::Deserialization)?;
-
-            Self::error_when_incompatible(imported_data.version).map(|_| imported_data)
+            Self::from_ron_str(&import)
         }
 
         pub async fn async_import_to_db(path: impl AsRef<path::Path>) -> Result<(), ImportError> {
-            DB.import(&Self::async_import(path).await?);
-            Ok(())
+            DB.import(&Self::async_import(path).await?)
+                .map_err(ImportError::Database)
+        }
+
+        /// Compares the transfer data with the database, returning what importing it would
+        /// change without touching the database
+        pub fn get_import_changes(&self) -> Result<ImportChanges, DatabaseError> {
+            let mut import_changes = ImportChanges::default();
+
+            for series in &self.series {
+                // The database series would write itself back into the database when dropped
+                let Some(current_series) = DB.get_series(series.id)?.map(ManuallyDrop::new) else {
+                    import_changes
+                        .new_series
+                        .push(SeriesChanges::new(None, series));
+                    continue;
+                };
+
+                let series_changes = SeriesChanges::new(Some(&current_series), series);
+                if series_changes.has_changes() {
+                    import_changes.changed_series.push(series_changes);
+                } else {
+                    import_changes.unchanged_series += 1;
+                }
+            }
+
+            Ok(import_changes)
         }
 
         pub fn get_series(&self) -> &[Series] {
@@ -528,8 +1279,11 @@ pub mod database_transfer {
             std::fs::write(path, ron_str)
         }
 
-        pub fn blocking_export_from_db(path: impl AsRef<path::Path>) -> Result<(), io::Error> {
-            DB.export().blocking_export(path)
+        pub fn blocking_export_from_db(path: impl AsRef<path::Path>) -> Result<(), ExportError> {
+            DB.export()
+                .map_err(ExportError::Database)?
+                .blocking_export(path)
+                .map_err(ExportError::Io)
         }
 
         pub async fn async_export(&self, path: impl AsRef<path::Path>) -> Result<(), io::Error> {
@@ -537,8 +1291,419 @@ pub mod database_transfer {
             tokio::fs::write(path, ron_str).await
         }
 
-        pub async fn async_export_from_db(path: impl AsRef<path::Path>) -> Result<(), io::Error> {
-            DB.export().async_export(path).await
+        pub async fn async_export_from_db(path: impl AsRef<path::Path>) -> Result<(), ExportError> {
+            DB.export()
+                .map_err(ExportError::Database)?
+                .async_export(path)
+                .await
+                .map_err(ExportError::Io)
+        }
+    }
+
+    impl From<versions::v1::TransferData> for TransferData {
+        fn from(value: versions::v1::TransferData) -> Self {
+            let series = value
+                .series
+                .into_iter()
+                .map(|series| {
+                    Series::from(v4::Series::from(v3::Series::from(v2::Series::from(series))))
+                })
+                .collect();
+
+            Self::new(series)
+        }
+    }
+
+    mod versions {
+        //! Transfer data of the older versions, every version converts into the next one
+        //! through `From` up to the current `TransferData`
+
+        use serde::Deserialize;
+
+        /// Only the version of transfer data of any version
+        #[derive(Deserialize)]
+        #[serde(rename = "TransferData")]
+        pub struct DataVersion {
+            pub version: u16,
+        }
+
+        pub mod v1 {
+            //! Transfer data of version 1, the series had no watch times
+
+            use serde::Deserialize;
+
+            use crate::core::database::migration::v1;
+
+            #[derive(Deserialize)]
+            pub struct TransferData {
+                pub series: Vec<v1::Series>,
+            }
+        }
+    }
+
+    /// What importing transfer data would change in the database
+    #[derive(Debug, Default)]
+    pub struct ImportChanges {
+        /// The series not yet in the database
+        pub new_series: Vec<SeriesChanges>,
+        /// The series in the database that the import changes
+        pub changed_series: Vec<SeriesChanges>,
+        pub unchanged_series: usize,
+    }
+
+    #[derive(Debug)]
+    pub struct SeriesChanges {
+        pub series_id: u32,
+        pub series_name: String,
+        /// The episodes watched in the import only
+        pub added_episodes: Vec<(u32, Episode)>,
+        /// The episodes watched in the database only, the import unwatches them as it
+        /// replaces the series
+        pub removed_episodes: Vec<(u32, Episode)>,
+        pub conflicts: Vec<ImportConflict>,
+    }
+
+    impl SeriesChanges {
+        fn new(current_series: Option<&Series>, imported_series: &Series) -> Self {
+            let mut added_episodes = vec![];
+            let mut removed_episodes = vec![];
+            let mut conflicts = vec![];
+
+            let mut current_episodes = current_series.map(get_episode_watches).unwrap_or_default();
+            for (season_number, episode, imported_watch) in get_episode_watches(imported_series) {
+                let current_watch = current_episodes
+                    .iter()
+                    .position(|(current_season_number, current_episode, _)| {
+                        *current_season_number == season_number && *current_episode == episode
+                    })
+                    .map(|index| current_episodes.remove(index).2);
+
+                match current_watch {
+                    Some(current_watch) => {
+                        if current_watch.watched_at != imported_watch.watched_at
+                            || current_watch.rewatches != imported_watch.rewatches
+                        {
+                            conflicts.push(ImportConflict::WatchHistory {
+                                season: season_number,
+                                episode,
+                                current: current_watch.last_watched_at(),
+                                imported: imported_watch.last_watched_at(),
+                            })
+                        }
+                    }
+                    None => added_episodes.push((season_number, episode)),
+                }
+            }
+            removed_episodes.extend(
+                current_episodes
+                    .into_iter()
+                    .map(|(season_number, episode, _)| (season_number, episode)),
+            );
+
+            if let Some(current_series) = current_series {
+                if current_series.is_tracked != imported_series.is_tracked {
+                    conflicts.push(ImportConflict::Tracking {
+                        current: current_series.is_tracked,
+                        imported: imported_series.is_tracked,
+                    });
+                }
+                if !current_series.review.is_empty()
+                    && current_series.review != imported_series.review
+                {
+                    conflicts.push(ImportConflict::Review);
+                }
+
+                let mut episode_review_conflicts: Vec<_> = current_series
+                    .episode_reviews
+                    .iter()
+                    .filter(|(key, review)| {
+                        !review.is_empty()
+                            && imported_series.episode_reviews.get(key) != Some(review)
+                    })
+                    .map(
+                        |((season_number, episode), _)| ImportConflict::EpisodeReview {
+                            season: *season_number,
+                            episode: *episode,
+                        },
+                    )
+                    .collect();
+                episode_review_conflicts.sort_by_key(|conflict| match conflict {
+                    ImportConflict::EpisodeReview { season, episode } => (*season, *episode),
+                    _ => (0, 0),
+                });
+                conflicts.extend(episode_review_conflicts);
+            }
+
+            Self {
+                series_id: imported_series.id,
+                series_name: imported_series.name.clone(),
+                added_episodes,
+                removed_episodes,
+                conflicts,
+            }
+        }
+
+        pub fn has_changes(&self) -> bool {
+            !self.added_episodes.is_empty()
+                || !self.removed_episodes.is_empty()
+                || !self.conflicts.is_empty()
+        }
+    }
+
+    /// The watched episodes of the series sorted by their season and episode numbers
+    fn get_episode_watches(series: &Series) -> Vec<(u32, Episode, &super::EpisodeWatch)> {
+        let mut episode_watches: Vec<_> = series
+            .get_seasons()
+            .flat_map(|(season_number, season)| {
+                season
+                    .get_episode_watches()
+                    .map(move |(episode, episode_watch)| (season_number, episode, episode_watch))
+            })
+            .collect();
+        episode_watches.sort_by_key(|(season_number, episode, _)| (*season_number, *episode));
+        episode_watches
+    }
+
+    /// Data in the database that the import would overwrite
+    #[derive(Debug)]
+    pub enum ImportConflict {
+        Tracking {
+            current: bool,
+            imported: bool,
+        },
+        /// An episode watched in both with different watch times
+        WatchHistory {
+            season: u32,
+            episode: Episode,
+            current: Option<DateTime<Utc>>,
+            imported: Option<DateTime<Utc>>,
+        },
+        Review,
+        EpisodeReview {
+            season: u32,
+            episode: Episode,
+        },
+    }
+
+    impl std::fmt::Display for ImportConflict {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            let format_time = |time: &Option<DateTime<Utc>>| {
+                time.map(|time| time.format("%Y-%m-%d %H:%M").to_string())
+                    .unwrap_or_else(|| "unknown time".to_owned())
+            };
+
+            match self {
+                ImportConflict::Tracking { current, imported } => write!(
+                    f,
+                    "tracking changes from {} to {}",
+                    get_tracking_str(*current),
+                    get_tracking_str(*imported)
+                ),
+                ImportConflict::WatchHistory {
+                    season,
+                    episode,
+                    current,
+                    imported,
+                } => write!(
+                    f,
+                    "S{:02}E{:02} last watched at {} is replaced by {}",
+                    season,
+                    episode,
+                    format_time(current),
+                    format_time(imported)
+                ),
+                ImportConflict::Review => write!(f, "series review is replaced"),
+                ImportConflict::EpisodeReview { season, episode } => {
+                    write!(f, "S{:02}E{:02} review is replaced", season, episode)
+                }
+            }
+        }
+    }
+
+    fn get_tracking_str(is_tracked: bool) -> &'static str {
+        if is_tracked {
+            "tracked"
+        } else {
+            "untracked"
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::core::testing;
+
+        #[tokio::test]
+        async fn version_1_data_is_upgraded_without_watch_times() {
+            let _database = testing::lock_database().await;
+
+            let transfer_data = TransferData::from_ron_str(
+                r#"(
+                    version: 1,
+                    series: [
+                        (
+                            id: 1,
+                            name: "Under the Dome",
+                            is_tracked: true,
+                            seasons: {1: (episodes: [1, 2])},
+                        ),
+                    ],
+                )"#,
+            )
+            .expect("failed to read version 1 data");
+
+            assert_eq!(transfer_data.version, CURRENT_DATA_VERSION);
+            let series = &transfer_data.series[0];
+            assert!(series.is_tracked());
+            let season = series.get_season(1).expect("season not upgraded");
+            for episode in [1, 2] {
+                let episode_watch = season
+                    .get_episode_watch(episode)
+                    .expect("episode not upgraded");
+                assert_eq!(episode_watch.get_watched_at(), None);
+            }
+            assert!(series.get_watch_history(..).is_empty());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use std::ops::Bound;
+
+    use super::*;
+    use crate::core::testing;
+
+    fn day(day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2023, 1, day, 0, 0, 0).unwrap()
+    }
+
+    fn get_history_episodes(series: &Series, range: impl RangeBounds<DateTime<Utc>>) -> Vec<u32> {
+        series
+            .get_watch_history(range)
+            .iter()
+            .map(|watch_event| watch_event.episode_number)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn watch_history_respects_the_range_bounds() {
+        let _database = testing::lock_database().await;
+
+        let mut series = Series::new("Under the Dome".to_owned(), 1);
+        for episode in 1..=3 {
+            series.add_episode_unchecked_with_time(1, episode, day(episode));
+        }
+
+        assert_eq!(get_history_episodes(&series, ..), [1, 2, 3]);
+        assert_eq!(get_history_episodes(&series, day(1)..day(3)), [1, 2]);
+        assert_eq!(get_history_episodes(&series, day(1)..=day(3)), [1, 2, 3]);
+        assert_eq!(get_history_episodes(&series, ..day(2)), [1]);
+        assert_eq!(
+            get_history_episodes(&series, (Bound::Excluded(day(1)), Bound::Unbounded)),
+            [2, 3]
+        );
+        assert!(get_history_episodes(&series, day(4)..).is_empty());
+    }
+
+    #[tokio::test]
+    async fn rewatches_are_recorded_in_the_watch_history() {
+        let _database = testing::lock_database().await;
+
+        let mut series = Series::new("Under the Dome".to_owned(), 1);
+        series.add_episode_unchecked_with_time(1, 1, day(1));
+
+        assert!(series.rewatch_episode(1, 1));
+        assert!(!series.rewatch_episode(1, 2));
+        assert!(!series.rewatch_episode(2, 1));
+
+        let episode_watch = series
+            .get_season(1)
+            .and_then(|season| season.get_episode_watch(1))
+            .expect("episode not watched");
+        assert_eq!(episode_watch.get_watched_at(), Some(day(1)));
+        assert_eq!(episode_watch.get_rewatch_count(), 1);
+
+        let watch_history = series.get_watch_history(..);
+        assert_eq!(watch_history.len(), 2);
+        assert!(!watch_history[0].is_rewatch);
+        assert!(watch_history[1].is_rewatch);
+        assert_eq!(
+            Some(watch_history[1].watched_at),
+            series.get_last_watch_time()
+        );
+    }
+
+    #[tokio::test]
+    async fn episode_list_errors_are_not_queued_for_validation() {
+        let _database = testing::lock_database().await;
+        let pending_validations = || caching::offline::subscribe().borrow().pending_validations;
+        let initial_pending_validations = pending_validations();
+
+        // The mock server has no episode list for this series
+        let mut season = Season::new();
+        let result = season.track_episode(999_999, 1, 1).await;
+
+        assert!(matches!(result, Err(ApiError::Deserialization(..))));
+        assert_eq!(pending_validations(), initial_pending_validations);
+    }
+
+    #[tokio::test]
+    async fn series_are_written_into_their_profile_after_a_switch() {
+        let _database = testing::lock_database().await;
+        let other_profile = "series-owner-test";
+        if !profiles::profile_exists(other_profile) {
+            profiles::add_profile(other_profile).expect("failed to add the test profile");
         }
+
+        let mut series = Series::new("Under the Dome".to_owned(), 1);
+        series.mark_tracked();
+        drop(series);
+        let mut series = DB
+            .get_series(1)
+            .expect("failed to read the series")
+            .expect("series not saved");
+
+        profiles::switch_profile(other_profile)
+            .await
+            .expect("failed to switch profile");
+        series.mark_untracked();
+        series.add_episode_unchecked_with_time(1, 1, Utc::now());
+        drop(series);
+        let other_profile_series = DB.get_series(1).expect("failed to read the series");
+        profiles::switch_profile(profiles::DEFAULT_PROFILE)
+            .await
+            .expect("failed to switch back to the default profile");
+
+        assert!(other_profile_series.is_none());
+        let series = DB
+            .get_series(1)
+            .expect("failed to read the series")
+            .expect("series not kept in it's profile");
+        assert!(!series.is_tracked());
+        assert!(series.get_season(1).is_some());
+    }
+
+    #[tokio::test]
+    async fn corrupt_series_are_quarantined_in_the_profile_they_were_read_from() {
+        let _database = testing::lock_database().await;
+        let other_profile = "quarantine-owner-test";
+        let other_trees =
+            ProfileTrees::open(&DB.db, other_profile).expect("failed to open the profile trees");
+        let active_trees = DB.active_profile_trees();
+        let series_id = b"999998";
+
+        let result = deserialize_series(&other_trees, series_id, b"not a series");
+
+        assert!(matches!(result, Err(DatabaseError::CorruptSeries(..))));
+        assert!(other_trees
+            .quarantine
+            .contains_key(series_id)
+            .expect("failed to read the quarantine"));
+        assert!(!active_trees
+            .quarantine
+            .contains_key(series_id)
+            .expect("failed to read the quarantine"));
     }
 }
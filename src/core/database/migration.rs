@@ -0,0 +1,831 @@
+//! # Database schema migrations
+//!
+//! The series are stored in the database as raw bincode, so any change to the database
+//! structures makes the previously stored data unreadable. To prevent that, the database
+//! records it's schema version in a metadata tree and every schema change comes with a
+//! migration that upgrades the stored data from the previous version in place.
+//!
+//! Older versions of series troxide encoded the schema version in the database folder name
+//! i.e. `series-troxide-db-1`. Such databases are detected, moved into the current database
+//! folder and upgraded like any other outdated database.
+//!
+//! Databases without a recorded schema version have their version detected from the layout
+//! of their series records.
+//!
+//! A backup of the database folder is always taken before running any migration, and the
+//! series records a migration can't read are moved into the quarantine of their profile.
+
+use std::io;
+use std::path;
+
+use sled::transaction::{TransactionError, Transactional};
+use sled::{Batch, Db, IVec};
+use thiserror::Error;
+use tracing::{error, info, warn};
+
+use super::{EpisodeWatch, NotificationPreferences, ProfileTrees, Season, Series};
+use crate::core::profiles;
+
+/// The schema version of the database structures in this version of the program
+pub const CURRENT_SCHEMA_VERSION: u32 = 5;
+
+const DATABASE_FOLDER_NAME: &str = "series-troxide-db";
+/// Older databases had their schema version appended to this prefix as their folder name
+const LEGACY_DATABASE_FOLDER_PREFIX: &str = "series-troxide-db-";
+const BACKUP_FOLDER_SUFFIX: &str = "backup";
+
+const METADATA_TREE_NAME: &str = "metadata";
+const SCHEMA_VERSION_KEY: &str = "schema-version";
+
+/// A migration upgrades the series tree of a profile from one schema version to the next one,
+/// moving the series it can't read into the quarantine of the profile
+type Migration = fn(&ProfileTrees) -> Result<(), MigrationError>;
+
+/// Checks whether a series record has the structure of a schema version
+type RecordReader = fn(&[u8]) -> bool;
+
+/// All the migrations, the migration at index `n` upgrades the schema version `n` to `n + 1`
+const MIGRATIONS: [Migration; CURRENT_SCHEMA_VERSION as usize] = [
+    migrate_v0_to_v1,
+    migrate_v1_to_v2,
+    migrate_v2_to_v3,
+    migrate_v3_to_v4,
+    migrate_v4_to_v5,
+];
+
+#[derive(Debug, Error)]
+pub enum MigrationError {
+    #[error("database error: {0}")]
+    Sled(sled::Error),
+
+    #[error("IO error: {0}")]
+    Io(io::Error),
+
+    #[error("invalid schema version record in the database metadata")]
+    InvalidSchemaVersion,
+
+    #[error("database schema version {0} is newer than the supported version {1}")]
+    NewerSchemaVersion(u32, u32),
+}
+
+/// Opens the database found in the given data directory, upgrading it to the current schema
+/// version when it's outdated
+///
+/// A fresh database is created when none is found.
+pub fn open_database(data_dir_path: &path::Path) -> Result<Db, MigrationError> {
+    let database_path = data_dir_path.join(DATABASE_FOLDER_NAME);
+
+    if !database_path.exists() {
+        if let Some((schema_version, legacy_database_path)) =
+            find_legacy_database(data_dir_path).map_err(MigrationError::Io)?
+        {
+            info!(
+                "found a legacy database with schema version {} at {}",
+                schema_version,
+                legacy_database_path.display()
+            );
+
+            if schema_version > CURRENT_SCHEMA_VERSION {
+                return Err(MigrationError::NewerSchemaVersion(
+                    schema_version,
+                    CURRENT_SCHEMA_VERSION,
+                ));
+            }
+
+            backup_database(&legacy_database_path, schema_version)?;
+            std::fs::rename(&legacy_database_path, &database_path).map_err(MigrationError::Io)?;
+
+            let db = sled::open(&database_path).map_err(MigrationError::Sled)?;
+            set_schema_version(&db, schema_version)?;
+            run_migrations(&db, schema_version)?;
+            return Ok(db);
+        }
+    }
+
+    let db = sled::open(&database_path).map_err(MigrationError::Sled)?;
+
+    if !db.was_recovered() {
+        info!("created a fresh database as none was found");
+        set_schema_version(&db, CURRENT_SCHEMA_VERSION)?;
+        return Ok(db);
+    }
+
+    let recorded_schema_version = get_schema_version(&db)?;
+    let schema_version = match recorded_schema_version {
+        Some(schema_version) => schema_version,
+        None => {
+            let schema_version = detect_schema_version(&db)?;
+            warn!(
+                "no schema version recorded in the database, detected schema version {} from the series records",
+                schema_version
+            );
+            schema_version
+        }
+    };
+
+    if schema_version > CURRENT_SCHEMA_VERSION {
+        return Err(MigrationError::NewerSchemaVersion(
+            schema_version,
+            CURRENT_SCHEMA_VERSION,
+        ));
+    }
+
+    if schema_version < CURRENT_SCHEMA_VERSION {
+        // Sled holds a lock on the database folder, so the database has to be
+        // closed before the backup can be safely taken.
+        db.flush().map_err(MigrationError::Sled)?;
+        drop(db);
+        backup_database(&database_path, schema_version)?;
+
+        let db = sled::open(&database_path).map_err(MigrationError::Sled)?;
+        run_migrations(&db, schema_version)?;
+        return Ok(db);
+    }
+
+    if recorded_schema_version.is_none() {
+        set_schema_version(&db, schema_version)?;
+    }
+
+    Ok(db)
+}
+
+/// Retrieves the schema version recorded in the database, `None` when no version is recorded
+pub fn get_schema_version(db: &Db) -> Result<Option<u32>, MigrationError> {
+    let metadata = db
+        .open_tree(METADATA_TREE_NAME)
+        .map_err(MigrationError::Sled)?;
+
+    metadata
+        .get(SCHEMA_VERSION_KEY)
+        .map_err(MigrationError::Sled)?
+        .map(|version_bytes| {
+            let version_bytes: [u8; 4] = version_bytes
+                .as_ref()
+                .try_into()
+                .map_err(|_| MigrationError::InvalidSchemaVersion)?;
+            Ok(u32::from_be_bytes(version_bytes))
+        })
+        .transpose()
+}
+
+/// Detects the schema version of a database without a recorded version from the layout of
+/// it's series records
+///
+/// The records are read with the structures of every schema version, the version whose
+/// structure reads the most records exactly being the detected one, the newest version
+/// winning ties. Schema version 0 shares it's structure with version 1 so it's detected as
+/// version 1, and a database without series is of the current version.
+fn detect_schema_version(db: &Db) -> Result<u32, MigrationError> {
+    let mut records = vec![];
+    for profile_trees in get_profiles_trees(db)? {
+        for record in profile_trees.series.iter() {
+            let (_, series) = record.map_err(MigrationError::Sled)?;
+            records.push(series);
+        }
+    }
+
+    if records.is_empty() {
+        return Ok(CURRENT_SCHEMA_VERSION);
+    }
+
+    let readers: [(u32, RecordReader); CURRENT_SCHEMA_VERSION as usize] = [
+        (1, is_record_of::<v1::Series>),
+        (2, is_record_of::<v2::Series>),
+        (3, is_record_of::<v3::Series>),
+        (4, is_record_of::<v4::Series>),
+        (CURRENT_SCHEMA_VERSION, is_record_of::<Series>),
+    ];
+
+    let (schema_version, _) = readers
+        .into_iter()
+        .max_by_key(|(schema_version, is_record_of)| {
+            let read_records = records.iter().filter(|record| is_record_of(record)).count();
+            (read_records, *schema_version)
+        })
+        .expect("there should be a reader for every schema version");
+    Ok(schema_version)
+}
+
+/// Whether the record is exactly the bincode of the structure `T`, without any trailing bytes
+fn is_record_of<T: serde::de::DeserializeOwned>(record: &[u8]) -> bool {
+    use bincode::Options;
+
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .deserialize::<T>(record)
+        // Preventing the drop implementation of `Series` from writing into the global
+        // database which is not yet initialized at this point
+        .map(std::mem::ManuallyDrop::new)
+        .is_ok()
+}
+
+fn set_schema_version(db: &Db, schema_version: u32) -> Result<(), MigrationError> {
+    db.open_tree(METADATA_TREE_NAME)
+        .and_then(|metadata| metadata.insert(SCHEMA_VERSION_KEY, &schema_version.to_be_bytes()))
+        .map_err(MigrationError::Sled)?;
+    db.flush().map_err(MigrationError::Sled)?;
+    Ok(())
+}
+
+/// Runs all the migrations from the given schema version up to the current schema version
+/// on the series of every profile
+///
+/// The schema version is recorded after each migration so that an interrupted upgrade
+/// continues from where it stopped.
+fn run_migrations(db: &Db, from_schema_version: u32) -> Result<(), MigrationError> {
+    let profiles_trees = get_profiles_trees(db)?;
+
+    for (schema_version, migration) in MIGRATIONS
+        .iter()
+        .enumerate()
+        .skip(from_schema_version as usize)
+    {
+        let schema_version = schema_version as u32;
+        info!(
+            "migrating database from schema version {} to {}",
+            schema_version,
+            schema_version + 1
+        );
+        for profile_trees in &profiles_trees {
+            migration(profile_trees)?;
+        }
+        set_schema_version(db, schema_version + 1)?;
+    }
+    Ok(())
+}
+
+/// The trees of all the profiles having series in the database
+fn get_profiles_trees(db: &Db) -> Result<Vec<ProfileTrees>, MigrationError> {
+    let mut profile_names = vec![profiles::DEFAULT_PROFILE.to_owned()];
+    for tree_name in db.tree_names() {
+        if let Some(profile_name) = tree_name.strip_prefix(super::PROFILE_TREE_PREFIX.as_bytes()) {
+            profile_names.push(String::from_utf8_lossy(profile_name).into_owned());
+        }
+    }
+
+    profile_names
+        .iter()
+        .map(|profile_name| ProfileTrees::open(db, profile_name).map_err(MigrationError::Sled))
+        .collect()
+}
+
+/// Finds the legacy database with the highest schema version in the given data directory
+fn find_legacy_database(data_dir_path: &path::Path) -> io::Result<Option<(u32, path::PathBuf)>> {
+    let read_dir = match std::fs::read_dir(data_dir_path) {
+        Ok(read_dir) => read_dir,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+
+    let mut legacy_database = None;
+    for dir_entry in read_dir {
+        let dir_entry = dir_entry?;
+        let folder_name = dir_entry.file_name().to_string_lossy().to_string();
+
+        let schema_version = match folder_name
+            .strip_prefix(LEGACY_DATABASE_FOLDER_PREFIX)
+            .and_then(|version| version.parse::<u32>().ok())
+        {
+            Some(schema_version) => schema_version,
+            None => continue,
+        };
+
+        if legacy_database
+            .as_ref()
+            .map(|(version, _)| schema_version > *version)
+            .unwrap_or(true)
+        {
+            legacy_database = Some((schema_version, dir_entry.path()));
+        }
+    }
+    Ok(legacy_database)
+}
+
+/// Copies the database folder at the given path into a backup folder next to it
+fn backup_database(database_path: &path::Path, schema_version: u32) -> Result<(), MigrationError> {
+    let mut backup_folder_name = database_path
+        .file_name()
+        .expect("database path should have a folder name")
+        .to_os_string();
+    backup_folder_name.push(format!("-v{}-{}", schema_version, BACKUP_FOLDER_SUFFIX));
+
+    let backup_path = database_path.with_file_name(backup_folder_name);
+
+    info!(
+        "backing up database at {} to {}",
+        database_path.display(),
+        backup_path.display()
+    );
+
+    copy_dir_all(database_path, &backup_path).map_err(MigrationError::Io)
+}
+
+fn copy_dir_all(source: &path::Path, destination: &path::Path) -> io::Result<()> {
+    std::fs::create_dir_all(destination)?;
+    for dir_entry in std::fs::read_dir(source)? {
+        let dir_entry = dir_entry?;
+        let destination = destination.join(dir_entry.file_name());
+        if dir_entry.file_type()?.is_dir() {
+            copy_dir_all(&dir_entry.path(), &destination)?;
+        } else {
+            std::fs::copy(dir_entry.path(), destination)?;
+        }
+    }
+    Ok(())
+}
+
+/// Schema version 1 shares it's series structure with schema version 0, so this migration only
+/// quarantines the records that can not be read as such.
+fn migrate_v0_to_v1(profile_trees: &ProfileTrees) -> Result<(), MigrationError> {
+    let mut batch = MigrationBatch::default();
+
+    for record in profile_trees.series.iter() {
+        let (series_id, series) = record.map_err(MigrationError::Sled)?;
+        if let Err(err) = bincode::deserialize::<v1::Series>(&series) {
+            batch.quarantine(series_id, series, err);
+        }
+    }
+
+    batch.apply(profile_trees)
+}
+
+/// Schema version 2 records the watch times of episodes, the episodes watched before
+/// this migration have no watch time.
+fn migrate_v1_to_v2(profile_trees: &ProfileTrees) -> Result<(), MigrationError> {
+    convert_records::<v1::Series, v2::Series>(profile_trees)
+}
+
+/// Schema version 3 records the times episodes were unwatched, no episode has been
+/// unwatched before this migration.
+fn migrate_v2_to_v3(profile_trees: &ProfileTrees) -> Result<(), MigrationError> {
+    convert_records::<v2::Series, v3::Series>(profile_trees)
+}
+
+/// Schema version 4 records personal ratings and notes of series and episodes, nothing
+/// has been reviewed before this migration.
+fn migrate_v3_to_v4(profile_trees: &ProfileTrees) -> Result<(), MigrationError> {
+    convert_records::<v3::Series, v4::Series>(profile_trees)
+}
+
+/// Schema version 5 records per series notification preferences, all the series use the
+/// global notification settings before this migration.
+fn migrate_v4_to_v5(profile_trees: &ProfileTrees) -> Result<(), MigrationError> {
+    convert_records::<v4::Series, Series>(profile_trees)
+}
+
+/// Converts every series record of the profile from the structure `F` to the structure `T`
+///
+/// Records that can not be read as `F` are quarantined.
+fn convert_records<F, T>(profile_trees: &ProfileTrees) -> Result<(), MigrationError>
+where
+    F: serde::de::DeserializeOwned,
+    T: From<F> + serde::Serialize,
+{
+    let mut batch = MigrationBatch::default();
+
+    for record in profile_trees.series.iter() {
+        let (series_id, series) = record.map_err(MigrationError::Sled)?;
+        match bincode::deserialize::<F>(&series) {
+            Ok(series) => {
+                // Preventing the drop implementation of `Series` from writing into the
+                // global database which is not yet initialized at this point
+                let series = std::mem::ManuallyDrop::new(T::from(series));
+                batch.series.insert(
+                    series_id,
+                    bincode::serialize(&*series).expect("series should be serializable"),
+                );
+            }
+            Err(err) => batch.quarantine(series_id, series, err),
+        }
+    }
+
+    batch.apply(profile_trees)
+}
+
+/// The changes a migration makes to the trees of a profile
+#[derive(Default)]
+struct MigrationBatch {
+    series: Batch,
+    quarantine: Batch,
+}
+
+impl MigrationBatch {
+    /// Moves the unreadable series record into the quarantine, where `repair-database`
+    /// reports it
+    fn quarantine(&mut self, series_id: IVec, series: IVec, err: bincode::Error) {
+        error!(
+            "quarantining unreadable series with id '{}': {}",
+            String::from_utf8_lossy(&series_id),
+            err
+        );
+        self.quarantine.insert(series_id.clone(), series);
+        self.series.remove(series_id);
+    }
+
+    /// Applies the changes to both trees at once
+    fn apply(self, profile_trees: &ProfileTrees) -> Result<(), MigrationError> {
+        (&profile_trees.series, &profile_trees.quarantine)
+            .transaction(|(series_tree, quarantine_tree)| {
+                series_tree.apply_batch(&self.series)?;
+                quarantine_tree.apply_batch(&self.quarantine)?;
+                Ok(())
+            })
+            .map_err(|err: TransactionError| match err {
+                TransactionError::Abort(err) | TransactionError::Storage(err) => {
+                    MigrationError::Sled(err)
+                }
+            })
+    }
+}
+
+pub(super) mod v1 {
+    //! Database structures of the schema version 1
+
+    use serde::Deserialize;
+    use std::collections::{HashMap, HashSet};
+
+    #[derive(Deserialize)]
+    #[cfg_attr(test, derive(serde::Serialize))]
+    pub struct Series {
+        pub id: u32,
+        pub name: String,
+        pub is_tracked: bool,
+        pub seasons: HashMap<u32, Season>,
+    }
+
+    #[derive(Deserialize)]
+    #[cfg_attr(test, derive(serde::Serialize))]
+    pub struct Season {
+        pub episodes: HashSet<u32>,
+    }
+}
+
+pub(super) mod v2 {
+    //! Database structures of the schema version 2
+
+    use chrono::{DateTime, Utc};
+    use serde::{Deserialize, Serialize};
+    use std::collections::HashMap;
+
+    use super::v1;
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Series {
+        pub id: u32,
+        pub name: String,
+        pub is_tracked: bool,
+        pub seasons: HashMap<u32, Season>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Season {
+        pub episodes: HashMap<u32, EpisodeWatch>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct EpisodeWatch {
+        pub watched_at: Option<DateTime<Utc>>,
+        pub rewatches: Vec<DateTime<Utc>>,
+    }
+
+    impl From<v1::Series> for Series {
+        fn from(value: v1::Series) -> Self {
+            let seasons = value
+                .seasons
+                .into_iter()
+                .map(|(season_number, season)| {
+                    let episodes = season
+                        .episodes
+                        .into_iter()
+                        .map(|episode| {
+                            let episode_watch = EpisodeWatch {
+                                watched_at: None,
+                                rewatches: vec![],
+                            };
+                            (episode, episode_watch)
+                        })
+                        .collect();
+                    (season_number, Season { episodes })
+                })
+                .collect();
+
+            Self {
+                id: value.id,
+                name: value.name,
+                is_tracked: value.is_tracked,
+                seasons,
+            }
+        }
+    }
+}
+
+pub(super) mod v3 {
+    //! Database structures of the schema version 3
+
+    use chrono::{DateTime, Utc};
+    use serde::{Deserialize, Serialize};
+    use std::collections::HashMap;
+
+    use super::v2;
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Series {
+        pub id: u32,
+        pub name: String,
+        pub is_tracked: bool,
+        pub seasons: HashMap<u32, Season>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Season {
+        pub episodes: HashMap<u32, v2::EpisodeWatch>,
+        pub unwatched: HashMap<u32, DateTime<Utc>>,
+    }
+
+    impl From<v2::Series> for Series {
+        fn from(value: v2::Series) -> Self {
+            let seasons = value
+                .seasons
+                .into_iter()
+                .map(|(season_number, season)| {
+                    let season = Season {
+                        episodes: season.episodes,
+                        unwatched: HashMap::new(),
+                    };
+                    (season_number, season)
+                })
+                .collect();
+
+            Self {
+                id: value.id,
+                name: value.name,
+                is_tracked: value.is_tracked,
+                seasons,
+            }
+        }
+    }
+}
+
+pub(super) mod v4 {
+    //! Database structures of the schema version 4
+
+    use serde::{Deserialize, Serialize};
+    use std::collections::HashMap;
+
+    use super::v3;
+    use crate::core::database::Review;
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Series {
+        pub id: u32,
+        pub name: String,
+        pub is_tracked: bool,
+        pub seasons: HashMap<u32, v3::Season>,
+        pub review: Review,
+        pub episode_reviews: HashMap<(u32, u32), Review>,
+    }
+
+    impl From<v3::Series> for Series {
+        fn from(value: v3::Series) -> Self {
+            Self {
+                id: value.id,
+                name: value.name,
+                is_tracked: value.is_tracked,
+                seasons: value.seasons,
+                review: Review::default(),
+                episode_reviews: HashMap::new(),
+            }
+        }
+    }
+}
+
+impl From<v4::Series> for Series {
+    fn from(value: v4::Series) -> Self {
+        let seasons = value
+            .seasons
+            .into_iter()
+            .map(|(season_number, season)| {
+                let episodes = season
+                    .episodes
+                    .into_iter()
+                    .map(|(episode, episode_watch)| {
+                        let episode_watch = EpisodeWatch {
+                            watched_at: episode_watch.watched_at,
+                            rewatches: episode_watch.rewatches,
+                        };
+                        (episode, episode_watch)
+                    })
+                    .collect();
+                let season = Season {
+                    episodes,
+                    unwatched: season.unwatched,
+                };
+                (season_number, season)
+            })
+            .collect();
+
+        Self {
+            id: value.id,
+            name: value.name,
+            is_tracked: value.is_tracked,
+            seasons,
+            review: value.review,
+            episode_reviews: value.episode_reviews,
+            notification_preferences: NotificationPreferences::default(),
+            profile: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+    use std::mem::ManuallyDrop;
+
+    use chrono::{DateTime, TimeZone, Utc};
+
+    use super::*;
+    use crate::core::database::Review;
+
+    const SERIES_ID: &str = "1";
+    const UNREADABLE_SERIES_ID: &str = "2";
+    const UNREADABLE_SERIES: &[u8] = b"unreadable";
+    const OTHER_PROFILE: &str = "other";
+
+    fn watch_time() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2023, 1, 1, 0, 0, 0).unwrap()
+    }
+
+    fn v1_series() -> v1::Series {
+        v1::Series {
+            id: 1,
+            name: "Under the Dome".to_owned(),
+            is_tracked: true,
+            seasons: HashMap::from([(
+                1,
+                v1::Season {
+                    episodes: HashSet::from([1]),
+                },
+            )]),
+        }
+    }
+
+    fn v2_series() -> v2::Series {
+        let mut series = v2::Series::from(v1_series());
+        for season in series.seasons.values_mut() {
+            for episode_watch in season.episodes.values_mut() {
+                episode_watch.watched_at = Some(watch_time());
+            }
+        }
+        series
+    }
+
+    fn v3_series() -> v3::Series {
+        let mut series = v3::Series::from(v2_series());
+        for season in series.seasons.values_mut() {
+            season.unwatched.insert(2, watch_time());
+        }
+        series
+    }
+
+    fn v4_series() -> v4::Series {
+        let mut series = v4::Series::from(v3_series());
+        series.review = Review::new(Some(8), "Gripping".to_owned());
+        series
+    }
+
+    /// Creates a database holding the given series record and an unreadable one, with another
+    /// unreadable one in another profile, the schema version not being recorded when `None`
+    fn create_database(database_path: &path::Path, schema_version: Option<u32>, series: &[u8]) {
+        let db = sled::open(database_path).unwrap();
+        db.insert(SERIES_ID, series).unwrap();
+        db.insert(UNREADABLE_SERIES_ID, UNREADABLE_SERIES).unwrap();
+        db.open_tree(super::super::get_profile_tree_name(OTHER_PROFILE))
+            .unwrap()
+            .insert(UNREADABLE_SERIES_ID, UNREADABLE_SERIES)
+            .unwrap();
+        if let Some(schema_version) = schema_version {
+            set_schema_version(&db, schema_version).unwrap();
+        }
+        db.flush().unwrap();
+    }
+
+    /// Opens the database in the data directory, checking that it's of the current schema
+    /// version and that the original records were backed up into the given folder
+    fn migrate(data_dir_path: &path::Path, backup_folder_name: &str, series: &[u8]) -> Db {
+        let db = open_database(data_dir_path).unwrap();
+        assert_eq!(
+            get_schema_version(&db).unwrap(),
+            Some(CURRENT_SCHEMA_VERSION)
+        );
+
+        let backup = sled::open(data_dir_path.join(backup_folder_name)).unwrap();
+        assert_eq!(backup.get(SERIES_ID).unwrap().as_deref(), Some(series));
+
+        db
+    }
+
+    /// Checks that the unreadable series were moved into the quarantine of their profiles
+    fn assert_quarantined(db: &Db) {
+        for profile_name in [profiles::DEFAULT_PROFILE, OTHER_PROFILE] {
+            let profile_trees = ProfileTrees::open(db, profile_name).unwrap();
+            assert!(profile_trees
+                .series
+                .get(UNREADABLE_SERIES_ID)
+                .unwrap()
+                .is_none());
+            assert_eq!(
+                profile_trees
+                    .quarantine
+                    .get(UNREADABLE_SERIES_ID)
+                    .unwrap()
+                    .as_deref(),
+                Some(UNREADABLE_SERIES)
+            );
+        }
+    }
+
+    fn get_series(db: &Db) -> ManuallyDrop<Series> {
+        let series = db.get(SERIES_ID).unwrap().expect("series was dropped");
+        ManuallyDrop::new(bincode::deserialize(&series).unwrap())
+    }
+
+    fn get_watched_at(series: &Series) -> Option<DateTime<Utc>> {
+        series
+            .get_season(1)
+            .and_then(|season| season.get_episode_watch(1))
+            .expect("episode not watched")
+            .get_watched_at()
+    }
+
+    #[test]
+    fn legacy_version_0_database_is_upgraded() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let series = bincode::serialize(&v1_series()).unwrap();
+        create_database(&data_dir.path().join("series-troxide-db-0"), None, &series);
+
+        let db = migrate(data_dir.path(), "series-troxide-db-0-v0-backup", &series);
+
+        assert!(!data_dir.path().join("series-troxide-db-0").exists());
+        assert_quarantined(&db);
+
+        let migrated_series = get_series(&db);
+        assert_eq!(migrated_series.get_name(), "Under the Dome");
+        assert!(migrated_series.is_tracked());
+        assert_eq!(get_watched_at(&migrated_series), None);
+    }
+
+    #[test]
+    fn every_schema_version_is_upgraded() {
+        let databases = [
+            (1, bincode::serialize(&v1_series()).unwrap(), None),
+            (
+                2,
+                bincode::serialize(&v2_series()).unwrap(),
+                Some(watch_time()),
+            ),
+            (
+                3,
+                bincode::serialize(&v3_series()).unwrap(),
+                Some(watch_time()),
+            ),
+            (
+                4,
+                bincode::serialize(&v4_series()).unwrap(),
+                Some(watch_time()),
+            ),
+        ];
+
+        for (schema_version, series, watched_at) in databases {
+            let data_dir = tempfile::tempdir().unwrap();
+            create_database(
+                &data_dir.path().join(DATABASE_FOLDER_NAME),
+                Some(schema_version),
+                &series,
+            );
+
+            let backup_folder_name = format!("{}-v{}-backup", DATABASE_FOLDER_NAME, schema_version);
+            let db = migrate(data_dir.path(), &backup_folder_name, &series);
+
+            assert_quarantined(&db);
+
+            let migrated_series = get_series(&db);
+            assert_eq!(migrated_series.get_name(), "Under the Dome");
+            assert_eq!(get_watched_at(&migrated_series), watched_at);
+            let expected_rating = (schema_version == 4).then_some(8);
+            assert_eq!(migrated_series.get_review().get_rating(), expected_rating);
+        }
+    }
+
+    #[test]
+    fn unversioned_database_version_is_detected_from_the_records() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let series = bincode::serialize(&v3_series()).unwrap();
+        create_database(&data_dir.path().join(DATABASE_FOLDER_NAME), None, &series);
+
+        let backup_folder_name = format!("{}-v3-backup", DATABASE_FOLDER_NAME);
+        let db = migrate(data_dir.path(), &backup_folder_name, &series);
+
+        assert_eq!(get_watched_at(&get_series(&db)), Some(watch_time()));
+    }
+}
@@ -0,0 +1,617 @@
+//! Exporting and importing series data in formats other than the RON transfer data
+//!
+//! - JSON is lossless, it holds everything the RON transfer data holds.
+//! - CSV has one row per watched episode with it's watch times, tracked series without
+//!   watched episodes get a row without a season and episode. Reviews and notification
+//!   preferences are not part of it.
+//! - Markdown is a human readable report and can only be exported.
+//!
+//! The series ids of imports are validated against TVmaze before anything is written to
+//! the database, series with invalid ids are skipped and reported.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::mem::ManuallyDrop;
+use std::path;
+
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::database_transfer::{ImportChanges, TransferData, CURRENT_DATA_VERSION};
+use super::{
+    DatabaseError, Episode, EpisodeWatch, NotificationPreferences, Review, Season, Series, DB,
+};
+use crate::core::caching::series_information::get_series_main_info_with_id;
+
+/// The separator of the rewatch times in a CSV cell
+const CSV_LIST_SEPARATOR: char = ';';
+
+#[derive(Debug, Error)]
+pub enum TransferError {
+    #[error("IO error: {0}")]
+    Io(std::io::Error),
+
+    #[error("json error: {0}")]
+    Json(serde_json::Error),
+
+    #[error("csv error: {0}")]
+    Csv(csv::Error),
+
+    #[error("invalid {0} on line {1}: {2}")]
+    InvalidCsvField(&'static str, u64, String),
+
+    #[error("incompatible version. Expected version {0}, found {1}")]
+    Version(u16, u16),
+
+    #[error("{0} data can not be imported")]
+    NotImportable(TransferFormat),
+
+    #[error("dry runs are not supported for {0} data")]
+    DryRunUnsupported(TransferFormat),
+
+    #[error("RON import error: {0}")]
+    Ron(super::database_transfer::ImportError),
+
+    #[error("database error: {0}")]
+    Database(DatabaseError),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TransferFormat {
+    /// The native transfer format
+    Ron,
+    Json,
+    Csv,
+    /// A human readable report, export only
+    Markdown,
+}
+
+impl TransferFormat {
+    /// Guesses the format from the extension of the file, `None` when it's not known
+    pub fn from_path(path: impl AsRef<path::Path>) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "ron" => Some(Self::Ron),
+            "json" => Some(Self::Json),
+            "csv" => Some(Self::Csv),
+            "md" | "markdown" => Some(Self::Markdown),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for TransferFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str = match self {
+            TransferFormat::Ron => "RON",
+            TransferFormat::Json => "JSON",
+            TransferFormat::Csv => "CSV",
+            TransferFormat::Markdown => "Markdown",
+        };
+
+        write!(f, "{}", str)
+    }
+}
+
+/// The outcome of an import
+#[derive(Debug, Default, Clone)]
+pub struct ImportReport {
+    pub imported_series: usize,
+    pub imported_episodes: usize,
+    /// The series ids that could not be found on TVmaze together with the reason
+    pub invalid_series: Vec<(u32, String)>,
+    /// The series ids that could not be checked as TVmaze was not reachable, importing the
+    /// file again once online imports them
+    pub unchecked_series: Vec<(u32, String)>,
+}
+
+/// Exports the series of the active profile to the file in the given format
+pub async fn export_to_file(
+    path: impl AsRef<path::Path>,
+    format: TransferFormat,
+) -> Result<(), TransferError> {
+    if format == TransferFormat::Ron {
+        return TransferData::async_export_from_db(path)
+            .await
+            .map_err(|err| match err {
+                super::database_transfer::ExportError::Io(err) => TransferError::Io(err),
+                super::database_transfer::ExportError::Database(err) => {
+                    TransferError::Database(err)
+                }
+            });
+    }
+
+    let mut series_collection = DB
+        .get_series_collection()
+        .map_err(TransferError::Database)?;
+    series_collection.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let content = match format {
+        TransferFormat::Ron => unreachable!("RON exports are handled by the transfer data"),
+        TransferFormat::Json => export_json(&series_collection)?,
+        TransferFormat::Csv => export_csv(&series_collection)?,
+        TransferFormat::Markdown => export_markdown(&series_collection),
+    };
+
+    tokio::fs::write(path, content)
+        .await
+        .map_err(TransferError::Io)
+}
+
+/// Imports the file in the given format into the series of the active profile
+///
+/// JSON and RON imports replace the series they contain while CSV imports add their
+/// episodes to the existing series.
+pub async fn import_from_file(
+    path: impl AsRef<path::Path>,
+    format: TransferFormat,
+) -> Result<ImportReport, TransferError> {
+    let report = match format {
+        TransferFormat::Ron => {
+            let transfer_data = TransferData::async_import(path)
+                .await
+                .map_err(TransferError::Ron)?;
+            let mut report = ImportReport::default();
+            for series in transfer_data.get_series() {
+                report.imported_series += 1;
+                report.imported_episodes += series.get_total_episodes();
+            }
+            DB.import(&transfer_data).map_err(TransferError::Database)?;
+            report
+        }
+        TransferFormat::Json => {
+            let content = tokio::fs::read_to_string(path)
+                .await
+                .map_err(TransferError::Io)?;
+            import_json(&content).await?
+        }
+        TransferFormat::Csv => {
+            let content = tokio::fs::read_to_string(path)
+                .await
+                .map_err(TransferError::Io)?;
+            import_csv(&content).await?
+        }
+        TransferFormat::Markdown => return Err(TransferError::NotImportable(format)),
+    };
+
+    DB.flush().map_err(TransferError::Database)?;
+    Ok(report)
+}
+
+/// Reports what importing the file would change without writing anything into the database
+///
+/// Only the RON and JSON imports can be previewed, CSV imports merge into the existing series.
+pub async fn dry_run_import(
+    path: impl AsRef<path::Path>,
+    format: TransferFormat,
+) -> Result<ImportChanges, TransferError> {
+    let transfer_data = match format {
+        TransferFormat::Ron => TransferData::async_import(path)
+            .await
+            .map_err(TransferError::Ron)?,
+        TransferFormat::Json => {
+            let content = tokio::fs::read_to_string(path)
+                .await
+                .map_err(TransferError::Io)?;
+            let json_transfer_data = parse_json(&content)?;
+            TransferData::new(
+                json_transfer_data
+                    .series
+                    .into_iter()
+                    .map(Series::from)
+                    .collect(),
+            )
+        }
+        TransferFormat::Csv | TransferFormat::Markdown => {
+            return Err(TransferError::DryRunUnsupported(format))
+        }
+    };
+
+    // The series would write themselves into the database when dropped
+    let transfer_data = ManuallyDrop::new(transfer_data);
+    transfer_data
+        .get_import_changes()
+        .map_err(TransferError::Database)
+}
+
+/// Checks the ids against TVmaze returning the valid ones with their TVmaze names
+async fn validate_series_ids(
+    series_ids: impl IntoIterator<Item = u32>,
+    report: &mut ImportReport,
+) -> HashMap<u32, String> {
+    let handles: Vec<_> = series_ids
+        .into_iter()
+        .map(|series_id| {
+            (
+                series_id,
+                tokio::spawn(get_series_main_info_with_id(series_id)),
+            )
+        })
+        .collect();
+
+    let mut valid_series = HashMap::new();
+    for (series_id, handle) in handles {
+        match handle.await {
+            Ok(Ok(series_info)) => {
+                valid_series.insert(series_id, series_info.name);
+            }
+            Ok(Err(err)) if err.is_connection_error() => {
+                report.unchecked_series.push((series_id, err.to_string()))
+            }
+            Ok(Err(err)) => report.invalid_series.push((series_id, err.to_string())),
+            Err(err) => report.unchecked_series.push((series_id, err.to_string())),
+        }
+    }
+    valid_series
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonTransferData {
+    version: u16,
+    series: Vec<JsonSeries>,
+}
+
+/// A series with it's maps keyed by strings or numbers as json requires
+#[derive(Serialize, Deserialize)]
+struct JsonSeries {
+    id: u32,
+    name: String,
+    is_tracked: bool,
+    seasons: BTreeMap<u32, Season>,
+    review: Review,
+    episode_reviews: Vec<JsonEpisodeReview>,
+    notification_preferences: NotificationPreferences,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonEpisodeReview {
+    season: u32,
+    episode: Episode,
+    review: Review,
+}
+
+impl From<&Series> for JsonSeries {
+    fn from(series: &Series) -> Self {
+        let mut episode_reviews: Vec<_> = series
+            .episode_reviews
+            .iter()
+            .map(|((season, episode), review)| JsonEpisodeReview {
+                season: *season,
+                episode: *episode,
+                review: review.clone(),
+            })
+            .collect();
+        episode_reviews
+            .sort_by_key(|episode_review| (episode_review.season, episode_review.episode));
+
+        Self {
+            id: series.id,
+            name: series.name.clone(),
+            is_tracked: series.is_tracked,
+            seasons: series
+                .seasons
+                .iter()
+                .map(|(season_number, season)| (*season_number, season.clone()))
+                .collect(),
+            review: series.review.clone(),
+            episode_reviews,
+            notification_preferences: series.notification_preferences.clone(),
+        }
+    }
+}
+
+impl From<JsonSeries> for Series {
+    fn from(json_series: JsonSeries) -> Self {
+        Self {
+            id: json_series.id,
+            name: json_series.name,
+            is_tracked: json_series.is_tracked,
+            seasons: json_series.seasons.into_iter().collect(),
+            review: json_series.review,
+            episode_reviews: json_series
+                .episode_reviews
+                .into_iter()
+                .map(|episode_review| {
+                    (
+                        (episode_review.season, episode_review.episode),
+                        episode_review.review,
+                    )
+                })
+                .collect(),
+            notification_preferences: json_series.notification_preferences,
+            profile: None,
+        }
+    }
+}
+
+fn export_json(series_collection: &[Series]) -> Result<String, TransferError> {
+    let transfer_data = JsonTransferData {
+        version: CURRENT_DATA_VERSION,
+        series: series_collection.iter().map(JsonSeries::from).collect(),
+    };
+    serde_json::to_string_pretty(&transfer_data).map_err(TransferError::Json)
+}
+
+fn parse_json(content: &str) -> Result<JsonTransferData, TransferError> {
+    let transfer_data: JsonTransferData =
+        serde_json::from_str(content).map_err(TransferError::Json)?;
+    if transfer_data.version != CURRENT_DATA_VERSION {
+        return Err(TransferError::Version(
+            CURRENT_DATA_VERSION,
+            transfer_data.version,
+        ));
+    }
+    Ok(transfer_data)
+}
+
+async fn import_json(content: &str) -> Result<ImportReport, TransferError> {
+    let transfer_data = parse_json(content)?;
+
+    let mut report = ImportReport::default();
+    let valid_series = validate_series_ids(
+        transfer_data
+            .series
+            .iter()
+            .map(|json_series| json_series.id),
+        &mut report,
+    )
+    .await;
+
+    // Series write themselves into the database when dropped, so only the valid ones are
+    // turned into series
+    for json_series in transfer_data
+        .series
+        .into_iter()
+        .filter(|json_series| valid_series.contains_key(&json_series.id))
+    {
+        let series = Series::from(json_series);
+        report.imported_series += 1;
+        report.imported_episodes += series.get_total_episodes();
+        DB.add_series(series.id, &series)
+            .map_err(TransferError::Database)?;
+    }
+
+    Ok(report)
+}
+
+#[derive(Serialize, Deserialize)]
+struct CsvRecord {
+    series_name: String,
+    tvmaze_id: u32,
+    tracked: bool,
+    /// Empty for the row of a series without watched episodes
+    season: Option<u32>,
+    episode: Option<Episode>,
+    /// Empty when the watch time is unknown
+    watched_at: Option<DateTime<Utc>>,
+    /// The rewatch times separated by `CSV_LIST_SEPARATOR`
+    rewatches: String,
+}
+
+fn export_csv(series_collection: &[Series]) -> Result<String, TransferError> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+
+    for series in series_collection {
+        let mut episodes: Vec<(u32, Episode, &EpisodeWatch)> = series
+            .seasons
+            .iter()
+            .flat_map(|(season_number, season)| {
+                season
+                    .episodes
+                    .iter()
+                    .map(move |(episode, episode_watch)| (*season_number, *episode, episode_watch))
+            })
+            .collect();
+        episodes.sort_by_key(|(season_number, episode, _)| (*season_number, *episode));
+
+        if episodes.is_empty() {
+            writer
+                .serialize(CsvRecord {
+                    series_name: series.name.clone(),
+                    tvmaze_id: series.id,
+                    tracked: series.is_tracked,
+                    season: None,
+                    episode: None,
+                    watched_at: None,
+                    rewatches: String::new(),
+                })
+                .map_err(TransferError::Csv)?;
+        }
+
+        for (season_number, episode, episode_watch) in episodes {
+            writer
+                .serialize(CsvRecord {
+                    series_name: series.name.clone(),
+                    tvmaze_id: series.id,
+                    tracked: series.is_tracked,
+                    season: Some(season_number),
+                    episode: Some(episode),
+                    watched_at: episode_watch.watched_at,
+                    rewatches: episode_watch
+                        .rewatches
+                        .iter()
+                        .map(|rewatch| rewatch.to_rfc3339())
+                        .collect::<Vec<_>>()
+                        .join(&CSV_LIST_SEPARATOR.to_string()),
+                })
+                .map_err(TransferError::Csv)?;
+        }
+    }
+
+    let bytes = writer
+        .into_inner()
+        .map_err(|err| TransferError::Io(err.into_error()))?;
+    Ok(String::from_utf8(bytes).expect("csv writer output is utf-8"))
+}
+
+/// A csv record with it's parsed rewatch times
+type CsvEpisode = (CsvRecord, Vec<DateTime<Utc>>);
+
+async fn import_csv(content: &str) -> Result<ImportReport, TransferError> {
+    let mut reader = csv::Reader::from_reader(content.as_bytes());
+    let headers = reader.headers().map_err(TransferError::Csv)?.clone();
+
+    // Reading everything first so that nothing is written when the file is malformed
+    let mut records: BTreeMap<u32, Vec<CsvEpisode>> = BTreeMap::new();
+    let mut string_record = csv::StringRecord::new();
+    while reader
+        .read_record(&mut string_record)
+        .map_err(TransferError::Csv)?
+    {
+        let line = string_record
+            .position()
+            .map(|position| position.line())
+            .unwrap_or_default();
+        let record: CsvRecord = string_record
+            .deserialize(Some(&headers))
+            .map_err(TransferError::Csv)?;
+
+        if record.season.is_some() != record.episode.is_some() {
+            return Err(TransferError::InvalidCsvField(
+                "episode",
+                line,
+                "season and episode have to be both set or both empty".to_owned(),
+            ));
+        }
+
+        let rewatches = record
+            .rewatches
+            .split(CSV_LIST_SEPARATOR)
+            .filter(|rewatch| !rewatch.trim().is_empty())
+            .map(|rewatch| {
+                DateTime::parse_from_rfc3339(rewatch.trim())
+                    .map(|rewatch| rewatch.with_timezone(&Utc))
+                    .map_err(|err| {
+                        TransferError::InvalidCsvField("rewatches", line, err.to_string())
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        records
+            .entry(record.tvmaze_id)
+            .or_default()
+            .push((record, rewatches));
+    }
+
+    let mut report = ImportReport::default();
+    let valid_series = validate_series_ids(records.keys().copied(), &mut report).await;
+
+    for (series_id, series_records) in records {
+        let Some(series_name) = valid_series.get(&series_id) else {
+            continue;
+        };
+
+        let mut series = match DB.get_series(series_id).map_err(TransferError::Database)? {
+            Some(series) => series,
+            None => Series::new(series_name.clone(), series_id),
+        };
+
+        for (record, rewatches) in series_records {
+            if record.tracked {
+                series.mark_tracked();
+            }
+
+            let (Some(season_number), Some(episode)) = (record.season, record.episode) else {
+                continue;
+            };
+
+            let season = series.seasons.entry(season_number).or_default();
+            if season.insert_episode(episode, record.watched_at) {
+                report.imported_episodes += 1;
+            }
+            if let Some(episode_watch) = season.episodes.get_mut(&episode) {
+                for rewatch in rewatches {
+                    if !episode_watch.rewatches.contains(&rewatch) {
+                        episode_watch.rewatches.push(rewatch);
+                    }
+                }
+                episode_watch.rewatches.sort();
+            }
+        }
+        report.imported_series += 1;
+    }
+
+    Ok(report)
+}
+
+fn export_markdown(series_collection: &[Series]) -> String {
+    let mut report = String::from("# Series Troxide Report\n\n");
+
+    let total_episodes: usize = series_collection
+        .iter()
+        .map(|series| series.get_total_episodes())
+        .sum();
+    let _ = writeln!(
+        report,
+        "Exported on {}, {} series with {} watched episodes.\n",
+        Utc::now().format("%Y-%m-%d"),
+        series_collection.len(),
+        total_episodes
+    );
+
+    for series in series_collection {
+        let _ = writeln!(report, "## {} ({})\n", series.name, series.id);
+        let _ = writeln!(
+            report,
+            "- Tracked: {}",
+            if series.is_tracked { "yes" } else { "no" }
+        );
+        let _ = writeln!(
+            report,
+            "- Watched episodes: {}",
+            series.get_total_episodes()
+        );
+        if let Some(rating) = series.review.get_rating() {
+            let _ = writeln!(
+                report,
+                "- My rating: {}/{}",
+                rating,
+                super::RATING_RANGE.end()
+            );
+        }
+        if !series.review.get_notes().is_empty() {
+            let _ = writeln!(report, "- My notes: {}", series.review.get_notes());
+        }
+        report.push('\n');
+
+        let mut seasons: Vec<_> = series
+            .seasons
+            .iter()
+            .filter(|(_, season)| season.get_total_episodes() != 0)
+            .collect();
+        seasons.sort_by_key(|(season_number, _)| **season_number);
+
+        for (season_number, season) in seasons {
+            let _ = writeln!(report, "### Season {}\n", season_number);
+
+            let mut episodes: Vec<_> = season.episodes.iter().collect();
+            episodes.sort_by_key(|(episode, _)| **episode);
+
+            for (episode, episode_watch) in episodes {
+                let watched_at = episode_watch
+                    .watched_at
+                    .map(|watched_at| watched_at.format("%Y-%m-%d").to_string())
+                    .unwrap_or_else(|| "unknown date".to_owned());
+                let _ = write!(report, "- Episode {}, watched on {}", episode, watched_at);
+                if episode_watch.get_rewatch_count() != 0 {
+                    let _ = write!(
+                        report,
+                        ", rewatched {} times",
+                        episode_watch.get_rewatch_count()
+                    );
+                }
+                if let Some(review) = series.episode_reviews.get(&(*season_number, *episode)) {
+                    if let Some(rating) = review.get_rating() {
+                        let _ = write!(report, ", rated {}/{}", rating, super::RATING_RANGE.end());
+                    }
+                }
+                report.push('\n');
+            }
+            report.push('\n');
+        }
+    }
+
+    report
+}
@@ -0,0 +1,371 @@
+//! Importing watch history from other trackers
+//!
+//! Every tracker has an adapter parsing it's export into `ImportedShow`s, which are then
+//! looked up on TVmaze with the same conversion as the Trakt import. The adapters only parse
+//! text, so they can be run against export files without touching the network.
+//!
+//! Entries of an export that can't be parsed i.e. with a malformed watch time are skipped and
+//! reported together with the shows not found on TVmaze, rather than failing the whole import.
+
+use std::mem::ManuallyDrop;
+use std::path;
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use clap::ValueEnum;
+use serde::de::DeserializeOwned;
+use thiserror::Error;
+use tokio::sync::mpsc;
+
+use super::api::trakt::convert::{
+    convert_external_shows_to_troxide, ExternalShow, ProgressData, WatchedEpisode,
+};
+use super::api::tv_maze::ApiError as TvMazeApiError;
+use super::database::{Series, DB};
+
+pub mod generic_csv;
+pub mod simkl;
+pub mod tv_time;
+
+#[derive(Debug, Error)]
+pub enum ImportError {
+    #[error("IO error: {0}")]
+    Io(std::io::Error),
+
+    #[error("csv error: {0}")]
+    Csv(csv::Error),
+
+    #[error("json error: {0}")]
+    Json(serde_json::Error),
+
+    #[error("invalid {0} '{1}'")]
+    InvalidField(&'static str, String),
+
+    #[error("tvmaze api error: {0}")]
+    TvMazeApi(TvMazeApiError),
+}
+
+/// The trackers whose exports can be imported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ImportSource {
+    /// The `seen_episode.csv` of a TV Time GDPR data export
+    TvTime,
+    /// A Simkl JSON backup
+    Simkl,
+    /// A CSV with the IMDb ids of the shows and their watched episodes
+    Csv,
+}
+
+impl std::fmt::Display for ImportSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str = match self {
+            ImportSource::TvTime => "TV Time",
+            ImportSource::Simkl => "Simkl",
+            ImportSource::Csv => "CSV",
+        };
+
+        write!(f, "{}", str)
+    }
+}
+
+/// A show parsed from the export of a tracker
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportedShow {
+    pub title: String,
+    pub imdb_id: Option<String>,
+    pub tvdb_id: Option<u32>,
+    pub episodes: Vec<WatchedEpisode>,
+}
+
+impl ImportedShow {
+    fn new(title: String, imdb_id: Option<String>, tvdb_id: Option<u32>) -> Self {
+        Self {
+            title,
+            imdb_id,
+            tvdb_id,
+            episodes: vec![],
+        }
+    }
+}
+
+/// An entry of an export that could not be parsed, the entry being a row or an episode of
+/// the export
+#[derive(Debug)]
+pub struct MalformedEntry {
+    /// Where the entry is in the export i.e. `row 3`
+    pub entry: String,
+    pub error: ImportError,
+}
+
+impl MalformedEntry {
+    fn new(entry: impl Into<String>, error: ImportError) -> Self {
+        Self {
+            entry: entry.into(),
+            error,
+        }
+    }
+}
+
+/// The shows parsed from an export together with the entries that could not be parsed
+#[derive(Debug, Default)]
+pub struct ParsedExport {
+    pub shows: Vec<ImportedShow>,
+    pub malformed_entries: Vec<MalformedEntry>,
+}
+
+/// A show or an entry of an export that was not imported
+#[derive(Debug)]
+pub enum FailedImport {
+    /// A show that could not be found on TVmaze
+    NotFound(ImportedShow),
+    Malformed(MalformedEntry),
+}
+
+impl std::fmt::Display for FailedImport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FailedImport::NotFound(show) => write!(f, "could not find '{}' on TVmaze", show.title),
+            FailedImport::Malformed(malformed_entry) => write!(
+                f,
+                "skipped {}: {}",
+                malformed_entry.entry, malformed_entry.error
+            ),
+        }
+    }
+}
+
+impl ExternalShow for ImportedShow {
+    fn get_imdb_id(&self) -> Option<String> {
+        self.imdb_id.clone()
+    }
+
+    fn get_tvdb_id(&self) -> Option<u32> {
+        self.tvdb_id
+    }
+
+    fn get_watched_episodes(&self) -> Vec<WatchedEpisode> {
+        self.episodes.clone()
+    }
+}
+
+/// Parses the content of an export of the given source
+///
+/// Only an export that can't be read at all is an error, the entries that can't be parsed
+/// are returned in the `ParsedExport`.
+pub fn parse(source: ImportSource, content: &str) -> Result<ParsedExport, ImportError> {
+    match source {
+        ImportSource::TvTime => tv_time::parse(content),
+        ImportSource::Simkl => simkl::parse(content),
+        ImportSource::Csv => generic_csv::parse(content),
+    }
+}
+
+/// Imports the export file of the given source
+///
+/// Like the Trakt import, the shows found on TVmaze are returned as series together with the
+/// shows and the entries that failed. The series are not written into the database, see
+/// `save_imports`.
+#[allow(clippy::type_complexity)]
+pub async fn import(
+    source: ImportSource,
+    path: impl AsRef<path::Path>,
+    progress_sender: mpsc::Sender<ProgressData>,
+) -> Result<(Vec<(u32, ManuallyDrop<Series>)>, Vec<FailedImport>), ImportError> {
+    let content = tokio::fs::read_to_string(path)
+        .await
+        .map_err(ImportError::Io)?;
+    let parsed_export = parse(source, &content)?;
+
+    let (imports, not_found_shows) =
+        convert_external_shows_to_troxide(parsed_export.shows, progress_sender)
+            .await
+            .map_err(ImportError::TvMazeApi)?;
+
+    let failed_imports = parsed_export
+        .malformed_entries
+        .into_iter()
+        .map(FailedImport::Malformed)
+        .chain(not_found_shows.into_iter().map(FailedImport::NotFound))
+        .collect();
+
+    Ok((imports, failed_imports))
+}
+
+/// Writes the imported series into the database as tracked series
+///
+/// The imported episodes are merged into the series already in the database, the episodes
+/// they already have keeping their watch history.
+pub fn save_imports(imports: Vec<(u32, ManuallyDrop<Series>)>) {
+    for (series_id, mut imported_series) in imports {
+        match DB.get_series(series_id) {
+            Ok(Some(mut series)) => {
+                merge_episodes(&mut series, &imported_series);
+                series.mark_tracked();
+            }
+            Ok(None) => {
+                imported_series.mark_tracked();
+                DB.add_series(series_id, &imported_series)
+                    .unwrap_or_else(|err| {
+                        tracing::error!("failed to add series with id '{}': {}", series_id, err)
+                    });
+            }
+            Err(err) => {
+                tracing::error!(
+                    "failed to merge imported series with id '{}': {}",
+                    series_id,
+                    err
+                )
+            }
+        }
+    }
+}
+
+/// Adds the episodes of the imported series that the series has not watched
+fn merge_episodes(series: &mut Series, imported_series: &Series) {
+    for (season_number, imported_season) in imported_series.get_seasons() {
+        if series.get_season(season_number).is_none() {
+            series.add_season(season_number);
+        }
+        let season = series
+            .get_season_mut(season_number)
+            .expect("season just added");
+
+        for (episode, episode_watch) in imported_season.get_episode_watches() {
+            // Does nothing for already watched episodes
+            season.track_episode_unchecked_with_time(episode, episode_watch.get_watched_at());
+        }
+    }
+}
+
+/// Parses the watch times found in exports, those without a timezone being in UTC
+fn parse_watch_time(watch_time: &str) -> Result<Option<DateTime<Utc>>, ImportError> {
+    let watch_time = watch_time.trim();
+    if watch_time.is_empty() {
+        return Ok(None);
+    }
+
+    if let Ok(watch_time) = DateTime::parse_from_rfc3339(watch_time) {
+        return Ok(Some(watch_time.with_timezone(&Utc)));
+    }
+
+    ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(watch_time, format).ok())
+        .or_else(|| {
+            chrono::NaiveDate::parse_from_str(watch_time, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })
+        .map(|watch_time| Some(watch_time.and_utc()))
+        .ok_or_else(|| ImportError::InvalidField("watch time", watch_time.to_owned()))
+}
+
+/// Reads the rows of a csv export together with where they are in it, the rows that can't be
+/// read being returned as errors
+#[allow(clippy::type_complexity)]
+fn read_csv_rows<T: DeserializeOwned>(
+    content: &str,
+) -> Result<Vec<(String, Result<T, ImportError>)>, ImportError> {
+    let mut reader = csv::Reader::from_reader(content.as_bytes());
+    let headers = reader.headers().map_err(ImportError::Csv)?.clone();
+
+    let get_entry = |position: Option<&csv::Position>| {
+        position
+            .map(|position| format!("row {}", position.line()))
+            .unwrap_or_else(|| "row".to_owned())
+    };
+
+    Ok(reader
+        .records()
+        .map(|result| match result {
+            Ok(record) => (
+                get_entry(record.position()),
+                record
+                    .deserialize::<T>(Some(&headers))
+                    .map_err(ImportError::Csv),
+            ),
+            Err(err) => (get_entry(err.position()), Err(ImportError::Csv(err))),
+        })
+        .collect())
+}
+
+/// Adds the episode to the show with the given key, creating the show when it's new
+///
+/// Exports list episodes rather than shows, this groups them keeping the order the shows
+/// first appeared in.
+fn add_episode<K: PartialEq>(
+    shows: &mut Vec<(K, ImportedShow)>,
+    key: K,
+    new_show: impl FnOnce() -> ImportedShow,
+    episode: WatchedEpisode,
+) {
+    let index = match shows.iter().position(|(show_key, _)| *show_key == key) {
+        Some(index) => index,
+        None => {
+            shows.push((key, new_show()));
+            shows.len() - 1
+        }
+    };
+
+    let episodes = &mut shows[index].1.episodes;
+    // Keeping the earliest watch of episodes listed more than once
+    match episodes.iter_mut().find(|watched_episode| {
+        watched_episode.season == episode.season && watched_episode.episode == episode.episode
+    }) {
+        Some(watched_episode) => match (watched_episode.watched_at, episode.watched_at) {
+            (Some(watched_at), Some(new_watched_at)) if new_watched_at >= watched_at => {}
+            (_, None) => {}
+            _ => watched_episode.watched_at = episode.watched_at,
+        },
+        None => episodes.push(episode),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::core::testing;
+
+    fn day(day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2023, 1, day, 0, 0, 0).unwrap()
+    }
+
+    fn get_watched_at(series: &Series, episode: u32) -> Option<DateTime<Utc>> {
+        series
+            .get_season(1)
+            .and_then(|season| season.get_episode_watch(episode))
+            .expect("episode not watched")
+            .get_watched_at()
+    }
+
+    #[tokio::test]
+    async fn imported_episodes_are_merged_into_existing_series() {
+        let _database = testing::lock_database().await;
+
+        {
+            let mut series = Series::new("Under the Dome".to_owned(), 1);
+            series.mark_tracked();
+            series.add_episode_unchecked_with_time(1, 1, day(1));
+        }
+
+        let mut imported_series = Series::new("Under the Dome".to_owned(), 1);
+        imported_series.add_episode_unchecked_with_time(1, 1, day(5));
+        imported_series.add_episode_unchecked_with_time(1, 2, day(2));
+        let mut new_series = Series::new("Person of Interest".to_owned(), 2);
+        new_series.add_episode_unchecked_with_time(1, 1, day(3));
+
+        save_imports(vec![
+            (1, ManuallyDrop::new(imported_series)),
+            (2, ManuallyDrop::new(new_series)),
+        ]);
+
+        let series = DB.get_series(1).unwrap().expect("series removed");
+        assert_eq!(get_watched_at(&series, 1), Some(day(1)));
+        assert_eq!(get_watched_at(&series, 2), Some(day(2)));
+
+        let new_series = DB.get_series(2).unwrap().expect("series not added");
+        assert!(new_series.is_tracked());
+        assert_eq!(get_watched_at(&new_series, 1), Some(day(3)));
+    }
+}
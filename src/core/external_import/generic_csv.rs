@@ -0,0 +1,135 @@
+//! Adapter for CSVs listing watched episodes by the IMDb ids of their shows
+//!
+//! The columns are `imdb_id,title,season,episode,watched_at` in any order, only `imdb_id`,
+//! `season` and `episode` being required. The capitalized headers of Letterboxd-style
+//! exports i.e. `Const`, `Name` and `Date` are accepted too.
+
+use serde::Deserialize;
+
+use super::{
+    add_episode, parse_watch_time, read_csv_rows, ImportError, ImportedShow, MalformedEntry,
+    ParsedExport, WatchedEpisode,
+};
+
+#[derive(Deserialize)]
+struct Row {
+    #[serde(alias = "imdb", alias = "Const", alias = "IMDb ID")]
+    imdb_id: String,
+    #[serde(default, alias = "Title", alias = "name", alias = "Name")]
+    title: String,
+    #[serde(alias = "Season")]
+    season: u32,
+    #[serde(alias = "Episode")]
+    episode: u32,
+    #[serde(
+        default,
+        alias = "date",
+        alias = "Date",
+        alias = "Watched Date",
+        alias = "watched_date"
+    )]
+    watched_at: String,
+}
+
+pub fn parse(content: &str) -> Result<ParsedExport, ImportError> {
+    let mut shows = vec![];
+    let mut malformed_entries = vec![];
+    for (entry, row) in read_csv_rows(content)? {
+        match row.and_then(parse_row) {
+            Ok((imdb_id, title, episode)) => add_episode(
+                &mut shows,
+                imdb_id.clone(),
+                || ImportedShow::new(title, Some(imdb_id), None),
+                episode,
+            ),
+            Err(err) => malformed_entries.push(MalformedEntry::new(entry, err)),
+        }
+    }
+
+    Ok(ParsedExport {
+        shows: shows.into_iter().map(|(_, show)| show).collect(),
+        malformed_entries,
+    })
+}
+
+/// Parses the row into the IMDb id and the title of it's show and the watched episode
+fn parse_row(row: Row) -> Result<(String, String, WatchedEpisode), ImportError> {
+    let imdb_id = row.imdb_id.trim().to_owned();
+    if !is_imdb_id(&imdb_id) {
+        return Err(ImportError::InvalidField("imdb id", imdb_id));
+    }
+
+    let episode = WatchedEpisode {
+        season: row.season,
+        episode: row.episode,
+        watched_at: parse_watch_time(&row.watched_at)?,
+    };
+
+    let title = if row.title.is_empty() {
+        imdb_id.clone()
+    } else {
+        row.title
+    };
+    Ok((imdb_id, title, episode))
+}
+
+/// Whether the id looks like an IMDb title id i.e. `tt0944947`
+fn is_imdb_id(id: &str) -> bool {
+    id.strip_prefix("tt")
+        .map(|digits| !digits.is_empty() && digits.chars().all(|char| char.is_ascii_digit()))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+
+    const EXPORT: &str = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/imports/watched.csv"
+    ));
+
+    #[test]
+    fn malformed_rows_are_reported_without_failing_the_export() {
+        let parsed_export = parse(EXPORT).unwrap();
+
+        let breaking_bad = ImportedShow {
+            episodes: vec![
+                WatchedEpisode {
+                    season: 1,
+                    episode: 1,
+                    watched_at: Utc.with_ymd_and_hms(2021, 3, 1, 20, 15, 0).single(),
+                },
+                WatchedEpisode {
+                    season: 1,
+                    episode: 2,
+                    watched_at: None,
+                },
+            ],
+            ..ImportedShow::new(
+                "Breaking Bad".to_owned(),
+                Some("tt0903747".to_owned()),
+                None,
+            )
+        };
+        // Shows without a title are named by their IMDb id
+        let game_of_thrones = ImportedShow {
+            episodes: vec![WatchedEpisode {
+                season: 1,
+                episode: 2,
+                watched_at: Utc.with_ymd_and_hms(2021, 4, 2, 0, 0, 0).single(),
+            }],
+            ..ImportedShow::new("tt0944947".to_owned(), Some("tt0944947".to_owned()), None)
+        };
+        assert_eq!(parsed_export.shows, vec![breaking_bad, game_of_thrones]);
+
+        let malformed_entries: Vec<_> = parsed_export
+            .malformed_entries
+            .iter()
+            .map(|malformed_entry| malformed_entry.entry.as_str())
+            .collect();
+        assert_eq!(malformed_entries, vec!["row 4", "row 5", "row 6"]);
+    }
+}
@@ -0,0 +1,165 @@
+//! Adapter for Simkl JSON backups
+//!
+//! The backup has the same layout as the Simkl sync api, the watched episodes of a show
+//! being listed by season under `shows` and `anime`. Simkl is inconsistent on whether ids
+//! are strings or numbers so both are accepted.
+
+use serde::Deserialize;
+
+use super::{
+    parse_watch_time, ImportError, ImportedShow, MalformedEntry, ParsedExport, WatchedEpisode,
+};
+
+#[derive(Deserialize)]
+struct Backup {
+    #[serde(default)]
+    shows: Vec<SimklShow>,
+    #[serde(default)]
+    anime: Vec<SimklShow>,
+}
+
+#[derive(Deserialize)]
+struct SimklShow {
+    show: Show,
+    #[serde(default)]
+    seasons: Vec<Season>,
+}
+
+#[derive(Deserialize)]
+struct Show {
+    title: String,
+    ids: Ids,
+}
+
+#[derive(Deserialize)]
+struct Ids {
+    imdb: Option<String>,
+    tvdb: Option<NumberOrString>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum NumberOrString {
+    Number(u32),
+    String(String),
+}
+
+#[derive(Deserialize)]
+struct Season {
+    number: u32,
+    #[serde(default)]
+    episodes: Vec<Episode>,
+}
+
+#[derive(Deserialize)]
+struct Episode {
+    number: u32,
+    watched_at: Option<String>,
+}
+
+pub fn parse(content: &str) -> Result<ParsedExport, ImportError> {
+    let backup: Backup = serde_json::from_str(content).map_err(ImportError::Json)?;
+
+    let mut parsed_export = ParsedExport::default();
+    for simkl_show in backup.shows.into_iter().chain(backup.anime) {
+        let title = simkl_show.show.title;
+        let tvdb_id = match simkl_show.show.ids.tvdb {
+            Some(NumberOrString::Number(tvdb_id)) => Some(tvdb_id),
+            Some(NumberOrString::String(tvdb_id)) if !tvdb_id.is_empty() => match tvdb_id.parse() {
+                Ok(tvdb_id) => Some(tvdb_id),
+                Err(_) => {
+                    parsed_export.malformed_entries.push(MalformedEntry::new(
+                        format!("'{}'", title),
+                        ImportError::InvalidField("tvdb id", tvdb_id),
+                    ));
+                    continue;
+                }
+            },
+            _ => None,
+        };
+        let imdb_id = simkl_show
+            .show
+            .ids
+            .imdb
+            .filter(|imdb_id| !imdb_id.is_empty());
+
+        let mut show = ImportedShow::new(title, imdb_id, tvdb_id);
+        for season in simkl_show.seasons {
+            for episode in season.episodes {
+                match parse_watch_time(episode.watched_at.as_deref().unwrap_or("")) {
+                    Ok(watched_at) => show.episodes.push(WatchedEpisode {
+                        season: season.number,
+                        episode: episode.number,
+                        watched_at,
+                    }),
+                    Err(err) => parsed_export.malformed_entries.push(MalformedEntry::new(
+                        format!(
+                            "'{}' season {} episode {}",
+                            show.title, season.number, episode.number
+                        ),
+                        err,
+                    )),
+                }
+            }
+        }
+        parsed_export.shows.push(show);
+    }
+
+    Ok(parsed_export)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+
+    const EXPORT: &str = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/imports/simkl-backup.json"
+    ));
+
+    #[test]
+    fn malformed_entries_are_reported_without_failing_the_export() {
+        let parsed_export = parse(EXPORT).unwrap();
+
+        let breaking_bad = ImportedShow {
+            episodes: vec![
+                WatchedEpisode {
+                    season: 1,
+                    episode: 1,
+                    watched_at: Utc.with_ymd_and_hms(2021, 3, 1, 20, 15, 0).single(),
+                },
+                WatchedEpisode {
+                    season: 1,
+                    episode: 3,
+                    watched_at: None,
+                },
+            ],
+            ..ImportedShow::new(
+                "Breaking Bad".to_owned(),
+                Some("tt0903747".to_owned()),
+                Some(81189),
+            )
+        };
+        let cowboy_bebop = ImportedShow {
+            episodes: vec![WatchedEpisode {
+                season: 1,
+                episode: 1,
+                watched_at: Utc.with_ymd_and_hms(2021, 5, 1, 0, 0, 0).single(),
+            }],
+            ..ImportedShow::new("Cowboy Bebop".to_owned(), None, Some(76885))
+        };
+        assert_eq!(parsed_export.shows, vec![breaking_bad, cowboy_bebop]);
+
+        let malformed_entries: Vec<_> = parsed_export
+            .malformed_entries
+            .iter()
+            .map(|malformed_entry| malformed_entry.entry.as_str())
+            .collect();
+        assert_eq!(
+            malformed_entries,
+            vec!["'Breaking Bad' season 1 episode 2", "'Game of Thrones'"]
+        );
+    }
+}
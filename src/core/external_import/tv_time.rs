@@ -0,0 +1,113 @@
+//! Adapter for the `seen_episode.csv` of a TV Time GDPR data export
+//!
+//! TV Time identifies shows by their TVDB ids, the rows look like
+//! `tv_show_id,tv_show_name,episode_season_number,episode_number,created_at,...` with the
+//! watch time in UTC without a timezone.
+
+use serde::Deserialize;
+
+use super::{
+    add_episode, parse_watch_time, read_csv_rows, ImportError, ImportedShow, MalformedEntry,
+    ParsedExport, WatchedEpisode,
+};
+
+#[derive(Deserialize)]
+struct SeenEpisode {
+    #[serde(alias = "series_id")]
+    tv_show_id: u32,
+    #[serde(alias = "series_name")]
+    tv_show_name: String,
+    #[serde(alias = "season_number")]
+    episode_season_number: u32,
+    episode_number: u32,
+    #[serde(default, alias = "updated_at")]
+    created_at: String,
+}
+
+pub fn parse(content: &str) -> Result<ParsedExport, ImportError> {
+    let mut shows = vec![];
+    let mut malformed_entries = vec![];
+    for (entry, seen_episode) in read_csv_rows::<SeenEpisode>(content)? {
+        let seen_episode = match seen_episode {
+            Ok(seen_episode) => seen_episode,
+            Err(err) => {
+                malformed_entries.push(MalformedEntry::new(entry, err));
+                continue;
+            }
+        };
+
+        // Specials are not tracked by TV Time as episodes of a season
+        if seen_episode.episode_season_number == 0 {
+            continue;
+        }
+
+        let watched_at = match parse_watch_time(&seen_episode.created_at) {
+            Ok(watched_at) => watched_at,
+            Err(err) => {
+                malformed_entries.push(MalformedEntry::new(entry, err));
+                continue;
+            }
+        };
+        let episode = WatchedEpisode {
+            season: seen_episode.episode_season_number,
+            episode: seen_episode.episode_number,
+            watched_at,
+        };
+
+        let tv_show_name = seen_episode.tv_show_name;
+        add_episode(
+            &mut shows,
+            seen_episode.tv_show_id,
+            || ImportedShow::new(tv_show_name, None, Some(seen_episode.tv_show_id)),
+            episode,
+        );
+    }
+
+    Ok(ParsedExport {
+        shows: shows.into_iter().map(|(_, show)| show).collect(),
+        malformed_entries,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+
+    const EXPORT: &str = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/imports/seen_episode.csv"
+    ));
+
+    #[test]
+    fn malformed_rows_are_reported_without_failing_the_export() {
+        let parsed_export = parse(EXPORT).unwrap();
+
+        let breaking_bad = ImportedShow {
+            episodes: vec![WatchedEpisode {
+                season: 1,
+                episode: 1,
+                // The earliest of the watches is kept
+                watched_at: Utc.with_ymd_and_hms(2021, 2, 1, 20, 15, 0).single(),
+            }],
+            ..ImportedShow::new("Breaking Bad".to_owned(), None, Some(81189))
+        };
+        let game_of_thrones = ImportedShow {
+            episodes: vec![WatchedEpisode {
+                season: 1,
+                episode: 1,
+                watched_at: Utc.with_ymd_and_hms(2021, 4, 1, 21, 0, 0).single(),
+            }],
+            ..ImportedShow::new("Game of Thrones".to_owned(), None, Some(121361))
+        };
+        assert_eq!(parsed_export.shows, vec![breaking_bad, game_of_thrones]);
+
+        let malformed_entries: Vec<_> = parsed_export
+            .malformed_entries
+            .iter()
+            .map(|malformed_entry| malformed_entry.entry.as_str())
+            .collect();
+        assert_eq!(malformed_entries, vec!["row 3", "row 5"]);
+    }
+}
@@ -0,0 +1,294 @@
+//! Detecting gaps in the watch progress of series
+//!
+//! `Series::get_last_season` and `Season::get_last_episode` only care about the furthest
+//! watched episode, the analysis here looks at what was left behind on the way there i.e.
+//! the released episodes skipped before the last watched one, the seasons left partially
+//! watched and the series that were not watched for a long time.
+
+use chrono::{DateTime, Duration, Utc};
+
+use super::caching::episode_list::EpisodeList;
+use super::database::Series;
+
+/// An episode identified by it's season and number
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct EpisodeNumber {
+    pub season: u32,
+    pub episode: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartialSeason {
+    pub season: u32,
+    pub watched_episodes: usize,
+    pub watchable_episodes: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GapAnalysis {
+    /// The released and unwatched episodes coming before the last watched episode
+    skipped_episodes: Vec<EpisodeNumber>,
+    /// The watched episodes after the first skipped episode in it's season
+    episodes_after_gap: Vec<EpisodeNumber>,
+    /// The seasons with both watched and unwatched released episodes
+    partial_seasons: Vec<PartialSeason>,
+    /// The last watch time of a series with episodes left that was not watched for longer
+    /// than the abandonment period
+    abandoned_since: Option<DateTime<Utc>>,
+}
+
+impl GapAnalysis {
+    /// Analyzes the watch progress of the series, it's considered abandoned when it has
+    /// episodes left and was not watched for longer than `abandoned_after`
+    pub fn new(series: &Series, episode_list: &EpisodeList, abandoned_after: Duration) -> Self {
+        let mut released_episodes: Vec<(EpisodeNumber, bool)> = episode_list
+            .get_all_episodes()
+            .iter()
+            .filter(|episode| EpisodeList::is_episode_watchable(episode) == Some(true))
+            .filter_map(|episode| {
+                let episode_number = EpisodeNumber {
+                    season: episode.season,
+                    episode: episode.number?,
+                };
+                let is_watched = series
+                    .get_season(episode_number.season)
+                    .map(|season| season.is_episode_watched(episode_number.episode))
+                    .unwrap_or(false);
+                Some((episode_number, is_watched))
+            })
+            .collect();
+        released_episodes.sort_by_key(|(episode_number, _)| *episode_number);
+
+        let last_watched_episode = released_episodes
+            .iter()
+            .rev()
+            .find(|(_, is_watched)| *is_watched)
+            .map(|(episode_number, _)| *episode_number);
+
+        let skipped_episodes: Vec<EpisodeNumber> = released_episodes
+            .iter()
+            .filter(|(episode_number, is_watched)| {
+                !is_watched && Some(*episode_number) < last_watched_episode
+            })
+            .map(|(episode_number, _)| *episode_number)
+            .collect();
+
+        let episodes_after_gap = skipped_episodes
+            .first()
+            .map(|first_skipped_episode| {
+                released_episodes
+                    .iter()
+                    .filter(|(episode_number, is_watched)| {
+                        *is_watched
+                            && episode_number.season == first_skipped_episode.season
+                            && episode_number > first_skipped_episode
+                    })
+                    .map(|(episode_number, _)| *episode_number)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let mut partial_seasons: Vec<PartialSeason> = vec![];
+        for (episode_number, is_watched) in &released_episodes {
+            let partial_season = match partial_seasons.last_mut() {
+                Some(partial_season) if partial_season.season == episode_number.season => {
+                    partial_season
+                }
+                _ => {
+                    partial_seasons.push(PartialSeason {
+                        season: episode_number.season,
+                        watched_episodes: 0,
+                        watchable_episodes: 0,
+                    });
+                    partial_seasons.last_mut().expect("season just pushed")
+                }
+            };
+            partial_season.watchable_episodes += 1;
+            if *is_watched {
+                partial_season.watched_episodes += 1;
+            }
+        }
+        partial_seasons.retain(|partial_season| {
+            partial_season.watched_episodes != 0
+                && partial_season.watched_episodes != partial_season.watchable_episodes
+        });
+
+        let has_episodes_left = released_episodes.iter().any(|(_, is_watched)| !is_watched);
+        let abandoned_since = series.get_last_watch_time().filter(|last_watch_time| {
+            has_episodes_left && Utc::now() - *last_watch_time > abandoned_after
+        });
+
+        Self {
+            skipped_episodes,
+            episodes_after_gap,
+            partial_seasons,
+            abandoned_since,
+        }
+    }
+
+    pub fn get_skipped_episodes(&self) -> &[EpisodeNumber] {
+        &self.skipped_episodes
+    }
+
+    /// The first skipped episode, the one resuming from the gap continues from
+    pub fn get_first_skipped_episode(&self) -> Option<EpisodeNumber> {
+        self.skipped_episodes.first().copied()
+    }
+
+    /// The watched episodes `resume_from_gap` unmarks
+    pub fn get_episodes_after_gap(&self) -> &[EpisodeNumber] {
+        &self.episodes_after_gap
+    }
+
+    pub fn get_partial_seasons(&self) -> &[PartialSeason] {
+        &self.partial_seasons
+    }
+
+    pub fn get_abandoned_since(&self) -> Option<DateTime<Utc>> {
+        self.abandoned_since
+    }
+
+    pub fn has_gaps(&self) -> bool {
+        !self.skipped_episodes.is_empty()
+    }
+
+    /// Whether there is anything worth the user's attention
+    pub fn has_findings(&self) -> bool {
+        self.has_gaps() || self.abandoned_since.is_some()
+    }
+
+    /// Marks all the skipped episodes watched
+    pub fn mark_gaps_watched(&self, series: &mut Series) {
+        for episode_number in &self.skipped_episodes {
+            series.add_episode_unchecked(episode_number.season, episode_number.episode);
+        }
+    }
+
+    /// Unmarks the episodes watched after the first skipped episode in it's season so that
+    /// watching continues from it
+    ///
+    /// # Note
+    /// The other seasons are left untouched, so the watch history of the episodes watched
+    /// past the gap's season is kept.
+    pub fn resume_from_gap(&self, series: &mut Series) {
+        for episode_number in &self.episodes_after_gap {
+            series.remove_episode(episode_number.season, episode_number.episode);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::api::mock_server;
+    use crate::core::testing;
+
+    /// The episode list of the series with id 1, having 3 episodes in the first season and 2
+    /// in the second one all released
+    fn episode_list() -> EpisodeList {
+        let episode_list =
+            std::fs::read_to_string(mock_server::get_fixtures_dir().join("shows/1/episodes.json"))
+                .expect("failed to read the episode list");
+        EpisodeList::with_cache(1, &episode_list).expect("invalid episode list")
+    }
+
+    fn episode(season: u32, episode: u32) -> EpisodeNumber {
+        EpisodeNumber { season, episode }
+    }
+
+    fn series_with_watched_episodes(watched_episodes: &[(u32, u32)]) -> Series {
+        let mut series = Series::new("Under the Dome".to_owned(), 1);
+        for (season, episode) in watched_episodes {
+            series.add_episode_unchecked(*season, *episode);
+        }
+        series
+    }
+
+    #[tokio::test]
+    async fn resuming_from_gap_keeps_other_seasons() {
+        let _database = testing::lock_database().await;
+        let episode_list = episode_list();
+
+        let mut series = series_with_watched_episodes(&[(1, 1), (1, 3), (2, 1)]);
+
+        let gap_analysis = GapAnalysis::new(&series, &episode_list, Duration::weeks(1));
+        assert_eq!(gap_analysis.get_skipped_episodes(), [episode(1, 2)]);
+        assert_eq!(gap_analysis.get_episodes_after_gap(), [episode(1, 3)]);
+
+        gap_analysis.resume_from_gap(&mut series);
+
+        let first_season = series.get_season(1).expect("first season removed");
+        assert!(first_season.is_episode_watched(1));
+        assert!(!first_season.is_episode_watched(3));
+        assert!(series
+            .get_season(2)
+            .expect("second season removed")
+            .is_episode_watched(1));
+    }
+
+    #[tokio::test]
+    async fn marking_gaps_watched_leaves_no_gaps() {
+        let _database = testing::lock_database().await;
+        let episode_list = episode_list();
+
+        let mut series = series_with_watched_episodes(&[(1, 1), (1, 3), (2, 2)]);
+
+        let gap_analysis = GapAnalysis::new(&series, &episode_list, Duration::weeks(1));
+        assert_eq!(
+            gap_analysis.get_skipped_episodes(),
+            [episode(1, 2), episode(2, 1)]
+        );
+
+        gap_analysis.mark_gaps_watched(&mut series);
+
+        assert_eq!(series.get_total_episodes(), 5);
+        let gap_analysis = GapAnalysis::new(&series, &episode_list, Duration::weeks(1));
+        assert!(!gap_analysis.has_gaps());
+        assert!(gap_analysis.get_partial_seasons().is_empty());
+    }
+
+    #[tokio::test]
+    async fn partially_watched_seasons_are_reported() {
+        let _database = testing::lock_database().await;
+        let episode_list = episode_list();
+
+        // The first season fully watched, the second one started
+        let series = series_with_watched_episodes(&[(1, 1), (1, 2), (1, 3), (2, 1)]);
+
+        let gap_analysis = GapAnalysis::new(&series, &episode_list, Duration::weeks(1));
+
+        assert!(!gap_analysis.has_gaps());
+        assert_eq!(
+            gap_analysis.get_partial_seasons(),
+            [PartialSeason {
+                season: 2,
+                watched_episodes: 1,
+                watchable_episodes: 2,
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn series_not_watched_for_long_with_episodes_left_are_abandoned() {
+        let _database = testing::lock_database().await;
+        let episode_list = episode_list();
+        let last_watch_time = Utc::now() - Duration::weeks(5);
+
+        let mut series = Series::new("Under the Dome".to_owned(), 1);
+        series.add_episode_unchecked_with_time(1, 1, last_watch_time);
+
+        let gap_analysis = GapAnalysis::new(&series, &episode_list, Duration::weeks(4));
+        assert_eq!(gap_analysis.get_abandoned_since(), Some(last_watch_time));
+        assert!(gap_analysis.has_findings());
+
+        let gap_analysis = GapAnalysis::new(&series, &episode_list, Duration::weeks(6));
+        assert_eq!(gap_analysis.get_abandoned_since(), None);
+
+        // Nothing left to watch
+        for (season, episode) in [(1, 2), (1, 3), (2, 1), (2, 2)] {
+            series.add_episode_unchecked_with_time(season, episode, last_watch_time);
+        }
+        let gap_analysis = GapAnalysis::new(&series, &episode_list, Duration::weeks(4));
+        assert_eq!(gap_analysis.get_abandoned_since(), None);
+    }
+}
@@ -0,0 +1,181 @@
+//! Detecting an already running instance of the program
+//!
+//! The database can only be opened by one process at a time, so the gui and the daemon
+//! hold an exclusive lock on a file in the data directory for as long as they run. The lock
+//! is released by the OS when the process dies, a lockfile left behind by a crash hence does
+//! not prevent starting again.
+//!
+//! The lockfile also records what the holding instance runs as, so that starting while the
+//! daemon runs can tell the user to stop the daemon first.
+
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use fs2::FileExt;
+use thiserror::Error;
+use tracing::{info, warn};
+
+use super::paths;
+
+const LOCK_FILENAME: &str = "series-troxide.lock";
+
+#[derive(Debug, Error)]
+pub enum InstanceLockError {
+    #[error("{0}")]
+    AlreadyRunning(RunningInstance),
+
+    #[error("failed to lock '{0}': {1}")]
+    Io(PathBuf, io::Error),
+}
+
+/// What an instance holding the lock runs as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstanceKind {
+    Gui,
+    Cli,
+    Daemon,
+}
+
+impl InstanceKind {
+    fn name(&self) -> &'static str {
+        match self {
+            InstanceKind::Gui => "gui",
+            InstanceKind::Cli => "cli",
+            InstanceKind::Daemon => "daemon",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        [Self::Gui, Self::Cli, Self::Daemon]
+            .into_iter()
+            .find(|kind| kind.name() == name)
+    }
+}
+
+/// The instance holding the lock, read from the lockfile
+#[derive(Debug)]
+pub struct RunningInstance {
+    pub kind: Option<InstanceKind>,
+    pub pid: Option<u32>,
+}
+
+impl RunningInstance {
+    /// Reads the lockfile, written as the pid followed by the kind on the next line
+    fn read(path: &Path) -> Self {
+        let contents = fs::read_to_string(path).unwrap_or_default();
+        let mut lines = contents.lines();
+        let pid = lines.next().and_then(|pid| pid.trim().parse().ok());
+        let kind = lines
+            .next()
+            .and_then(|kind| InstanceKind::from_name(kind.trim()));
+        Self { kind, pid }
+    }
+}
+
+impl fmt::Display for RunningInstance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pid = self
+            .pid
+            .map(|pid| format!(" with pid {}", pid))
+            .unwrap_or_default();
+        match self.kind {
+            Some(InstanceKind::Daemon) => write!(
+                f,
+                "the series troxide daemon is already running{}, it has to be stopped before \
+                 using series troxide i.e. with 'systemctl --user stop series-troxide'",
+                pid
+            ),
+            _ => write!(
+                f,
+                "another instance of series troxide is already running{}",
+                pid
+            ),
+        }
+    }
+}
+
+/// An exclusive lock on the lockfile, released and removed on drop
+pub struct InstanceLock {
+    file: File,
+    path: PathBuf,
+}
+
+impl InstanceLock {
+    /// Locks the lockfile writing the current pid and the given kind into it
+    pub fn acquire(kind: InstanceKind) -> Result<Self, InstanceLockError> {
+        let data_dir = paths::PATHS
+            .read()
+            .expect("failed to read paths")
+            .get_data_dir_path()
+            .into_owned();
+        let path = data_dir.join(LOCK_FILENAME);
+
+        let io_error = |err| InstanceLockError::Io(path.clone(), err);
+
+        fs::create_dir_all(&data_dir).map_err(io_error)?;
+
+        // Not truncating before the lock is held so that the pid of a running instance is kept
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .map_err(io_error)?;
+
+        if let Err(err) = file.try_lock_exclusive() {
+            if err.kind() == fs2::lock_contended_error().kind() {
+                return Err(InstanceLockError::AlreadyRunning(RunningInstance::read(
+                    &path,
+                )));
+            }
+            return Err(io_error(err));
+        }
+
+        file.set_len(0).map_err(io_error)?;
+        write!(file, "{}\n{}", std::process::id(), kind.name()).map_err(io_error)?;
+
+        info!("acquired instance lock at '{}'", path.display());
+        Ok(Self { file, path })
+    }
+}
+
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        // Removing before unlocking so that another instance never locks a file that's about to be removed
+        if let Err(err) = fs::remove_file(&self.path) {
+            warn!("failed to remove '{}': {}", self.path.display(), err);
+        }
+        if let Err(err) = self.file.unlock() {
+            warn!("failed to unlock '{}': {}", self.path.display(), err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testing;
+
+    #[test]
+    fn running_daemon_is_reported_to_other_instances() {
+        testing::environment();
+
+        let _daemon_lock =
+            InstanceLock::acquire(InstanceKind::Daemon).expect("failed to lock as the daemon");
+
+        match InstanceLock::acquire(InstanceKind::Cli) {
+            Err(InstanceLockError::AlreadyRunning(running_instance)) => {
+                assert_eq!(running_instance.kind, Some(InstanceKind::Daemon));
+                assert_eq!(running_instance.pid, Some(std::process::id()));
+                assert!(running_instance
+                    .to_string()
+                    .contains("systemctl --user stop"));
+            }
+            Err(err) => panic!("unexpected error: {}", err),
+            Ok(_) => panic!("locked while the daemon holds the lock"),
+        }
+    }
+}
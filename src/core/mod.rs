@@ -1,8 +1,17 @@
 pub mod api;
 pub mod caching;
+pub mod calendar_export;
 pub mod cli;
+pub mod daemon;
 pub mod database;
+pub mod external_import;
+pub mod gap_analysis;
+pub mod instance_lock;
 pub mod notifications;
 pub mod paths;
 pub mod posters_hiding;
+pub mod profiles;
 pub mod settings_config;
+#[cfg(test)]
+pub mod testing;
+pub mod up_next;
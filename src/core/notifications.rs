@@ -1,19 +1,45 @@
+pub mod digest;
+pub mod ledger;
+pub mod sinks;
+
 use super::{
     api::tv_maze::{episodes_information::Episode, series_information::SeriesMainInformation},
-    caching::series_list,
-    paths, settings_config,
+    caching::{episode_list::EpisodeList, series_list},
+    database, paths, profiles, settings_config,
 };
 use anyhow::Context;
-use chrono::Duration;
+use chrono::{Duration, Utc};
+use lazy_static::lazy_static;
+use ledger::NotificationLedger;
 use notify::{recommended_watcher, EventHandler, Watcher};
-use std::sync::mpsc;
+use std::sync::{mpsc, Mutex};
 use tokio::task::JoinHandle;
 
 enum Signal {
     SettingsFileChanged,
+    RefreshRequested,
     NotificationSent,
 }
 
+lazy_static! {
+    /// The sender of the running notifier, used to signal changes made outside the config file
+    static ref SIGNAL_SENDER: Mutex<Option<mpsc::Sender<Signal>>> = Mutex::new(None);
+}
+
+/// Makes the running notifier reschedule it's notifications i.e. after the notification
+/// preferences of a series or the cached releases changed
+pub fn refresh_notifications() {
+    if let Some(signal_sender) = SIGNAL_SENDER
+        .lock()
+        .expect("failed to lock signal sender")
+        .as_ref()
+    {
+        signal_sender
+            .send(Signal::RefreshRequested)
+            .unwrap_or_else(|err| tracing::error!("failed to refresh notifications: {}", err));
+    }
+}
+
 pub struct TroxideNotify {
     signal_receiver: mpsc::Receiver<Signal>,
     signal_sender: mpsc::Sender<Signal>,
@@ -26,6 +52,8 @@ impl TroxideNotify {
         let file_change_signal_sender = signal_sender.clone();
         std::thread::spawn(move || Self::file_change_watcher(file_change_signal_sender));
 
+        *SIGNAL_SENDER.lock().expect("failed to lock signal sender") = Some(signal_sender.clone());
+
         Ok(Self {
             signal_receiver,
             signal_sender,
@@ -36,22 +64,37 @@ impl TroxideNotify {
         tokio::runtime::Runtime::new()?.block_on(async {
             let mut current_notification_time_setting = get_current_notification_time_setting();
 
+            notify_missed_releases().await;
+
             loop {
-                // This is the time before the actual release of an episode that should be used by the notification
-                // to send notifications before the actual release of an episode.
-                let duration_before_release =
-                    Duration::minutes(current_notification_time_setting as i64);
-
-                // Creating a handle for each episode release notification so that we can be able to abort them at anytime
-                // we want.
-                let notification_handles: Vec<_> = get_releases_with_duration_to_release()
+                if let Some(ledger) = open_ledger() {
+                    if let Err(err) = ledger.set_last_seen(Utc::now()) {
+                        tracing::error!("failed to update the notification ledger: {}", err);
+                    }
+                }
+
+                // Creating a handle for each reminder of an episode release so that we can be able to abort them at anytime
+                // we want. Every series can have multiple reminders, each at it's own time before the actual release.
+                let mut notification_handles: Vec<_> = get_releases_with_duration_to_release()
                     .await
                     .into_iter()
-                    .map(|(series_info, episode, duration)| {
-                        (series_info, episode, duration - duration_before_release)
+                    .flat_map(|(series_info, episode, duration)| {
+                        get_reminder_lead_times(series_info.id, current_notification_time_setting)
+                            .into_iter()
+                            .map(move |lead_time| {
+                                (
+                                    series_info.clone(),
+                                    episode.clone(),
+                                    lead_time,
+                                    duration - Duration::minutes(lead_time as i64),
+                                )
+                            })
+                    })
+                    .filter(|(_, _, _, duration)| duration.to_std().is_ok())
+                    .filter(|(series_info, episode, lead_time, _)| {
+                        !is_reminder_notified(series_info.id, episode, *lead_time)
                     })
-                    .filter(|(_, _, duration)| duration.to_std().is_ok())
-                    .map(|(series_info, episode, duration)| {
+                    .map(|(series_info, episode, lead_time, duration)| {
                         let signal_sender = self.signal_sender.clone();
                         tokio::spawn(async move {
                             tracing::info!(
@@ -60,16 +103,19 @@ impl TroxideNotify {
                                 series_info.name,
                             );
                             tokio::time::sleep(duration.to_std().unwrap()).await;
-                            notify_episode_release(
-                                &series_info,
-                                &episode,
-                                current_notification_time_setting,
-                            );
+                            // A reminder can be rescheduled while it's being sent
+                            if !is_reminder_notified(series_info.id, &episode, lead_time) {
+                                notify_episode_release(&series_info, &episode, lead_time).await;
+                            }
                             signal_sender.send(Signal::NotificationSent).unwrap();
                         })
                     })
                     .collect();
 
+                if let Some(digest_handle) = schedule_digest(self.signal_sender.clone()) {
+                    notification_handles.push(digest_handle);
+                }
+
                 match &self.signal_receiver.recv().unwrap() {
                     Signal::SettingsFileChanged => {
                         /*
@@ -83,6 +129,11 @@ impl TroxideNotify {
 
                         Self::abort_notifications(notification_handles);
                     }
+                    Signal::RefreshRequested => {
+                        tracing::info!("refresh requested, refreshing notifications");
+
+                        Self::abort_notifications(notification_handles);
+                    }
                     Signal::NotificationSent => {
                         /*
                         When a new episode has been notified, when can't keep on using the same obtained episode releases as it might
@@ -149,46 +200,280 @@ async fn get_releases_with_duration_to_release() -> Vec<(SeriesMainInformation,
         .collect()
 }
 
-fn notify_episode_release(
+/// The times in minutes before a release of the given series to send reminders at
+///
+/// A muted series has no reminders.
+fn get_reminder_lead_times(series_id: u32, global_lead_time: u32) -> Vec<u32> {
+    match database::DB.get_series(series_id) {
+        Ok(Some(series)) => series
+            .get_notification_preferences()
+            .resolve_reminder_lead_times(global_lead_time),
+        Ok(None) => vec![global_lead_time],
+        Err(err) => {
+            tracing::error!(
+                "failed to get notification preferences of series with id '{}': {}",
+                series_id,
+                err
+            );
+            vec![global_lead_time]
+        }
+    }
+}
+
+async fn notify_episode_release(
     series_info: &SeriesMainInformation,
     episode: &Episode,
-    release_time_in_minute: u32,
+    lead_time: u32,
 ) {
-    let series_name = series_info.name.as_str();
-    let episode_name = episode.name.as_str();
-    let episode_order = crate::gui::helpers::season_episode_str_gen(
-        episode.season,
-        episode
-            .number
-            .expect("an episode should have a valid number"),
-    );
+    let Some(release) = sinks::Release::new(series_info, episode) else {
+        tracing::warn!(
+            "skipping a release notification of '{}' without an episode number or release time",
+            series_info.name
+        );
+        return;
+    };
+
+    let episode_order =
+        crate::gui::helpers::season_episode_str_gen(release.season, release.episode);
 
-    let notification_summary = format!("\"{}\" episode release", series_name);
+    let notification_summary = format!("\"{}\" episode release", release.series_name);
+
+    let notification_body = if lead_time == 0 {
+        format!(
+            "{}: {}, is being released now",
+            episode_order, release.episode_name
+        )
+    } else {
+        format!(
+            "{}: {}, will be released in {}",
+            episode_order,
+            release.episode_name,
+            crate::gui::helpers::time::SaneTime::new(lead_time)
+                .to_string()
+                .trim_end()
+        )
+    };
+
+    let notification = sinks::Notification {
+        summary: notification_summary,
+        body: notification_body,
+        html_body: None,
+        releases: vec![release.clone()],
+    };
+
+    sinks::deliver(&notification, &get_current_notification_sinks()).await;
+
+    if let Some(ledger) = open_ledger() {
+        if let Err(err) = ledger.record(
+            release.series_id,
+            release.season,
+            release.episode,
+            release.release_time,
+            lead_time,
+        ) {
+            tracing::error!("failed to record a sent notification: {}", err);
+        }
+    }
+}
+
+/// Sends a digest of the releases of tracked series that happened while the notifier was
+/// not running and were hence never notified
+async fn notify_missed_releases() {
+    let Some(ledger) = open_ledger() else {
+        return;
+    };
+
+    let now = Utc::now();
+    let last_seen = ledger.get_last_seen();
+    if let Err(err) = ledger.set_last_seen(now) {
+        tracing::error!("failed to update the notification ledger: {}", err);
+    }
+    ledger.prune();
+
+    // Nothing could have been missed before the first run
+    let Some(last_seen) = last_seen else {
+        return;
+    };
+
+    if !get_current_notification_settings().missed_releases_digest {
+        return;
+    }
+
+    let series_infos = match series_list::SeriesList::new()
+        .get_tracked_series_information()
+        .await
+    {
+        Ok(series_infos) => series_infos,
+        Err(err) => {
+            tracing::error!("failed to get tracked series for missed releases: {}", err);
+            return;
+        }
+    };
 
-    let notification_body = format!(
-        "{}: {}, will be released in {} minutes",
-        episode_order, episode_name, release_time_in_minute
+    let mut missed_releases = vec![];
+    for series_info in series_infos {
+        if get_reminder_lead_times(series_info.id, 0).is_empty() {
+            // Muted series
+            continue;
+        }
+
+        let episode_list = match EpisodeList::new(series_info.id).await {
+            Ok(episode_list) => episode_list,
+            Err(err) => {
+                tracing::error!(
+                    "failed to get episode list of '{}' for missed releases: {}",
+                    series_info.name,
+                    err
+                );
+                continue;
+            }
+        };
+
+        missed_releases.extend(
+            episode_list
+                .get_all_episodes()
+                .iter()
+                .filter_map(|episode| sinks::Release::new(&series_info, episode))
+                .filter(|release| release.release_time > last_seen && release.release_time <= now)
+                .filter(|release| {
+                    !ledger.has_entry(release.series_id, release.season, release.episode)
+                }),
+        );
+    }
+
+    if missed_releases.is_empty() {
+        return;
+    }
+    missed_releases.sort_by_key(|release| release.release_time);
+
+    tracing::info!(
+        "{} releases missed since the last run",
+        missed_releases.len()
     );
 
-    notify_rust::Notification::new()
-        .appname("Series Troxide")
-        .summary(&notification_summary)
-        .body(&notification_body)
-        .timeout(0)
-        .auto_icon()
-        .show()
-        .expect("failed to show notification");
+    let notification_summary = if missed_releases.len() == 1 {
+        "You missed an episode release".to_owned()
+    } else {
+        format!("You missed {} episode releases", missed_releases.len())
+    };
+
+    let notification_body = missed_releases
+        .iter()
+        .map(|release| {
+            format!(
+                "{} {}: {}, released on {}",
+                release.series_name,
+                crate::gui::helpers::season_episode_str_gen(release.season, release.episode),
+                release.episode_name,
+                release
+                    .release_time
+                    .with_timezone(&chrono::Local)
+                    .format("%a, %d %b %Y %H:%M")
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let notification = sinks::Notification {
+        summary: notification_summary,
+        body: notification_body,
+        html_body: None,
+        releases: missed_releases,
+    };
+
+    sinks::deliver(&notification, &get_current_notification_sinks()).await;
+
+    for release in &notification.releases {
+        if let Err(err) = ledger.record(
+            release.series_id,
+            release.season,
+            release.episode,
+            release.release_time,
+            0,
+        ) {
+            tracing::error!("failed to record a missed release: {}", err);
+        }
+    }
+}
+
+/// Spawns a task waiting for the next scheduled digest, `None` when digests are off
+fn schedule_digest(signal_sender: mpsc::Sender<Signal>) -> Option<JoinHandle<()>> {
+    let digest_settings = get_current_notification_settings().digest;
+    let digest_time = digest::get_next_digest_time(&digest_settings, chrono::Local::now())?;
+
+    Some(tokio::spawn(async move {
+        let duration = (digest_time - chrono::Local::now())
+            .to_std()
+            .unwrap_or_default();
+        tracing::info!("waiting {} minutes for the digest", duration.as_secs() / 60);
+        tokio::time::sleep(duration).await;
+
+        let ledger = open_ledger();
+        // The digest can be rescheduled while it's being sent
+        let is_sent = ledger
+            .as_ref()
+            .and_then(NotificationLedger::get_last_digest)
+            .map(|last_digest| last_digest >= digest_time)
+            .unwrap_or(false);
+
+        if !is_sent {
+            let window = digest::get_digest_window(digest_settings.frequency);
+            match digest::build_digest(window, digest_settings.html).await {
+                Some(notification) => {
+                    sinks::deliver(&notification, &get_current_notification_sinks()).await
+                }
+                None => tracing::info!("no releases for the digest, skipping it"),
+            }
+
+            if let Some(ledger) = ledger {
+                if let Err(err) = ledger.set_last_digest(Utc::now()) {
+                    tracing::error!("failed to record the sent digest: {}", err);
+                }
+            }
+        }
+
+        signal_sender.send(Signal::NotificationSent).unwrap();
+    }))
+}
+
+fn open_ledger() -> Option<NotificationLedger> {
+    NotificationLedger::open()
+        .map_err(|err| tracing::error!("failed to open the notification ledger: {}", err))
+        .ok()
+}
+
+fn is_reminder_notified(series_id: u32, episode: &Episode, lead_time: u32) -> bool {
+    let (Some(episode_number), Ok(release_time), Some(ledger)) =
+        (episode.number, episode.local_date_time(), open_ledger())
+    else {
+        return false;
+    };
+    ledger.is_notified(
+        series_id,
+        episode.season,
+        episode_number,
+        release_time.with_timezone(&Utc),
+        lead_time,
+    )
 }
 
 struct FileWatcherEventHandler {
     sender: mpsc::Sender<Signal>,
 }
 
-fn get_current_notification_time_setting() -> u32 {
+fn get_current_notification_settings() -> settings_config::NotificationSettings {
     settings_config::Settings::new()
         .get_current_settings()
-        .notifications
-        .time_to_notify
+        .get_notification_settings(&profiles::get_active_profile())
+        .clone()
+}
+
+fn get_current_notification_time_setting() -> u32 {
+    get_current_notification_settings().time_to_notify
+}
+
+fn get_current_notification_sinks() -> Vec<settings_config::NotificationSinkConfig> {
+    get_current_notification_settings().sinks
 }
 
 impl FileWatcherEventHandler {
@@ -0,0 +1,367 @@
+//! Scheduled digests of the upcoming releases of tracked series
+//!
+//! A digest lists every episode of the tracked series airing in the day or week following
+//! it's sending time. It's rendered as plain text and optionally as html for the sinks
+//! that can show it i.e. email and webhooks.
+
+use chrono::{DateTime, Datelike, Duration, Local, TimeZone, Utc};
+use tracing::error;
+
+use super::sinks::{Notification, Release};
+use crate::core::caching::{episode_list::EpisodeList, series_list::SeriesList};
+use crate::core::database::DB;
+use crate::core::settings_config::{DigestFrequency, DigestSettings};
+
+/// The next time a digest is due after the given time, in the time zone of the given time,
+/// `None` when digests are off
+pub fn get_next_digest_time<Tz: TimeZone>(
+    settings: &DigestSettings,
+    now: DateTime<Tz>,
+) -> Option<DateTime<Tz>> {
+    if settings.frequency == DigestFrequency::Off {
+        return None;
+    }
+
+    let mut date = now.date_naive();
+    // A week and a day covers a weekly digest due earlier today
+    for _ in 0..8 {
+        let is_due_day =
+            settings.frequency == DigestFrequency::Daily || date.weekday() == settings.weekday;
+
+        // Skipping times that don't exist on the day i.e. in a daylight saving time gap
+        if let Some(digest_time) = now
+            .timezone()
+            .from_local_datetime(&date.and_time(settings.time))
+            .earliest()
+        {
+            if is_due_day && digest_time > now {
+                return Some(digest_time);
+            }
+        }
+        date = date.succ_opt()?;
+    }
+    None
+}
+
+/// How far ahead of it's sending time a digest looks
+pub fn get_digest_window(frequency: DigestFrequency) -> Duration {
+    match frequency {
+        DigestFrequency::Off | DigestFrequency::Daily => Duration::days(1),
+        DigestFrequency::Weekly => Duration::days(7),
+    }
+}
+
+/// Whether a release at the given time belongs in a digest sent now
+fn is_in_window(release_time: DateTime<Utc>, now: DateTime<Utc>, window: Duration) -> bool {
+    release_time > now && release_time <= now + window
+}
+
+/// Builds the digest of the releases of the not muted tracked series between now and the
+/// end of the window, `None` when there are no releases
+pub async fn build_digest(window: Duration, with_html: bool) -> Option<Notification> {
+    let now = Utc::now();
+
+    let upcoming_releases = match SeriesList::new()
+        .get_upcoming_release_series_information_and_episodes()
+        .await
+    {
+        Ok(upcoming_releases) => upcoming_releases,
+        Err(err) => {
+            error!("failed to get upcoming releases for the digest: {}", err);
+            return None;
+        }
+    };
+
+    let mut releases = vec![];
+    for (series_info, _, release_time) in upcoming_releases {
+        // The next episode is the earliest one, the series has nothing else in the window when
+        // it's outside of it
+        if release_time.get_remaining_release_duration() > window || is_series_muted(series_info.id)
+        {
+            continue;
+        }
+
+        let episode_list = match EpisodeList::new(series_info.id).await {
+            Ok(episode_list) => episode_list,
+            Err(err) => {
+                error!(
+                    "failed to get episode list of '{}' for the digest: {}",
+                    series_info.name, err
+                );
+                continue;
+            }
+        };
+
+        releases.extend(
+            episode_list
+                .get_future_episodes()
+                .into_iter()
+                .filter_map(|episode| Release::new(&series_info, episode))
+                .filter(|release| is_in_window(release.release_time, now, window)),
+        );
+    }
+
+    if releases.is_empty() {
+        return None;
+    }
+    releases.sort_by(|a, b| {
+        a.release_time
+            .cmp(&b.release_time)
+            .then_with(|| a.series_name.cmp(&b.series_name))
+    });
+
+    let summary = if releases.len() == 1 {
+        "An episode release coming up".to_owned()
+    } else {
+        format!("{} episode releases coming up", releases.len())
+    };
+
+    Some(Notification {
+        summary,
+        body: render_text(&releases),
+        html_body: with_html.then(|| render_html(&releases)),
+        releases,
+    })
+}
+
+fn is_series_muted(series_id: u32) -> bool {
+    DB.get_series(series_id)
+        .ok()
+        .flatten()
+        .map(|series| series.get_notification_preferences().is_muted())
+        .unwrap_or(false)
+}
+
+/// The releases grouped by their local release day
+fn group_by_day(releases: &[Release]) -> Vec<(String, Vec<&Release>)> {
+    let mut days: Vec<(String, Vec<&Release>)> = vec![];
+    for release in releases {
+        let day = release
+            .release_time
+            .with_timezone(&Local)
+            .format("%A, %d %B")
+            .to_string();
+        match days.last_mut() {
+            Some((last_day, day_releases)) if *last_day == day => day_releases.push(release),
+            _ => days.push((day, vec![release])),
+        }
+    }
+    days
+}
+
+fn format_release(release: &Release) -> (String, String) {
+    let time = release
+        .release_time
+        .with_timezone(&Local)
+        .format("%H:%M")
+        .to_string();
+    let episode = format!(
+        "{} {}: {}",
+        release.series_name,
+        crate::gui::helpers::season_episode_str_gen(release.season, release.episode),
+        release.episode_name
+    );
+    (time, episode)
+}
+
+fn render_text(releases: &[Release]) -> String {
+    let mut text = String::new();
+    for (day, day_releases) in group_by_day(releases) {
+        if !text.is_empty() {
+            text.push('\n');
+        }
+        text.push_str(&day);
+        text.push('\n');
+        for release in day_releases {
+            let (time, episode) = format_release(release);
+            text.push_str(&format!("  {}  {}\n", time, episode));
+        }
+    }
+    text
+}
+
+fn render_html(releases: &[Release]) -> String {
+    let mut html = String::from("<html><body>\n");
+    for (day, day_releases) in group_by_day(releases) {
+        html.push_str(&format!("<h3>{}</h3>\n<ul>\n", escape_html(&day)));
+        for release in day_releases {
+            let (time, episode) = format_release(release);
+            html.push_str(&format!(
+                "<li><b>{}</b> {}</li>\n",
+                escape_html(&time),
+                escape_html(&episode)
+            ));
+        }
+        html.push_str("</ul>\n");
+    }
+    html.push_str("</body></html>\n");
+    html
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped_text = String::with_capacity(text.len());
+    for character in text.chars() {
+        match character {
+            '&' => escaped_text.push_str("&amp;"),
+            '<' => escaped_text.push_str("&lt;"),
+            '>' => escaped_text.push_str("&gt;"),
+            '"' => escaped_text.push_str("&quot;"),
+            '\'' => escaped_text.push_str("&#39;"),
+            character => escaped_text.push(character),
+        }
+    }
+    escaped_text
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, NaiveTime, Weekday};
+    use chrono_tz::{Europe::Berlin, Tz};
+
+    use super::*;
+
+    fn digest_settings(frequency: DigestFrequency, hour: u32, minute: u32) -> DigestSettings {
+        DigestSettings {
+            frequency,
+            time: NaiveTime::from_hms_opt(hour, minute, 0).unwrap(),
+            weekday: Weekday::Mon,
+            html: true,
+        }
+    }
+
+    /// A time of 2023 in Berlin, the 1st of March being a Wednesday
+    fn berlin_time(month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Tz> {
+        Berlin
+            .with_ymd_and_hms(2023, month, day, hour, minute, 0)
+            .single()
+            .unwrap()
+    }
+
+    fn release(series_name: &str, episode_name: &str) -> Release {
+        Release {
+            series_id: 1,
+            series_name: series_name.to_owned(),
+            season: 1,
+            episode: 1,
+            episode_name: episode_name.to_owned(),
+            release_time: Utc.with_ymd_and_hms(2023, 3, 1, 20, 0, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn no_digest_is_due_when_they_are_off() {
+        let settings = digest_settings(DigestFrequency::Off, 9, 0);
+        assert_eq!(
+            get_next_digest_time(&settings, berlin_time(3, 1, 8, 0)),
+            None
+        );
+    }
+
+    #[test]
+    fn daily_digest_is_due_today_until_it_has_been_sent() {
+        let settings = digest_settings(DigestFrequency::Daily, 9, 0);
+
+        let next_digest_time = |now| get_next_digest_time(&settings, now);
+
+        assert_eq!(
+            next_digest_time(berlin_time(3, 1, 8, 0)),
+            Some(berlin_time(3, 1, 9, 0))
+        );
+        assert_eq!(
+            next_digest_time(berlin_time(3, 1, 9, 0)),
+            Some(berlin_time(3, 2, 9, 0))
+        );
+        assert_eq!(
+            next_digest_time(berlin_time(3, 1, 23, 0)),
+            Some(berlin_time(3, 2, 9, 0))
+        );
+    }
+
+    #[test]
+    fn weekly_digest_is_due_on_the_configured_weekday() {
+        let settings = digest_settings(DigestFrequency::Weekly, 9, 0);
+
+        let next_digest_time = |now| get_next_digest_time(&settings, now);
+
+        // Wednesday
+        assert_eq!(
+            next_digest_time(berlin_time(3, 1, 8, 0)),
+            Some(berlin_time(3, 6, 9, 0))
+        );
+        // Monday before and after the digest time
+        assert_eq!(
+            next_digest_time(berlin_time(3, 6, 8, 0)),
+            Some(berlin_time(3, 6, 9, 0))
+        );
+        assert_eq!(
+            next_digest_time(berlin_time(3, 6, 10, 0)),
+            Some(berlin_time(3, 13, 9, 0))
+        );
+    }
+
+    #[test]
+    fn digest_time_missing_on_a_daylight_saving_day_is_skipped() {
+        // The clocks jump from 02:00 to 03:00 on the 26th of March
+        let settings = digest_settings(DigestFrequency::Daily, 2, 30);
+
+        let next_digest_time = get_next_digest_time(&settings, berlin_time(3, 25, 3, 0));
+
+        assert_eq!(next_digest_time, Some(berlin_time(3, 27, 2, 30)));
+    }
+
+    #[test]
+    fn repeated_digest_time_on_a_daylight_saving_day_is_due_once() {
+        // The clocks go back from 03:00 to 02:00 on the 29th of October
+        let settings = digest_settings(DigestFrequency::Daily, 2, 30);
+        let first_digest_time = Berlin
+            .from_local_datetime(
+                &NaiveDate::from_ymd_opt(2023, 10, 29)
+                    .unwrap()
+                    .and_time(settings.time),
+            )
+            .earliest()
+            .unwrap();
+
+        assert_eq!(
+            get_next_digest_time(&settings, berlin_time(10, 29, 0, 0)),
+            Some(first_digest_time)
+        );
+        // Not due again when 02:30 comes a second time
+        assert_eq!(
+            get_next_digest_time(&settings, first_digest_time),
+            Some(berlin_time(10, 30, 2, 30))
+        );
+    }
+
+    #[test]
+    fn window_holds_the_releases_after_now_until_the_window_end() {
+        let now = Utc.with_ymd_and_hms(2023, 3, 1, 9, 0, 0).unwrap();
+        let window = get_digest_window(DigestFrequency::Daily);
+
+        assert!(!is_in_window(now, now, window));
+        assert!(is_in_window(now + Duration::seconds(1), now, window));
+        assert!(is_in_window(now + window, now, window));
+        assert!(!is_in_window(
+            now + window + Duration::seconds(1),
+            now,
+            window
+        ));
+
+        let weekly_window = get_digest_window(DigestFrequency::Weekly);
+        assert!(is_in_window(now + Duration::days(7), now, weekly_window));
+        assert!(!is_in_window(now + Duration::days(8), now, weekly_window));
+    }
+
+    #[test]
+    fn html_digest_escapes_the_names() {
+        let releases = [release("Law & Order: \"<SVU>\"", "<b>Pilot</b>")];
+
+        let html = render_html(&releases);
+
+        assert!(html
+            .contains("Law &amp; Order: &quot;&lt;SVU&gt;&quot; S01E01: &lt;b&gt;Pilot&lt;/b&gt;"));
+        assert!(!html.contains("<SVU>"));
+        assert!(!html.contains("<b>Pilot"));
+        assert!(render_text(&releases).contains("Law & Order: \"<SVU>\" S01E01: <b>Pilot</b>"));
+    }
+}
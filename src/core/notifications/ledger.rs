@@ -0,0 +1,184 @@
+//! A persisted record of the sent release notifications
+//!
+//! The notifier recomputes it's timers from scratch whenever something changes, so the
+//! ledger is what keeps it from notifying a reminder twice, be it in the same run or after a
+//! restart. It also remembers when the notifier last ran so that the releases that happened
+//! while the program was closed can be reported at startup.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sled::Tree;
+use tracing::warn;
+
+use crate::core::database::{get_episode_key, DatabaseError, DB};
+
+const LEDGER_TREE_NAME: &str = "notification-ledger";
+const LAST_SEEN_KEY: &[u8] = b"last-seen";
+const LAST_DIGEST_KEY: &[u8] = b"last-digest";
+/// Entries of releases older than this are removed as they can no longer be notified
+const ENTRY_RETENTION_DAYS: i64 = 30;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct LedgerEntry {
+    release_time: Option<DateTime<Utc>>,
+    /// The lead times in minutes of the reminders already sent
+    notified_lead_times: Vec<u32>,
+}
+
+pub struct NotificationLedger {
+    tree: Tree,
+}
+
+impl NotificationLedger {
+    /// Opens the ledger of the active profile
+    pub fn open() -> Result<Self, DatabaseError> {
+        Ok(Self {
+            tree: DB.open_profile_tree(LEDGER_TREE_NAME)?,
+        })
+    }
+
+    /// Whether the reminder of the given lead time has been sent for the episode releasing at
+    /// the given time
+    ///
+    /// The reminders sent before the episode got rescheduled don't count as they told the
+    /// old release time.
+    pub fn is_notified(
+        &self,
+        series_id: u32,
+        season: u32,
+        episode: u32,
+        release_time: DateTime<Utc>,
+        lead_time: u32,
+    ) -> bool {
+        self.get_entry(series_id, season, episode)
+            .filter(|entry| entry.release_time == Some(release_time))
+            .map(|entry| entry.notified_lead_times.contains(&lead_time))
+            .unwrap_or(false)
+    }
+
+    /// Whether any reminder or digest has covered the episode
+    pub fn has_entry(&self, series_id: u32, season: u32, episode: u32) -> bool {
+        self.get_entry(series_id, season, episode).is_some()
+    }
+
+    /// Records the reminder of the given lead time as sent
+    pub fn record(
+        &self,
+        series_id: u32,
+        season: u32,
+        episode: u32,
+        release_time: DateTime<Utc>,
+        lead_time: u32,
+    ) -> Result<(), DatabaseError> {
+        // The reminders sent for an older release time are forgotten
+        let mut entry = self
+            .get_entry(series_id, season, episode)
+            .filter(|entry| entry.release_time == Some(release_time))
+            .unwrap_or_default();
+        entry.release_time = Some(release_time);
+        if !entry.notified_lead_times.contains(&lead_time) {
+            entry.notified_lead_times.push(lead_time);
+        }
+
+        let entry_bytes = bincode::serialize(&entry).map_err(DatabaseError::Serialization)?;
+        self.tree
+            .insert(get_episode_key(series_id, season, episode), entry_bytes)
+            .map_err(DatabaseError::Sled)?;
+        Ok(())
+    }
+
+    /// The last time the notifier was running, `None` on it's first run
+    pub fn get_last_seen(&self) -> Option<DateTime<Utc>> {
+        let bytes = self.tree.get(LAST_SEEN_KEY).ok()??;
+        bincode::deserialize(&bytes).ok()
+    }
+
+    pub fn set_last_seen(&self, time: DateTime<Utc>) -> Result<(), DatabaseError> {
+        let time_bytes = bincode::serialize(&time).map_err(DatabaseError::Serialization)?;
+        self.tree
+            .insert(LAST_SEEN_KEY, time_bytes)
+            .map_err(DatabaseError::Sled)?;
+        Ok(())
+    }
+
+    /// The last time a scheduled digest was sent
+    pub fn get_last_digest(&self) -> Option<DateTime<Utc>> {
+        let bytes = self.tree.get(LAST_DIGEST_KEY).ok()??;
+        bincode::deserialize(&bytes).ok()
+    }
+
+    pub fn set_last_digest(&self, time: DateTime<Utc>) -> Result<(), DatabaseError> {
+        let time_bytes = bincode::serialize(&time).map_err(DatabaseError::Serialization)?;
+        self.tree
+            .insert(LAST_DIGEST_KEY, time_bytes)
+            .map_err(DatabaseError::Sled)?;
+        Ok(())
+    }
+
+    /// Removes the entries of releases older than the retention period
+    pub fn prune(&self) {
+        let oldest_release_time = Utc::now() - Duration::days(ENTRY_RETENTION_DAYS);
+
+        let expired_keys: Vec<_> = self
+            .tree
+            .iter()
+            .filter_map(Result::ok)
+            .filter(|(key, _)| key.as_ref() != LAST_SEEN_KEY && key.as_ref() != LAST_DIGEST_KEY)
+            .filter(|(_, entry_bytes)| {
+                bincode::deserialize::<LedgerEntry>(entry_bytes)
+                    .map(|entry| {
+                        entry
+                            .release_time
+                            .map(|release_time| release_time < oldest_release_time)
+                            .unwrap_or(true)
+                    })
+                    .unwrap_or(true)
+            })
+            .map(|(key, _)| key)
+            .collect();
+
+        for key in expired_keys {
+            if let Err(err) = self.tree.remove(key) {
+                warn!(
+                    "failed to remove an expired notification ledger entry: {}",
+                    err
+                );
+            }
+        }
+    }
+
+    fn get_entry(&self, series_id: u32, season: u32, episode: u32) -> Option<LedgerEntry> {
+        let entry_bytes = match self.tree.get(get_episode_key(series_id, season, episode)) {
+            Ok(entry_bytes) => entry_bytes?,
+            Err(err) => {
+                warn!("failed to read the notification ledger: {}", err);
+                return None;
+            }
+        };
+        bincode::deserialize(&entry_bytes).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::core::testing;
+
+    #[tokio::test]
+    async fn rescheduled_release_is_not_notified() {
+        let _database = testing::lock_database().await;
+        let ledger = NotificationLedger::open().unwrap();
+        let release_time = Utc.with_ymd_and_hms(2023, 1, 1, 20, 0, 0).unwrap();
+        let new_release_time = release_time + Duration::days(7);
+
+        ledger.record(1, 1, 1, release_time, 10).unwrap();
+        assert!(ledger.is_notified(1, 1, 1, release_time, 10));
+        assert!(!ledger.is_notified(1, 1, 1, new_release_time, 10));
+
+        ledger.record(1, 1, 1, new_release_time, 60).unwrap();
+        assert!(ledger.is_notified(1, 1, 1, new_release_time, 60));
+        assert!(!ledger.is_notified(1, 1, 1, new_release_time, 10));
+    }
+}
@@ -0,0 +1,632 @@
+//! Destinations of the release notifications
+//!
+//! Every sink configured under `[notifications]` in the config file gets every release
+//! notification. A sink failing to deliver a notification is logged and does not affect
+//! the other sinks.
+
+use std::process::Stdio;
+
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use lettre::message::{MultiPart, SinglePart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::transport::smtp::extension::ClientId;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use serde::Serialize;
+use tracing::{error, info};
+
+use crate::core::api::tv_maze::episodes_information::Episode;
+use crate::core::api::tv_maze::series_information::SeriesMainInformation;
+use crate::core::settings_config::{EmailSinkConfig, NotificationSinkConfig, SmtpSecurity};
+
+/// The name the email sink introduces itself with to the SMTP server
+const SMTP_HELLO_NAME: &str = "series-troxide";
+
+/// A notification as handed to the sinks
+#[derive(Debug, Clone, Serialize)]
+pub struct Notification {
+    pub summary: String,
+    pub body: String,
+    /// The body rendered as html for the sinks that can show it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub html_body: Option<String>,
+    /// The releases the notification is about
+    pub releases: Vec<Release>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Release {
+    pub series_id: u32,
+    pub series_name: String,
+    pub season: u32,
+    pub episode: u32,
+    pub episode_name: String,
+    pub release_time: DateTime<Utc>,
+}
+
+impl Release {
+    /// Returns `None` when the episode has no number or release time
+    pub fn new(series_info: &SeriesMainInformation, episode: &Episode) -> Option<Self> {
+        Some(Self {
+            series_id: series_info.id,
+            series_name: series_info.name.clone(),
+            season: episode.season,
+            episode: episode.number?,
+            episode_name: episode.name.clone(),
+            release_time: episode.local_date_time().ok()?.with_timezone(&Utc),
+        })
+    }
+}
+
+#[async_trait]
+pub trait NotificationSink: Send + Sync {
+    /// A short description of the sink used in logs
+    fn name(&self) -> String;
+
+    async fn send(&self, notification: &Notification) -> anyhow::Result<()>;
+}
+
+pub fn build_sink(config: &NotificationSinkConfig) -> Box<dyn NotificationSink> {
+    match config {
+        NotificationSinkConfig::Desktop => Box::new(DesktopSink),
+        NotificationSinkConfig::Webhook { url, headers } => Box::new(WebhookSink {
+            url: url.clone(),
+            headers: headers.clone().into_iter().collect(),
+        }),
+        NotificationSinkConfig::Email(config) => Box::new(EmailSink {
+            config: config.clone(),
+        }),
+        NotificationSinkConfig::Command { command } => Box::new(CommandSink {
+            command: command.clone(),
+        }),
+    }
+}
+
+/// Sends the notification to all the given sinks, logging the failed deliveries
+pub async fn deliver(notification: &Notification, sink_configs: &[NotificationSinkConfig]) {
+    for sink in sink_configs.iter().map(build_sink) {
+        match sink.send(notification).await {
+            Ok(()) => info!(
+                "delivered \"{}\" notification to {}",
+                notification.summary,
+                sink.name()
+            ),
+            Err(err) => error!(
+                "failed to deliver \"{}\" notification to {}: {:#}",
+                notification.summary,
+                sink.name(),
+                err
+            ),
+        }
+    }
+}
+
+pub struct DesktopSink;
+
+#[async_trait]
+impl NotificationSink for DesktopSink {
+    fn name(&self) -> String {
+        "desktop".to_owned()
+    }
+
+    async fn send(&self, notification: &Notification) -> anyhow::Result<()> {
+        let summary = notification.summary.clone();
+        let body = notification.body.clone();
+
+        // Showing a notification blocks on some platforms
+        tokio::task::spawn_blocking(move || {
+            notify_rust::Notification::new()
+                .appname("Series Troxide")
+                .summary(&summary)
+                .body(&body)
+                .timeout(0)
+                .auto_icon()
+                .show()
+                .map(|_| ())
+        })
+        .await?
+        .context("failed to show desktop notification")
+    }
+}
+
+/// The json body of webhook requests
+///
+/// `text` and `content` are what Slack and Discord style webhooks display, the rest is
+/// there for services wanting the details.
+#[derive(Serialize)]
+struct WebhookPayload<'a> {
+    text: String,
+    content: String,
+    #[serde(flatten)]
+    notification: &'a Notification,
+}
+
+pub struct WebhookSink {
+    url: String,
+    headers: Vec<(String, String)>,
+}
+
+#[async_trait]
+impl NotificationSink for WebhookSink {
+    fn name(&self) -> String {
+        format!("webhook '{}'", self.url)
+    }
+
+    async fn send(&self, notification: &Notification) -> anyhow::Result<()> {
+        let message = format!("{}\n{}", notification.summary, notification.body);
+        let payload = WebhookPayload {
+            text: message.clone(),
+            content: message,
+            notification,
+        };
+
+        let mut request = reqwest::Client::new().post(&self.url).json(&payload);
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+
+        request
+            .send()
+            .await
+            .context("failed to send the request")?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+pub struct CommandSink {
+    command: String,
+}
+
+#[async_trait]
+impl NotificationSink for CommandSink {
+    fn name(&self) -> String {
+        format!("command '{}'", self.command)
+    }
+
+    async fn send(&self, notification: &Notification) -> anyhow::Result<()> {
+        #[cfg(unix)]
+        let mut command = {
+            let mut command = tokio::process::Command::new("sh");
+            command.arg("-c");
+            command
+        };
+        #[cfg(not(unix))]
+        let mut command = {
+            let mut command = tokio::process::Command::new("cmd");
+            command.arg("/C");
+            command
+        };
+
+        command
+            .arg(&self.command)
+            .env("TROXIDE_SUMMARY", &notification.summary)
+            .env("TROXIDE_BODY", &notification.body)
+            .env(
+                "TROXIDE_RELEASES",
+                serde_json::to_string(&notification.releases)?,
+            );
+
+        if let Some(html_body) = &notification.html_body {
+            command.env("TROXIDE_HTML_BODY", html_body);
+        }
+
+        // The details of notifications about a single release get their own variables
+        if let [release] = notification.releases.as_slice() {
+            command
+                .env("TROXIDE_SERIES_ID", release.series_id.to_string())
+                .env("TROXIDE_SERIES_NAME", &release.series_name)
+                .env("TROXIDE_SEASON", release.season.to_string())
+                .env("TROXIDE_EPISODE", release.episode.to_string())
+                .env("TROXIDE_EPISODE_NAME", &release.episode_name)
+                .env("TROXIDE_RELEASE_TIME", release.release_time.to_rfc3339());
+        }
+
+        let output = command
+            .stdin(Stdio::null())
+            .output()
+            .await
+            .context("failed to run the command")?;
+
+        if !output.status.success() {
+            bail!(
+                "command exited with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(())
+    }
+}
+
+pub struct EmailSink {
+    config: EmailSinkConfig,
+}
+
+#[async_trait]
+impl NotificationSink for EmailSink {
+    fn name(&self) -> String {
+        format!("email '{}'", self.config.to.join(", "))
+    }
+
+    async fn send(&self, notification: &Notification) -> anyhow::Result<()> {
+        let message = build_email_message(&self.config, notification)?;
+        build_smtp_transport(&self.config)?
+            .send(message)
+            .await
+            .context("failed to submit the email")?;
+        Ok(())
+    }
+}
+
+fn build_smtp_transport(
+    config: &EmailSinkConfig,
+) -> anyhow::Result<AsyncSmtpTransport<Tokio1Executor>> {
+    let builder = match config.security {
+        SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.server)?,
+        SmtpSecurity::StartTls => {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.server)?
+        }
+        SmtpSecurity::None => {
+            AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(config.server.as_str())
+        }
+    };
+    let mut builder = builder
+        .port(
+            config
+                .port
+                .unwrap_or_else(|| config.security.default_port()),
+        )
+        .hello_name(ClientId::Domain(SMTP_HELLO_NAME.to_owned()));
+
+    if let Some(username) = &config.username {
+        builder = builder.credentials(Credentials::new(
+            username.clone(),
+            config.password.clone().unwrap_or_default(),
+        ));
+    }
+    Ok(builder.build())
+}
+
+/// The email of the notification
+///
+/// Notifications with an html body are sent as a multipart email with the plain text body
+/// as the alternative. The bodies get a transfer encoding keeping them 7 bit with short
+/// lines, so the server does not have to support 8BITMIME.
+fn build_email_message(
+    config: &EmailSinkConfig,
+    notification: &Notification,
+) -> anyhow::Result<Message> {
+    let mut builder = Message::builder()
+        .from(
+            config
+                .from
+                .parse()
+                .with_context(|| format!("invalid sender '{}'", config.from))?,
+        )
+        .subject(&notification.summary);
+    for recipient in &config.to {
+        builder = builder.to(recipient
+            .parse()
+            .with_context(|| format!("invalid recipient '{}'", recipient))?);
+    }
+
+    let message = match &notification.html_body {
+        Some(html_body) => builder.multipart(MultiPart::alternative_plain_html(
+            notification.body.clone(),
+            html_body.clone(),
+        )),
+        None => builder.singlepart(SinglePart::plain(notification.body.clone())),
+    };
+    message.context("failed to build the email")
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::Engine;
+    use chrono::TimeZone;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    fn notification() -> Notification {
+        Notification {
+            summary: "Under the Dome S01E02".to_owned(),
+            body: "The Fire is out\n.hidden line".to_owned(),
+            html_body: None,
+            releases: vec![Release {
+                series_id: 1,
+                series_name: "Under the Dome".to_owned(),
+                season: 1,
+                episode: 2,
+                episode_name: "The Fire".to_owned(),
+                release_time: Utc.with_ymd_and_hms(2013, 7, 1, 2, 0, 0).unwrap(),
+            }],
+        }
+    }
+
+    fn email_config(port: u16) -> EmailSinkConfig {
+        EmailSinkConfig {
+            server: "127.0.0.1".to_owned(),
+            port: Some(port),
+            security: SmtpSecurity::None,
+            username: Some("troxide".to_owned()),
+            password: Some("secret".to_owned()),
+            from: "Series Troxide <troxide@example.com>".to_owned(),
+            to: vec!["viewer@example.com".to_owned()],
+        }
+    }
+
+    /// Accepts a single http request answering with the given status, returning the request
+    async fn serve_http_request(listener: TcpListener, status: &str) -> String {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut stream = BufReader::new(stream);
+
+        let mut request = String::new();
+        let mut content_length = 0;
+        loop {
+            let mut line = String::new();
+            stream.read_line(&mut line).await.unwrap();
+            if let Some(length) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                content_length = length.trim().parse().unwrap();
+            }
+            request.push_str(&line);
+            if line == "\r\n" {
+                break;
+            }
+        }
+
+        let mut body = vec![0; content_length];
+        stream.read_exact(&mut body).await.unwrap();
+        request.push_str(&String::from_utf8(body).unwrap());
+
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            status
+        );
+        stream
+            .get_mut()
+            .write_all(response.as_bytes())
+            .await
+            .unwrap();
+        request
+    }
+
+    /// Plays an SMTP server without 8BITMIME accepting a single email, returning the received
+    /// commands and the message as it was sent
+    async fn serve_smtp_session(listener: TcpListener) -> (Vec<String>, String) {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut stream = BufReader::new(stream);
+        stream
+            .get_mut()
+            .write_all(b"220 localhost ready\r\n")
+            .await
+            .unwrap();
+
+        let mut commands = vec![];
+        let mut message = String::new();
+        loop {
+            let mut line = String::new();
+            if stream.read_line(&mut line).await.unwrap() == 0 {
+                break;
+            }
+            let command = line.trim_end().to_owned();
+
+            let reply: &[u8] = if command.starts_with("EHLO") {
+                b"250-localhost\r\n250 AUTH PLAIN\r\n"
+            } else if command == "DATA" {
+                b"354 end data with <CR><LF>.<CR><LF>\r\n"
+            } else if command == "QUIT" {
+                b"221 bye\r\n"
+            } else {
+                b"250 ok\r\n"
+            };
+            stream.get_mut().write_all(reply).await.unwrap();
+
+            if command == "DATA" {
+                loop {
+                    let mut line = String::new();
+                    stream.read_line(&mut line).await.unwrap();
+                    if line == ".\r\n" {
+                        break;
+                    }
+                    assert!(line.is_ascii(), "8 bit data sent without 8BITMIME");
+                    message.push_str(&line);
+                }
+                stream.get_mut().write_all(b"250 queued\r\n").await.unwrap();
+            }
+
+            let is_quit = command == "QUIT";
+            commands.push(command);
+            if is_quit {
+                break;
+            }
+        }
+        (commands, message)
+    }
+
+    fn format_email(notification: &Notification) -> String {
+        let message = build_email_message(&email_config(25), notification).unwrap();
+        String::from_utf8(message.formatted()).unwrap()
+    }
+
+    /// Unfolds the header with the given name, decoding it's RFC 2047 encoded words
+    fn decode_header(message: &str, header_name: &str) -> String {
+        let prefix = format!("{}: ", header_name);
+        let mut lines = message.split("\r\n");
+        let first_line = lines
+            .find_map(|line| line.strip_prefix(prefix.as_str()))
+            .expect("header missing");
+        let folded_lines = lines.map_while(|line| line.strip_prefix(' '));
+        let header_value = std::iter::once(first_line)
+            .chain(folded_lines)
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let mut text = String::new();
+        let mut previous_word_was_encoded = false;
+        for word in header_value.split(' ') {
+            let encoded_text = word
+                .get(..10)
+                .filter(|charset| charset.eq_ignore_ascii_case("=?UTF-8?B?"))
+                .and_then(|_| word[10..].strip_suffix("?="));
+            // The space between two encoded words is not part of the text
+            let is_between_encoded_words = previous_word_was_encoded && encoded_text.is_some();
+            if !text.is_empty() && !is_between_encoded_words {
+                text.push(' ');
+            }
+            match encoded_text {
+                Some(encoded_text) => {
+                    let decoded_text = base64::engine::general_purpose::STANDARD
+                        .decode(encoded_text)
+                        .unwrap();
+                    text.push_str(&String::from_utf8(decoded_text).unwrap());
+                }
+                None => text.push_str(word),
+            }
+            previous_word_was_encoded = encoded_text.is_some();
+        }
+        text
+    }
+
+    #[tokio::test]
+    async fn webhook_posts_the_notification_as_json() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let server = tokio::spawn(serve_http_request(listener, "204 No Content"));
+
+        let sink = WebhookSink {
+            url,
+            headers: vec![("X-Token".to_owned(), "secret".to_owned())],
+        };
+        sink.send(&notification()).await.unwrap();
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("POST /hook HTTP/1.1\r\n"));
+        assert!(request.to_ascii_lowercase().contains("x-token: secret\r\n"));
+
+        let (_, body) = request.split_once("\r\n\r\n").unwrap();
+        let payload: serde_json::Value = serde_json::from_str(body).unwrap();
+        let message = "Under the Dome S01E02\nThe Fire is out\n.hidden line";
+        assert_eq!(payload["text"], message);
+        assert_eq!(payload["content"], message);
+        assert_eq!(payload["releases"][0]["series_id"], 1);
+    }
+
+    #[tokio::test]
+    async fn webhook_error_status_fails_the_delivery() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(serve_http_request(listener, "500 Internal Server Error"));
+
+        let sink = WebhookSink {
+            url,
+            headers: vec![],
+        };
+        assert!(sink.send(&notification()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn email_is_submitted_over_smtp() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(serve_smtp_session(listener));
+
+        let sink = EmailSink {
+            config: email_config(port),
+        };
+        sink.send(&notification()).await.unwrap();
+
+        let (commands, message) = server.await.unwrap();
+        let credentials = base64::engine::general_purpose::STANDARD.encode("\0troxide\0secret");
+        assert_eq!(
+            commands,
+            [
+                "EHLO series-troxide".to_owned(),
+                format!("AUTH PLAIN {}", credentials),
+                "MAIL FROM:<troxide@example.com>".to_owned(),
+                "RCPT TO:<viewer@example.com>".to_owned(),
+                "DATA".to_owned(),
+                "QUIT".to_owned(),
+            ]
+        );
+        assert!(message.contains("\r\nSubject: Under the Dome S01E02\r\n"));
+        assert!(message.contains("\r\nThe Fire is out\r\n..hidden line\r\n"));
+    }
+
+    #[tokio::test]
+    async fn email_bodies_are_sent_as_7_bit_with_short_lines() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(serve_smtp_session(listener));
+
+        // A digest of many releases has long lines of non-ASCII html
+        let notification = Notification {
+            body: format!("Élite — {}", "Bienvenidos a Las Encinas ".repeat(50)),
+            html_body: Some(format!(
+                "<ul>{}</ul>",
+                "<li>Élite S01E01 — Bienvenidos</li>".repeat(50)
+            )),
+            ..notification()
+        };
+        let sink = EmailSink {
+            config: email_config(port),
+        };
+        sink.send(&notification).await.unwrap();
+
+        let (_, message) = server.await.unwrap();
+        assert!(message.split("\r\n").all(|line| line.len() <= 78));
+        assert!(!message.to_ascii_lowercase().contains("8bit"));
+    }
+
+    #[test]
+    fn non_ascii_email_subject_is_encoded() {
+        let summary = "Élite S01E01 — Bienvenidos a Las Encinas, ¿qué pasó?";
+        let notification = Notification {
+            summary: summary.to_owned(),
+            ..notification()
+        };
+
+        let message = format_email(&notification);
+        let (headers, _) = message.split_once("\r\n\r\n").unwrap();
+
+        assert!(headers.is_ascii());
+        assert!(headers.split("\r\n").all(|line| line.len() <= 78));
+        assert_eq!(decode_header(&message, "Subject"), summary);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn command_gets_the_notification_in_environment_variables() {
+        let output_dir = tempfile::tempdir().unwrap();
+        let output_path = output_dir.path().join("output");
+
+        let sink = CommandSink {
+            command: format!(
+                "printf '%s|%s|%s|%s|%s' \"$TROXIDE_SUMMARY\" \"$TROXIDE_SERIES_ID\" \
+                 \"$TROXIDE_SERIES_NAME\" \"$TROXIDE_EPISODE\" \"$TROXIDE_RELEASE_TIME\" > '{}'",
+                output_path.display()
+            ),
+        };
+        sink.send(&notification()).await.unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&output_path).unwrap(),
+            "Under the Dome S01E02|1|Under the Dome|2|2013-07-01T02:00:00+00:00"
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn failing_command_fails_the_delivery() {
+        let sink = CommandSink {
+            command: "echo broken >&2; exit 3".to_owned(),
+        };
+
+        let err = sink.send(&notification()).await.unwrap_err();
+
+        assert!(err.to_string().contains("broken"));
+    }
+}
@@ -9,7 +9,7 @@ use tokio::fs;
 use tokio::sync::RwLock;
 use tracing::{info, warn};
 
-use super::paths;
+use super::{paths, profiles};
 
 const HIDDEN_SERIES_FILENAME: &str = "hidden-series";
 
@@ -26,18 +26,33 @@ pub struct HiddenSeries {
 
 impl HiddenSeries {
     fn new() -> Self {
+        Self {
+            hidden_series: None,
+            hidden_series_filepath: Self::get_hidden_series_filepath(
+                &profiles::get_active_profile(),
+            ),
+        }
+    }
+
+    /// Every profile has it's own hidden series file in the config directory
+    fn get_hidden_series_filepath(profile_name: &str) -> path::PathBuf {
         let mut hidden_series_filepath = paths::PATHS
             .read()
             .expect("failed to read paths")
             .get_config_dir_path()
             .to_path_buf();
 
-        hidden_series_filepath.push(HIDDEN_SERIES_FILENAME);
+        hidden_series_filepath.push(profiles::get_profile_storage_name(
+            HIDDEN_SERIES_FILENAME,
+            profile_name,
+        ));
+        hidden_series_filepath
+    }
 
-        Self {
-            hidden_series: None,
-            hidden_series_filepath,
-        }
+    /// Uses the hidden series of the given profile, they are loaded on their next access
+    pub fn switch_profile(&mut self, profile_name: &str) {
+        self.hidden_series = None;
+        self.hidden_series_filepath = Self::get_hidden_series_filepath(profile_name);
     }
 
     pub async fn load_series(&mut self) -> anyhow::Result<()> {
@@ -0,0 +1,179 @@
+//! Multiple user profiles sharing one cache
+//!
+//! Every profile has it's own series in the database, it's own config section and it's own
+//! hidden series while the series information and images cache is shared between all of them.
+//!
+//! The default profile keeps using the storage that existed before profiles were introduced
+//! i.e. the default database tree, the top level config sections and the `hidden-series`
+//! file, so existing data ends up in the default profile without any conversion.
+
+use std::sync::RwLock;
+
+use lazy_static::lazy_static;
+use thiserror::Error;
+use tracing::info;
+
+use super::database::DB;
+use super::posters_hiding::HIDDEN_SERIES;
+use super::settings_config::SETTINGS;
+
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// The maximum length of a profile name, it's used in database tree and file names
+const MAX_PROFILE_NAME_LENGTH: usize = 32;
+
+lazy_static! {
+    /// A profile overriding the active profile of the config file i.e. from the command line
+    static ref PROFILE_OVERRIDE: RwLock<Option<String>> = RwLock::new(None);
+}
+
+#[derive(Debug, Error)]
+pub enum ProfileError {
+    #[error("profile name can not be empty")]
+    EmptyName,
+
+    #[error("profile name can not be longer than {MAX_PROFILE_NAME_LENGTH} characters")]
+    NameTooLong,
+
+    #[error("profile name '{0}' can only contain letters, digits, '-' and '_'")]
+    InvalidName(String),
+
+    #[error("profile '{0}' does not exist")]
+    NotFound(String),
+
+    #[error("profile '{0}' already exists")]
+    AlreadyExists(String),
+
+    #[error("failed to switch the database profile: {0}")]
+    Database(super::database::DatabaseError),
+}
+
+/// Checks that the given name can be used as a profile name
+pub fn validate_profile_name(profile_name: &str) -> Result<(), ProfileError> {
+    if profile_name.is_empty() {
+        return Err(ProfileError::EmptyName);
+    }
+    if profile_name.len() > MAX_PROFILE_NAME_LENGTH {
+        return Err(ProfileError::NameTooLong);
+    }
+    if !profile_name
+        .chars()
+        .all(|character| character.is_ascii_alphanumeric() || character == '-' || character == '_')
+    {
+        return Err(ProfileError::InvalidName(profile_name.to_owned()));
+    }
+    Ok(())
+}
+
+/// The name of the profile currently in use
+pub fn get_active_profile() -> String {
+    if let Some(profile_name) = PROFILE_OVERRIDE
+        .read()
+        .expect("failed to read profile override")
+        .as_ref()
+    {
+        return profile_name.clone();
+    }
+
+    SETTINGS
+        .read()
+        .expect("failed to read settings")
+        .get_current_settings()
+        .profiles
+        .active_profile
+        .clone()
+}
+
+/// Uses the given profile for this run of the program without saving it as the active profile
+///
+/// This has to be called before the database is first accessed. A profile that does not
+/// exist yet is only added to the config file when `create` is set, so that a mistyped
+/// profile name is an error rather than a new empty profile.
+pub fn set_profile_override(profile_name: String, create: bool) -> Result<(), ProfileError> {
+    if !profile_exists(&profile_name) {
+        if !create {
+            return Err(ProfileError::NotFound(profile_name));
+        }
+        add_profile(&profile_name)?;
+    }
+    info!("using profile '{}'", profile_name);
+    *PROFILE_OVERRIDE
+        .write()
+        .expect("failed to write profile override") = Some(profile_name);
+    Ok(())
+}
+
+/// All the profiles in the config file, the default profile always being the first one
+pub fn get_profiles() -> Vec<String> {
+    let settings = SETTINGS.read().expect("failed to read settings");
+    std::iter::once(DEFAULT_PROFILE.to_owned())
+        .chain(
+            settings
+                .get_current_settings()
+                .profiles
+                .profiles
+                .keys()
+                .filter(|profile_name| profile_name.as_str() != DEFAULT_PROFILE)
+                .cloned(),
+        )
+        .collect()
+}
+
+pub fn profile_exists(profile_name: &str) -> bool {
+    get_profiles()
+        .iter()
+        .any(|existing_profile| existing_profile == profile_name)
+}
+
+/// Adds a new profile to the config file and saves it
+pub fn add_profile(profile_name: &str) -> Result<(), ProfileError> {
+    validate_profile_name(profile_name)?;
+
+    if profile_exists(profile_name) {
+        return Err(ProfileError::AlreadyExists(profile_name.to_owned()));
+    }
+
+    SETTINGS
+        .write()
+        .expect("failed to write settings")
+        .save_with(|config| {
+            config
+                .profiles
+                .profiles
+                .insert(profile_name.to_owned(), Default::default());
+        });
+
+    info!("added profile '{}'", profile_name);
+    Ok(())
+}
+
+/// Switches every per profile storage to the given profile and saves it as the active profile
+pub async fn switch_profile(profile_name: &str) -> Result<(), ProfileError> {
+    if !profile_exists(profile_name) {
+        return Err(ProfileError::NotFound(profile_name.to_owned()));
+    }
+
+    DB.switch_profile(profile_name)
+        .map_err(ProfileError::Database)?;
+    HIDDEN_SERIES.write().await.switch_profile(profile_name);
+
+    *PROFILE_OVERRIDE
+        .write()
+        .expect("failed to write profile override") = None;
+    SETTINGS
+        .write()
+        .expect("failed to write settings")
+        .save_with(|config| config.profiles.active_profile = profile_name.to_owned());
+
+    info!("switched to profile '{}'", profile_name);
+    Ok(())
+}
+
+/// The name of a per profile file or database tree, the default profile uses the given name as is
+pub fn get_profile_storage_name(name: &str, profile_name: &str) -> String {
+    if profile_name == DEFAULT_PROFILE {
+        name.to_owned()
+    } else {
+        format!("{}-{}", name, profile_name)
+    }
+}
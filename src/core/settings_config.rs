@@ -1,14 +1,16 @@
 use std::{
+    collections::BTreeMap,
     io::ErrorKind,
     path::PathBuf,
     sync::{Arc, RwLock},
 };
 
+use chrono::{NaiveTime, Weekday};
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
 use tracing::{error, info, warn};
 
-use crate::core::paths;
+use crate::core::{paths, profiles};
 
 #[derive(Clone, Default, Debug, Serialize, Deserialize, Eq, PartialEq)]
 pub enum Theme {
@@ -36,6 +38,45 @@ pub struct Config {
     pub locale: LocaleSettings,
     pub notifications: NotificationSettings,
     pub custom_paths: Option<CustomPaths>,
+    #[serde(default)]
+    pub api: ApiSettings,
+    #[serde(default)]
+    pub profiles: ProfilesSettings,
+    #[serde(default)]
+    pub my_shows: MyShowsSettings,
+    #[serde(default)]
+    pub watchlist: WatchlistSettings,
+    #[serde(default)]
+    pub cache: CacheSettings,
+}
+
+impl Config {
+    /// The notification settings of the given profile
+    ///
+    /// The default profile and profiles without their own section use the top level ones.
+    pub fn get_notification_settings(&self, profile_name: &str) -> &NotificationSettings {
+        self.profiles
+            .profiles
+            .get(profile_name)
+            .filter(|_| profile_name != profiles::DEFAULT_PROFILE)
+            .map(|profile| &profile.notifications)
+            .unwrap_or(&self.notifications)
+    }
+
+    pub fn get_notification_settings_mut(
+        &mut self,
+        profile_name: &str,
+    ) -> &mut NotificationSettings {
+        if profile_name == profiles::DEFAULT_PROFILE {
+            return &mut self.notifications;
+        }
+        &mut self
+            .profiles
+            .profiles
+            .entry(profile_name.to_owned())
+            .or_default()
+            .notifications
+    }
 }
 
 #[derive(Clone, Default, Debug, Serialize, Deserialize, Eq, PartialEq)]
@@ -60,11 +101,119 @@ impl Default for LocaleSettings {
 pub struct NotificationSettings {
     // the time is in minutes
     pub time_to_notify: u32,
+    /// Where the release notifications are delivered to, every sink gets every notification
+    #[serde(default = "default_notification_sinks")]
+    pub sinks: Vec<NotificationSinkConfig>,
+    /// Whether to notify the releases that happened while the program was not running at startup
+    #[serde(default = "default_missed_releases_digest")]
+    pub missed_releases_digest: bool,
+    #[serde(default)]
+    pub digest: DigestSettings,
 }
 
 impl Default for NotificationSettings {
     fn default() -> Self {
-        Self { time_to_notify: 60 }
+        Self {
+            time_to_notify: 60,
+            sinks: default_notification_sinks(),
+            missed_releases_digest: default_missed_releases_digest(),
+            digest: DigestSettings::default(),
+        }
+    }
+}
+
+fn default_notification_sinks() -> Vec<NotificationSinkConfig> {
+    vec![NotificationSinkConfig::Desktop]
+}
+
+fn default_missed_releases_digest() -> bool {
+    true
+}
+
+/// A scheduled notification listing the releases of tracked series in the coming day or week
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct DigestSettings {
+    pub frequency: DigestFrequency,
+    /// The local time the digest is sent at
+    pub time: NaiveTime,
+    /// The day weekly digests are sent on
+    pub weekday: Weekday,
+    /// Whether to add an html version of the digest for the sinks supporting it
+    pub html: bool,
+}
+
+impl Default for DigestSettings {
+    fn default() -> Self {
+        Self {
+            frequency: DigestFrequency::default(),
+            time: NaiveTime::from_hms_opt(9, 0, 0).expect("valid digest time"),
+            weekday: Weekday::Mon,
+            html: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, Debug, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DigestFrequency {
+    #[default]
+    Off,
+    Daily,
+    Weekly,
+}
+
+/// A sink configured as a `[[notifications.sinks]]` table, the `type` key picks the sink
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NotificationSinkConfig {
+    /// A notification of the desktop environment
+    Desktop,
+    /// A json POST request to the url
+    Webhook {
+        url: String,
+        /// Extra headers of the request i.e. for authorization
+        #[serde(default)]
+        headers: BTreeMap<String, String>,
+    },
+    /// An email sent through an SMTP server
+    Email(EmailSinkConfig),
+    /// A shell command run with the notification in it's environment variables
+    Command { command: String },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct EmailSinkConfig {
+    pub server: String,
+    /// Defaults to the standard port of the chosen security
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub security: SmtpSecurity,
+    /// Authentication is skipped when no username is given
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+#[derive(Clone, Copy, Default, Debug, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SmtpSecurity {
+    /// TLS from the start of the connection
+    #[default]
+    Tls,
+    /// A plain connection upgraded to TLS with the STARTTLS command
+    StartTls,
+    /// No encryption at all, only meant for local relays
+    None,
+}
+
+impl SmtpSecurity {
+    pub fn default_port(&self) -> u16 {
+        match self {
+            SmtpSecurity::Tls => 465,
+            SmtpSecurity::StartTls => 587,
+            SmtpSecurity::None => 25,
+        }
     }
 }
 
@@ -74,6 +223,118 @@ pub struct CustomPaths {
     pub cache_dir: Option<PathBuf>,
 }
 
+/// Base urls overriding the default ones of the apis i.e. to point them to a mock server
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq, Default)]
+pub struct ApiSettings {
+    pub tvmaze_base_url: Option<String>,
+    pub trakt_base_url: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct ProfilesSettings {
+    /// The profile loaded at startup
+    pub active_profile: String,
+    /// The sections of the profiles other than the default one which uses the top level sections
+    pub profiles: BTreeMap<String, ProfileSettings>,
+}
+
+impl Default for ProfilesSettings {
+    fn default() -> Self {
+        Self {
+            active_profile: profiles::DEFAULT_PROFILE.to_owned(),
+            profiles: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq, Default)]
+pub struct ProfileSettings {
+    #[serde(default)]
+    pub notifications: NotificationSettings,
+}
+
+#[derive(Clone, Default, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct MyShowsSettings {
+    pub sort_order: MyShowsSortOrder,
+}
+
+#[derive(Clone, Copy, Default, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub enum MyShowsSortOrder {
+    #[default]
+    Name,
+    /// Highest personal rating first, unrated series last
+    Rating,
+}
+
+pub const ALL_MY_SHOWS_SORT_ORDERS: [MyShowsSortOrder; 2] =
+    [MyShowsSortOrder::Name, MyShowsSortOrder::Rating];
+
+impl std::fmt::Display for MyShowsSortOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str = match self {
+            MyShowsSortOrder::Name => "Name",
+            MyShowsSortOrder::Rating => "My Rating",
+        };
+
+        write!(f, "{}", str)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct WatchlistSettings {
+    /// Series with episodes left that were not watched for this long are considered abandoned
+    pub abandoned_after_weeks: u32,
+}
+
+impl Default for WatchlistSettings {
+    fn default() -> Self {
+        Self {
+            abandoned_after_weeks: 8,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct CacheSettings {
+    /// The size the cache is kept under in megabytes, 0 disables the limit
+    pub max_size_mb: u64,
+    /// Where the series data is cached, takes effect on the next start
+    #[serde(default)]
+    pub backend: CacheBackend,
+}
+
+impl Default for CacheSettings {
+    fn default() -> Self {
+        Self {
+            max_size_mb: 500,
+            backend: CacheBackend::default(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, Debug, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CacheBackend {
+    /// A json file for every piece of series data in a directory per series
+    #[default]
+    Files,
+    /// A single tree of the database holding the json of all the series
+    Database,
+}
+
+pub const ALL_CACHE_BACKENDS: [CacheBackend; 2] = [CacheBackend::Files, CacheBackend::Database];
+
+impl std::fmt::Display for CacheBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str = match self {
+            CacheBackend::Files => "Files",
+            CacheBackend::Database => "Database",
+        };
+
+        write!(f, "{}", str)
+    }
+}
+
 lazy_static! {
     pub static ref SETTINGS: Arc<RwLock<Settings>> = Arc::new(RwLock::new(Settings::new()));
 }
@@ -108,21 +369,37 @@ impl Settings {
     /// Loads the default settings
     ///
     /// # Note
-    /// Does not save the settings
+    /// Does not save the settings.
+    /// The profiles are kept as they are managed separately from the other settings.
     pub fn set_default_settings(&mut self) {
-        self.unsaved_config = Config::default();
+        self.unsaved_config = self.get_default_config();
     }
 
     /// Checks if the unsaved settings curresponds to the
     /// default settings of the program
     pub fn has_default_settings(&self) -> bool {
-        self.unsaved_config == Config::default()
+        self.unsaved_config == self.get_default_config()
+    }
+
+    fn get_default_config(&self) -> Config {
+        Config {
+            profiles: self.unsaved_config.profiles.clone(),
+            ..Config::default()
+        }
     }
 
     pub fn has_pending_save(&self) -> bool {
         self.current_config != self.unsaved_config
     }
 
+    /// Applies the given change to both the saved and the unsaved settings and saves it
+    /// right away, leaving the other unsaved changes pending
+    pub fn save_with(&mut self, change: impl Fn(&mut Config)) {
+        change(&mut self.current_config);
+        change(&mut self.unsaved_config);
+        save_config(&self.current_config);
+    }
+
     pub fn save_settings(&mut self) {
         save_config(&self.unsaved_config);
         self.current_config = self.unsaved_config.clone();
@@ -0,0 +1,109 @@
+//! The environment shared by the tests
+//!
+//! The paths, the settings, the database and the api base urls are global, so all the tests
+//! share one environment with the data, config and cache directories in a temporary directory
+//! and both apis pointed to the mock server.
+
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use lazy_static::lazy_static;
+use tokio::sync::{Mutex, MutexGuard};
+
+use super::api::mock_server::{self, MockServer, RecordedRequest, RequestLog};
+use super::api::{trakt, tv_maze};
+use super::caching::cache_store;
+use super::database::{self, DB};
+use super::paths;
+
+static ENVIRONMENT: OnceLock<TestEnvironment> = OnceLock::new();
+
+lazy_static! {
+    /// Serializes the tests changing the database as they all share it's series
+    static ref DATABASE_LOCK: Mutex<()> = Mutex::new(());
+}
+
+pub struct TestEnvironment {
+    pub root_dir: PathBuf,
+    request_log: RequestLog,
+}
+
+impl TestEnvironment {
+    fn init() -> Self {
+        let root_dir = tempfile::tempdir()
+            .expect("failed to create the test directory")
+            .into_path();
+
+        {
+            let mut paths = paths::PATHS.write().expect("failed to write to paths");
+            paths.set_data_dir_path(root_dir.join("data"));
+            paths.set_config_dir_path(root_dir.join("config"));
+            paths.set_cache_dir_path(root_dir.join("cache"));
+        }
+        database::open().expect("failed to open the test database");
+        cache_store::open();
+
+        // The mock server outlives the runtimes of the individual tests
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            tokio::runtime::Runtime::new()
+                .expect("failed to create the mock server runtime")
+                .block_on(async {
+                    let mock_server = MockServer::bind(
+                        mock_server::get_fixtures_dir(),
+                        ([127, 0, 0, 1], 0).into(),
+                    )
+                    .await
+                    .expect("failed to bind the mock server");
+                    let base_url = mock_server.base_url().expect("mock server has no address");
+                    sender
+                        .send((base_url, mock_server.get_request_log()))
+                        .expect("failed to send the mock server address");
+                    mock_server.run().await
+                })
+        });
+        let (base_url, request_log) = receiver.recv().expect("mock server failed to start");
+
+        std::env::set_var(tv_maze::BASE_URL_ENVIRONMENT_VARIABLE, &base_url);
+        std::env::set_var(trakt::BASE_URL_ENVIRONMENT_VARIABLE, &base_url);
+
+        Self {
+            root_dir,
+            request_log,
+        }
+    }
+
+    /// The requests answered by the mock server with the given method and path
+    pub fn get_requests(&self, method: &str, path: &str) -> Vec<RecordedRequest> {
+        self.request_log
+            .lock()
+            .expect("failed to lock the request log")
+            .iter()
+            .filter(|request| {
+                request.method == method && request.target.split('?').next() == Some(path)
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+/// The test environment, set up on the first call
+pub fn environment() -> &'static TestEnvironment {
+    ENVIRONMENT.get_or_init(TestEnvironment::init)
+}
+
+/// Gives the calling test the database to itself, without the series of previous tests
+pub async fn lock_database() -> MutexGuard<'static, ()> {
+    environment();
+    let database_guard = DATABASE_LOCK.lock().await;
+
+    for series_id in DB
+        .get_series_id_collection()
+        .expect("failed to read the test database")
+    {
+        DB.remove_series(series_id.parse().expect("invalid series id"))
+            .expect("failed to clear the test database");
+    }
+
+    database_guard
+}
@@ -0,0 +1,324 @@
+//! The Up Next queue, a user ordered queue of the tracked series to watch next
+//!
+//! The queue holds series rather than episodes, the episode of every series is it's next
+//! episode to watch, so marking an episode watched anywhere advances the queue. Series with
+//! episodes left to watch join at the end of the queue and the ones without leave it when
+//! the queue is synced.
+//!
+//! Pinned series always stay at the front of the queue while snoozed series are left out of
+//! it until their snooze expires.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::error;
+
+use super::api::tv_maze::{
+    episodes_information::Episode, series_information::SeriesMainInformation,
+};
+use super::caching::{episode_list::EpisodeList, series_list::SeriesList};
+use super::database::{DatabaseError, DB};
+
+const UP_NEXT_TREE_NAME: &str = "up-next";
+const QUEUE_KEY: &[u8] = b"queue";
+
+#[derive(Debug, Error)]
+pub enum UpNextError {
+    #[error("database error: {0}")]
+    Database(DatabaseError),
+
+    #[error("corrupt up next queue: {0}")]
+    Serialization(bincode::Error),
+
+    #[error("series with id '{0}' is not in the up next queue")]
+    NotQueued(u32),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct QueueEntry {
+    series_id: u32,
+    pinned: bool,
+    snoozed_until: Option<DateTime<Utc>>,
+}
+
+impl QueueEntry {
+    fn new(series_id: u32) -> Self {
+        Self {
+            series_id,
+            pinned: false,
+            snoozed_until: None,
+        }
+    }
+
+    pub fn get_series_id(&self) -> u32 {
+        self.series_id
+    }
+
+    pub fn is_pinned(&self) -> bool {
+        self.pinned
+    }
+
+    /// The end of the snooze when the series is currently snoozed
+    pub fn get_snoozed_until(&self) -> Option<DateTime<Utc>> {
+        self.snoozed_until
+            .filter(|snoozed_until| *snoozed_until > Utc::now())
+    }
+
+    pub fn is_snoozed(&self) -> bool {
+        self.get_snoozed_until().is_some()
+    }
+}
+
+/// The queue of the active profile
+///
+/// The entries are kept with the pinned ones first, the order of the entries being the
+/// order of the queue.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UpNextQueue {
+    entries: Vec<QueueEntry>,
+}
+
+impl UpNextQueue {
+    pub fn load() -> Result<Self, UpNextError> {
+        let tree = DB
+            .open_profile_tree(UP_NEXT_TREE_NAME)
+            .map_err(UpNextError::Database)?;
+        match tree
+            .get(QUEUE_KEY)
+            .map_err(|err| UpNextError::Database(DatabaseError::Sled(err)))?
+        {
+            Some(queue_bytes) => {
+                bincode::deserialize(&queue_bytes).map_err(UpNextError::Serialization)
+            }
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), UpNextError> {
+        let tree = DB
+            .open_profile_tree(UP_NEXT_TREE_NAME)
+            .map_err(UpNextError::Database)?;
+        let queue_bytes = bincode::serialize(self).map_err(UpNextError::Serialization)?;
+        tree.insert(QUEUE_KEY, queue_bytes)
+            .map_err(|err| UpNextError::Database(DatabaseError::Sled(err)))?;
+        Ok(())
+    }
+
+    /// Makes the queue contain exactly the given series, new ones joining at the end
+    ///
+    /// The entries of the series in `unknown_series_ids`, whose next episode could not be
+    /// found out, are left as they are.
+    pub fn sync(&mut self, series_ids: &[u32], unknown_series_ids: &[u32]) {
+        self.entries.retain(|entry| {
+            series_ids.contains(&entry.series_id) || unknown_series_ids.contains(&entry.series_id)
+        });
+
+        for series_id in series_ids {
+            if self.get_entry(*series_id).is_none() {
+                self.entries.push(QueueEntry::new(*series_id));
+            }
+        }
+
+        // Clearing expired snoozes
+        for entry in &mut self.entries {
+            if !entry.is_snoozed() {
+                entry.snoozed_until = None;
+            }
+        }
+    }
+
+    pub fn get_entry(&self, series_id: u32) -> Option<&QueueEntry> {
+        self.entries
+            .iter()
+            .find(|entry| entry.series_id == series_id)
+    }
+
+    /// The entries that are not snoozed in the queue's order
+    pub fn get_queued_entries(&self) -> impl Iterator<Item = &QueueEntry> {
+        self.entries.iter().filter(|entry| !entry.is_snoozed())
+    }
+
+    /// The snoozed entries, the ones waking up first being first
+    pub fn get_snoozed_entries(&self) -> Vec<&QueueEntry> {
+        let mut snoozed_entries: Vec<_> = self
+            .entries
+            .iter()
+            .filter(|entry| entry.is_snoozed())
+            .collect();
+        snoozed_entries.sort_by_key(|entry| entry.snoozed_until);
+        snoozed_entries
+    }
+
+    /// Moves the series to the given zero based position among the entries that are not
+    /// snoozed
+    ///
+    /// The position is clamped so that pinned series stay ahead of the unpinned ones.
+    pub fn move_to(&mut self, series_id: u32, position: usize) -> Result<(), UpNextError> {
+        let entry = self.take_entry(series_id)?;
+
+        let queued_positions: Vec<_> = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| !entry.is_snoozed())
+            .map(|(index, _)| index)
+            .collect();
+
+        let index = queued_positions
+            .get(position)
+            .copied()
+            .unwrap_or(self.entries.len());
+
+        let (first_index, last_index) = self.get_group_bounds(entry.pinned);
+        self.entries
+            .insert(index.clamp(first_index, last_index), entry);
+        Ok(())
+    }
+
+    /// Moves the series one place up or down the queue
+    pub fn shift(&mut self, series_id: u32, up: bool) -> Result<(), UpNextError> {
+        let position = self
+            .get_queued_entries()
+            .position(|entry| entry.series_id == series_id)
+            .ok_or(UpNextError::NotQueued(series_id))?;
+
+        let position = if up {
+            position.saturating_sub(1)
+        } else {
+            position + 1
+        };
+        self.move_to(series_id, position)
+    }
+
+    /// Pinning moves the series to the end of the pinned ones and unpinning to the start of
+    /// the unpinned ones
+    pub fn set_pinned(&mut self, series_id: u32, pinned: bool) -> Result<(), UpNextError> {
+        let mut entry = self.take_entry(series_id)?;
+        entry.pinned = pinned;
+
+        let (first_index, last_index) = self.get_group_bounds(pinned);
+        let index = if pinned { last_index } else { first_index };
+        self.entries.insert(index, entry);
+        Ok(())
+    }
+
+    pub fn snooze(&mut self, series_id: u32, days: u32) -> Result<(), UpNextError> {
+        self.get_entry_mut(series_id)?.snoozed_until =
+            Some(Utc::now() + Duration::days(days as i64));
+        Ok(())
+    }
+
+    pub fn unsnooze(&mut self, series_id: u32) -> Result<(), UpNextError> {
+        self.get_entry_mut(series_id)?.snoozed_until = None;
+        Ok(())
+    }
+
+    fn get_entry_mut(&mut self, series_id: u32) -> Result<&mut QueueEntry, UpNextError> {
+        self.entries
+            .iter_mut()
+            .find(|entry| entry.series_id == series_id)
+            .ok_or(UpNextError::NotQueued(series_id))
+    }
+
+    fn take_entry(&mut self, series_id: u32) -> Result<QueueEntry, UpNextError> {
+        let index = self
+            .entries
+            .iter()
+            .position(|entry| entry.series_id == series_id)
+            .ok_or(UpNextError::NotQueued(series_id))?;
+        Ok(self.entries.remove(index))
+    }
+
+    /// The range of indices an entry of the pinned or unpinned group can be inserted at
+    fn get_group_bounds(&self, pinned: bool) -> (usize, usize) {
+        let total_pinned = self.entries.iter().filter(|entry| entry.pinned).count();
+        if pinned {
+            (0, total_pinned)
+        } else {
+            (total_pinned, self.entries.len())
+        }
+    }
+}
+
+/// A series in the queue with it's next episode to watch
+pub struct UpNextItem {
+    pub series_info: SeriesMainInformation,
+    pub episode_list: EpisodeList,
+    pub next_episode: Episode,
+}
+
+/// Loads the queue synced with the tracked series having episodes left to watch and saves it
+///
+/// Returns the queue together with it's series, the series being in no particular order.
+/// The series whose episode list can't be loaded are logged and left out, keeping their
+/// place in the saved queue.
+pub async fn load_synced_queue() -> anyhow::Result<(UpNextQueue, Vec<UpNextItem>)> {
+    let tracked_series_information = SeriesList::new().get_tracked_series_information().await?;
+
+    let episode_list_handles: Vec<_> = tracked_series_information
+        .iter()
+        .map(|series_info| tokio::spawn(EpisodeList::new(series_info.id)))
+        .collect();
+
+    let mut items = Vec::with_capacity(tracked_series_information.len());
+    let mut failed_series_ids = vec![];
+    for (handle, series_info) in episode_list_handles
+        .into_iter()
+        .zip(tracked_series_information)
+    {
+        let episode_list = match handle.await? {
+            Ok(episode_list) => episode_list,
+            Err(err) => {
+                error!(
+                    "failed to get the episode list of series with id '{}', leaving it out of up next: {}",
+                    series_info.id, err
+                );
+                failed_series_ids.push(series_info.id);
+                continue;
+            }
+        };
+        if let Some(next_episode) = episode_list.get_next_episode_to_watch().cloned() {
+            items.push(UpNextItem {
+                series_info,
+                episode_list,
+                next_episode,
+            });
+        }
+    }
+
+    let mut queue = UpNextQueue::load()?;
+    queue.sync(
+        &items
+            .iter()
+            .map(|item| item.series_info.id)
+            .collect::<Vec<_>>(),
+        &failed_series_ids,
+    );
+    queue.save()?;
+
+    Ok((queue, items))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_series_ids(queue: &UpNextQueue) -> Vec<u32> {
+        queue
+            .entries
+            .iter()
+            .map(QueueEntry::get_series_id)
+            .collect()
+    }
+
+    #[test]
+    fn unknown_series_keep_their_place_when_syncing() {
+        let mut queue = UpNextQueue {
+            entries: [1, 2, 3].into_iter().map(QueueEntry::new).collect(),
+        };
+
+        queue.sync(&[1, 4], &[2]);
+
+        assert_eq!(get_series_ids(&queue), [1, 2, 4]);
+    }
+}
@@ -77,9 +77,12 @@ impl<'a> Application for TroxideGui<'a> {
     }
 
     fn subscription(&self) -> iced::Subscription<Message> {
-        self.tabs_controller
-            .subscription()
-            .map(Message::TabsController)
+        iced::Subscription::batch([
+            self.tabs_controller
+                .subscription()
+                .map(Message::TabsController),
+            self.title_bar.subscription().map(Message::TitleBar),
+        ])
     }
 
     fn update(&mut self, message: Message) -> Command<Message> {
@@ -129,6 +132,7 @@ impl<'a> Application for TroxideGui<'a> {
 
                         Command::batch([command, scrollers_offset_restore_command])
                     }
+                    TitleBarMessage::ConnectivityChanged(_) => Command::none(),
                 }
             }
         }
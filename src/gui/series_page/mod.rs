@@ -61,9 +61,7 @@ impl<'a> SeriesPageController<'a> {
 
     /// Tries to switch to series page if any has been received
     pub fn try_series_page_switch(&mut self) -> Command<Message> {
-        use crate::core::caching::{CacheFilePath, CACHER};
-        use tokio::fs;
-        use tracing::error;
+        use crate::core::caching::{cache_store, write_cache, CacheFilePath, CACHER};
 
         match self.series_page_receiver.try_recv() {
             Ok(series_info) => {
@@ -101,32 +99,16 @@ impl<'a> SeriesPageController<'a> {
                     .get_cache_file_path(CacheFilePath::SeriesMainInformation(series_page_id));
 
                 let cache_file_creation_future = async move {
-                    if !fs::try_exists(&series_main_info_cache_path)
+                    if !cache_store::exists(&series_main_info_cache_path)
                         .await
                         .unwrap_or(false)
                     {
-                        let mut folder_path = series_main_info_cache_path.to_owned();
-                        folder_path.pop();
-
-                        fs::create_dir_all(folder_path).await.unwrap_or_else(|err| {
-                            error!(
-                                "failed to create series cache folder for series id {}: {}",
-                                series_page_id, err
-                            )
-                        });
-
-                        fs::write(
-                            series_main_info_cache_path,
+                        write_cache(
                             serde_json::to_string_pretty(&series_info)
                                 .expect("fail to serialize series info to json"),
+                            &series_main_info_cache_path,
                         )
-                        .await
-                        .unwrap_or_else(|err| {
-                            error!(
-                                "failed to save series main information cache for series id {}: {}",
-                                series_page_id, err
-                            )
-                        });
+                        .await;
                     }
                 };
 
@@ -23,6 +23,8 @@ pub fn series_metadata<'a>(
     series_information: &'a SeriesMainInformation,
     image_bytes: Option<Bytes>,
     next_episode_release_time: Option<(&'a Episode, EpisodeReleaseTime)>,
+    review_widget: Element<'a, Message, Renderer>,
+    notifications_widget: Element<'a, Message, Renderer>,
 ) -> Element<'a, Message, Renderer> {
     let mut main_info = row!().padding(5).spacing(10);
 
@@ -74,6 +76,10 @@ pub fn series_metadata<'a>(
         horizontal_rule(1),
         series_data_grid,
         vertical_space(10),
+        text("My Review").size(18),
+        review_widget,
+        text("Notifications").size(18),
+        notifications_widget,
     ]
     .width(700)
     .spacing(5);
@@ -127,6 +133,8 @@ pub fn background(
 pub fn tracking_button(series_id: u32) -> Button<'static, Message, Renderer> {
     if database::DB
         .get_series(series_id)
+        .ok()
+        .flatten()
         .map(|series| series.is_tracked())
         .unwrap_or(false)
     {
@@ -7,8 +7,12 @@ use crate::core::api::tv_maze::series_information::SeriesMainInformation;
 use crate::core::api::tv_maze::Image;
 use crate::core::{caching, database};
 use crate::gui::styles;
+use crate::gui::troxide_widget::review_widget::{
+    Message as ReviewMessage, ReviewEditor, ReviewTarget,
+};
 use cast_widget::{CastWidget, Message as CastWidgetMessage};
 use data_widgets::*;
+use notifications_widget::{Message as NotificationsMessage, Notifications};
 use season_widget::{Message as SeasonsMessage, Seasons};
 use series_suggestion_widget::{Message as SeriesSuggestionMessage, SeriesSuggestion};
 
@@ -19,6 +23,7 @@ use iced::{Command, Element, Renderer};
 
 mod cast_widget;
 mod data_widgets;
+mod notifications_widget;
 mod season_widget;
 mod series_suggestion_widget;
 
@@ -30,6 +35,8 @@ pub enum Message {
     CastWidgetAction(CastWidgetMessage),
     SeriesSuggestion(SeriesSuggestionMessage),
     PageScrolled(Viewport),
+    Review(ReviewMessage),
+    Notifications(NotificationsMessage),
     TrackSeries,
     UntrackSeries,
 }
@@ -41,6 +48,8 @@ pub struct Series<'a> {
     series_image_blurred: Option<image::DynamicImage>,
     series_background: Option<Bytes>,
     seasons: Seasons,
+    review_editor: ReviewEditor,
+    notifications_widget: Notifications,
     casts_widget: CastWidget,
     series_suggestion_widget: SeriesSuggestion<'a>,
     scroll_offset: RelativeOffset,
@@ -64,6 +73,12 @@ impl<'a> Series<'a> {
         );
         let scroller_id = Id::new(format!("series-page-scroller-{}", series_id));
 
+        let review_editor = ReviewEditor::new(
+            series_id,
+            series_information.name.clone(),
+            ReviewTarget::Series,
+        );
+
         let series_image = series_information.image.clone();
         let series = Self {
             series_id,
@@ -72,6 +87,8 @@ impl<'a> Series<'a> {
             series_image_blurred: None,
             series_background: None,
             seasons,
+            review_editor,
+            notifications_widget: Notifications::new(series_id),
             casts_widget,
             series_suggestion_widget,
             scroll_offset: RelativeOffset::default(),
@@ -120,23 +137,23 @@ impl<'a> Series<'a> {
                 self.series_image = image;
             }
             Message::Seasons(message) => return self.seasons.update(message).map(Message::Seasons),
+            Message::Review(message) => self.review_editor.update(message),
+            Message::Notifications(message) => self.notifications_widget.update(message),
             Message::TrackSeries => {
                 let series_id = self.series_information.id;
 
-                if let Some(mut series) = database::DB.get_series(series_id) {
-                    series.mark_tracked();
-                } else {
-                    let mut series = database::Series::new(
-                        self.series_information.name.to_owned(),
-                        self.series_id,
-                    );
-                    series.mark_tracked();
-                    database::DB.add_series(self.series_information.id, &series);
+                match database::DB
+                    .get_series_or_new(self.series_id, self.series_information.name.to_owned())
+                {
+                    Ok(mut series) => series.mark_tracked(),
+                    Err(err) => {
+                        tracing::error!("failed to track series with id '{}': {}", series_id, err)
+                    }
                 }
             }
             Message::UntrackSeries => {
                 let series_id = self.series_information.id;
-                if let Some(mut series) = database::DB.get_series(series_id) {
+                if let Ok(Some(mut series)) = database::DB.get_series(series_id) {
                     series.mark_untracked();
                 }
             }
@@ -170,6 +187,8 @@ impl<'a> Series<'a> {
             &self.series_information,
             self.series_image.clone(),
             self.seasons.get_next_episode_and_release_time(),
+            self.review_editor.view().map(Message::Review),
+            self.notifications_widget.view().map(Message::Notifications),
         );
 
         let seasons_widget = self.seasons.view().map(Message::Seasons);
@@ -0,0 +1,178 @@
+use iced::widget::{button, checkbox, column, container, row, text, Row};
+use iced::{Alignment, Element, Length, Renderer};
+use iced_aw::NumberInput;
+
+use crate::core::database::{self, NotificationPreferences};
+use crate::core::notifications;
+use crate::core::profiles;
+use crate::core::settings_config::SETTINGS;
+use crate::gui::helpers::time::SaneTime;
+use crate::gui::styles;
+
+/// The lead time in minutes initially shown in the new reminder input
+const DEFAULT_NEW_REMINDER_LEAD_TIME: u32 = 60;
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    MuteToggled(bool),
+    NewReminderLeadTimeChanged(u32),
+    AddReminderPressed,
+    RemoveReminderPressed(u32),
+}
+
+/// Per series notification preferences editing
+pub struct Notifications {
+    series_id: u32,
+    preferences: NotificationPreferences,
+    new_reminder_lead_time: u32,
+}
+
+impl Notifications {
+    pub fn new(series_id: u32) -> Self {
+        let preferences = database::DB
+            .get_series(series_id)
+            .ok()
+            .flatten()
+            .map(|series| series.get_notification_preferences().clone())
+            .unwrap_or_default();
+
+        Self {
+            series_id,
+            preferences,
+            new_reminder_lead_time: DEFAULT_NEW_REMINDER_LEAD_TIME,
+        }
+    }
+
+    pub fn update(&mut self, message: Message) {
+        match message {
+            Message::MuteToggled(muted) => self.preferences.set_muted(muted),
+            Message::NewReminderLeadTimeChanged(lead_time) => {
+                self.new_reminder_lead_time = lead_time;
+                return;
+            }
+            Message::AddReminderPressed => {
+                if !self.preferences.add_reminder(self.new_reminder_lead_time) {
+                    return;
+                }
+            }
+            Message::RemoveReminderPressed(lead_time) => {
+                self.preferences.remove_reminder(lead_time)
+            }
+        }
+        self.save();
+    }
+
+    /// Saves the preferences into the series, only tracked series are notified hence
+    /// there is nothing to save into when the series is not in the database
+    fn save(&self) {
+        match database::DB.get_series(self.series_id) {
+            Ok(Some(mut series)) => {
+                series.set_notification_preferences(self.preferences.clone());
+                drop(series);
+                notifications::refresh_notifications();
+            }
+            Ok(None) => {}
+            Err(err) => tracing::error!(
+                "failed to save notification preferences of series with id '{}': {}",
+                self.series_id,
+                err
+            ),
+        }
+    }
+
+    pub fn view(&self) -> Element<'_, Message, Renderer> {
+        let is_tracked = database::DB
+            .get_series(self.series_id)
+            .ok()
+            .flatten()
+            .map(|series| series.is_tracked())
+            .unwrap_or(false);
+
+        if !is_tracked {
+            return text("Track the series to set up it's release notifications")
+                .size(11)
+                .into();
+        }
+
+        let mute_checkbox = checkbox(
+            "Mute release notifications",
+            self.preferences.is_muted(),
+            Message::MuteToggled,
+        )
+        .size(17)
+        .text_size(11);
+
+        let reminders: Element<'_, Message, Renderer> =
+            if self.preferences.get_reminder_lead_times().is_empty() {
+                let global_lead_time = SETTINGS
+                    .read()
+                    .unwrap()
+                    .get_current_settings()
+                    .get_notification_settings(&profiles::get_active_profile())
+                    .time_to_notify;
+
+                text(format!(
+                    "Reminded {} before releases as set in the settings",
+                    format_lead_time(global_lead_time)
+                ))
+                .size(11)
+                .into()
+            } else {
+                Row::with_children(
+                    self.preferences
+                        .get_reminder_lead_times()
+                        .iter()
+                        .map(|lead_time| reminder_widget(*lead_time))
+                        .collect(),
+                )
+                .spacing(5)
+                .into()
+            };
+
+        let add_reminder = row![
+            text("Remind").size(11),
+            NumberInput::new(
+                self.new_reminder_lead_time,
+                u32::MAX,
+                Message::NewReminderLeadTimeChanged
+            )
+            .width(Length::Fixed(150.0)),
+            text("minutes before releases").size(11),
+            button(text("Add reminder").size(11))
+                .on_press(Message::AddReminderPressed)
+                .style(styles::button_styles::transparent_button_with_rounded_border_theme()),
+        ]
+        .spacing(5)
+        .align_items(Alignment::Center);
+
+        let mut content = column![mute_checkbox].spacing(5);
+        if !self.preferences.is_muted() {
+            content = content.push(reminders).push(add_reminder);
+        }
+        content.into()
+    }
+}
+
+fn reminder_widget(lead_time: u32) -> Element<'static, Message, Renderer> {
+    let content = row![
+        text(format!("{} before", format_lead_time(lead_time))).size(11),
+        button(text("x").size(11))
+            .on_press(Message::RemoveReminderPressed(lead_time))
+            .style(styles::button_styles::transparent_button_theme()),
+    ]
+    .spacing(2)
+    .align_items(Alignment::Center);
+
+    container(content)
+        .padding(3)
+        .style(styles::container_styles::second_class_container_rounded_theme())
+        .into()
+}
+
+fn format_lead_time(lead_time: u32) -> String {
+    if lead_time == 0 {
+        "0 Minutes".to_owned()
+    } else {
+        SaneTime::new(lead_time).to_string().trim_end().to_owned()
+    }
+}
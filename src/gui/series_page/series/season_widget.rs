@@ -13,13 +13,15 @@ use season::{Message as SeasonMessage, Season};
 #[derive(Debug, Clone)]
 pub enum Message {
     Season(IndexedMessage<usize, SeasonMessage>),
-    EpisodeListLoaded(EpisodeList),
+    EpisodeListLoaded(Option<EpisodeList>),
 }
 
 pub struct Seasons {
     series_name: String,
     series_id: u32,
     episode_list: Option<EpisodeList>,
+    /// Whether the episode list could not be loaded i.e. when offline and not cached
+    is_episode_list_unavailable: bool,
     seasons: Vec<Season>,
 }
 
@@ -30,13 +32,21 @@ impl Seasons {
                 series_name,
                 series_id,
                 episode_list: None,
+                is_episode_list_unavailable: false,
                 seasons: vec![],
             },
             Command::perform(
                 async move {
                     EpisodeList::new(series_id)
                         .await
-                        .expect("failed to get episodes list")
+                        .map_err(|err| {
+                            tracing::error!(
+                                "failed to get episodes list for series with id '{}': {}",
+                                series_id,
+                                err
+                            )
+                        })
+                        .ok()
                 },
                 Message::EpisodeListLoaded,
             ),
@@ -56,7 +66,11 @@ impl Seasons {
             Message::Season(message) => self.seasons[message.index()]
                 .update(message)
                 .map(Message::Season),
-            Message::EpisodeListLoaded(episode_list) => {
+            Message::EpisodeListLoaded(None) => {
+                self.is_episode_list_unavailable = true;
+                Command::none()
+            }
+            Message::EpisodeListLoaded(Some(episode_list)) => {
                 let season_numbers = episode_list.get_season_numbers();
 
                 self.episode_list = Some(episode_list);
@@ -83,11 +97,28 @@ impl Seasons {
     }
 
     pub fn view(&self) -> Element<'_, Message> {
-        let seasons_body = column![text("Seasons").size(21)]
+        let mut seasons_body = column![text("Seasons").size(21)]
             .align_items(Alignment::Center)
             .spacing(10);
 
-        let content = if self.episode_list.is_none() {
+        if self
+            .episode_list
+            .as_ref()
+            .map(EpisodeList::is_stale)
+            .unwrap_or(false)
+        {
+            seasons_body = seasons_body.push(
+                text("Showing cached episodes, they may be outdated")
+                    .size(11)
+                    .style(styles::text_styles::red_text_theme()),
+            );
+        }
+
+        let content = if self.is_episode_list_unavailable {
+            container(seasons_body.push(text("Episodes are not available offline")))
+                .width(700)
+                .center_x()
+        } else if self.episode_list.is_none() {
             container(seasons_body.push(Spinner::new()))
                 .width(700)
                 .center_x()
@@ -141,7 +172,7 @@ mod season {
     #[derive(Clone, Debug)]
     pub enum Message {
         CheckboxPressed,
-        TrackCommandComplete(AddResult),
+        TrackCommandComplete(Option<AddResult>),
         Expand,
         Episode(IndexedMessage<usize, EpisodeMessage>),
     }
@@ -192,15 +223,26 @@ mod season {
 
                     return Command::perform(
                         async move {
-                            if let Some(mut series) = database::DB.get_series(series_id) {
-                                series
-                                    .add_episodes(season_number, 1..=total_episodes as u32)
-                                    .await
-                            } else {
-                                let mut series = database::Series::new(series_name, series_id);
-                                series
+                            match database::DB.get_series_or_new(series_id, series_name) {
+                                Ok(mut series) => series
                                     .add_episodes(season_number, 1..=total_episodes as u32)
                                     .await
+                                    .map_err(|err| {
+                                        tracing::error!(
+                                            "failed to mark season of series with id '{}' as watched: {}",
+                                            series_id,
+                                            err
+                                        )
+                                    })
+                                    .ok(),
+                                Err(err) => {
+                                    tracing::error!(
+                                        "failed to mark season of series with id '{}' as watched: {}",
+                                        series_id,
+                                        err
+                                    );
+                                    None
+                                }
                             }
                         },
                         Message::TrackCommandComplete,
@@ -253,8 +295,8 @@ mod season {
                         .map(move |message| IndexedMessage::new(season_index, message));
                 }
                 Message::TrackCommandComplete(add_result) => {
-                    if let AddResult::None = add_result {
-                        if let Some(mut series) = database::DB.get_series(self.series_id) {
+                    if let Some(AddResult::None) = add_result {
+                        if let Ok(Some(mut series)) = database::DB.get_series(self.series_id) {
                             series.remove_season(self.season_number);
                         }
                     }
@@ -266,6 +308,8 @@ mod season {
         pub fn view(&self) -> Element<'_, IndexedMessage<usize, Message>, Renderer> {
             let tracked_episodes = database::DB
                 .get_series(self.series_id)
+                .ok()
+                .flatten()
                 .map(|series| {
                     series
                         .get_season(self.season_number)
@@ -288,10 +288,27 @@ impl<'a> TabsController<'a> {
                     Command::none()
                 }
             }
-            Message::Settings(message) => self.settings_tab.update(message).map(Message::Settings),
+            Message::Settings(message) => {
+                let is_profile_switched = message.is_profile_switched();
+                let settings_command = self.settings_tab.update(message).map(Message::Settings);
+                if is_profile_switched {
+                    Command::batch([settings_command, self.reload_profile_tabs()])
+                } else {
+                    settings_command
+                }
+            }
         }
     }
 
+    /// Reloads the tabs showing the series of the previously active profile
+    fn reload_profile_tabs(&mut self) -> Command<Message> {
+        // The reloadable tabs are recreated from the new profile when switched to
+        self.reloadable_tab = None;
+        self.discover_tab
+            .update(DiscoverMessage::Reload)
+            .map(Message::Discover)
+    }
+
     pub fn get_labels(&self) -> [TabLabel; 5] {
         [
             DiscoverTab::tab_label(),
@@ -1,11 +1,12 @@
 use std::sync::mpsc;
 
 use crate::core::api::tv_maze::series_information::SeriesMainInformation;
+use crate::core::settings_config::{MyShowsSortOrder, ALL_MY_SHOWS_SORT_ORDERS, SETTINGS};
 use crate::gui::assets::icons::FILM;
 use crate::gui::styles;
 
 use iced::widget::scrollable::{RelativeOffset, Viewport};
-use iced::widget::{column, scrollable, text};
+use iced::widget::{column, horizontal_space, pick_list, row, scrollable, text};
 use iced::{Command, Element, Length, Renderer};
 
 use my_shows_widget::{Message as MyShowsMessage, MyShows};
@@ -22,6 +23,7 @@ pub enum Message {
     Waiting(MyShowsMessage),
     Upcoming(UpcomingReleasesMessage),
     Untracked(MyShowsMessage),
+    SortOrderSelected(MyShowsSortOrder),
     PageScrolled(Viewport),
 }
 
@@ -30,6 +32,7 @@ pub struct MyShowsTab<'a> {
     upcoming_releases: UpcomingReleases<'a>,
     ended_releases: MyShows<'a>,
     untracked_releases: MyShows<'a>,
+    sort_order: MyShowsSortOrder,
     scrollable_offset: RelativeOffset,
 }
 
@@ -38,14 +41,21 @@ impl<'a> MyShowsTab<'a> {
         series_page_sender: mpsc::Sender<SeriesMainInformation>,
         scrollable_offset: Option<RelativeOffset>,
     ) -> (Self, Command<Message>) {
+        let sort_order = SETTINGS
+            .read()
+            .unwrap()
+            .get_current_settings()
+            .my_shows
+            .sort_order;
+
         let (untracked_releases, untracked_releases_commands) =
-            MyShows::new_as_untracked_series(series_page_sender.clone());
+            MyShows::new_as_untracked_series(series_page_sender.clone(), sort_order);
         let (ended_releases, ended_releases_commands) =
-            MyShows::new_as_ended_tracked_series(series_page_sender.clone());
+            MyShows::new_as_ended_tracked_series(series_page_sender.clone(), sort_order);
         let (upcoming_releases, upcoming_releases_commands) =
             UpcomingReleases::new(series_page_sender.clone());
         let (waiting_releases, waiting_releases_commands) =
-            MyShows::new_as_waiting_release_series(series_page_sender);
+            MyShows::new_as_waiting_release_series(series_page_sender, sort_order);
 
         (
             Self {
@@ -53,6 +63,7 @@ impl<'a> MyShowsTab<'a> {
                 untracked_releases,
                 waiting_releases,
                 upcoming_releases,
+                sort_order,
                 scrollable_offset: scrollable_offset.unwrap_or(RelativeOffset::START),
             },
             Command::batch([
@@ -82,6 +93,18 @@ impl<'a> MyShowsTab<'a> {
                 .untracked_releases
                 .update(message)
                 .map(Message::Untracked),
+            Message::SortOrderSelected(sort_order) => {
+                self.sort_order = sort_order;
+                SETTINGS
+                    .write()
+                    .unwrap()
+                    .save_with(|config| config.my_shows.sort_order = sort_order);
+
+                self.waiting_releases.sort(sort_order);
+                self.ended_releases.sort(sort_order);
+                self.untracked_releases.sort(sort_order);
+                Command::none()
+            }
             Message::PageScrolled(view_port) => {
                 self.scrollable_offset = view_port.relative_offset();
                 Command::none()
@@ -90,6 +113,18 @@ impl<'a> MyShowsTab<'a> {
     }
 
     pub fn view(&self) -> Element<Message, Renderer> {
+        let sort_order_widget = row![
+            horizontal_space(Length::Fill),
+            text("Sort by"),
+            pick_list(
+                ALL_MY_SHOWS_SORT_ORDERS.as_slice(),
+                Some(self.sort_order),
+                Message::SortOrderSelected
+            ),
+        ]
+        .spacing(5)
+        .align_items(iced::Alignment::Center);
+
         let upcoming_releases = self.upcoming_releases.view().map(Message::Upcoming);
 
         let waiting_releases: Element<'_, Message, Renderer> = column![
@@ -119,6 +154,7 @@ impl<'a> MyShowsTab<'a> {
 
         scrollable(
             column![
+                sort_order_widget,
                 upcoming_releases,
                 waiting_releases,
                 ended_releases,
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::sync::mpsc;
 
 use iced::widget::{container, text};
@@ -6,6 +7,8 @@ use iced_aw::{Spinner, Wrap};
 
 use crate::core::api::tv_maze::series_information::SeriesMainInformation;
 use crate::core::caching;
+use crate::core::database::DB;
+use crate::core::settings_config::MyShowsSortOrder;
 use crate::gui::styles;
 use crate::gui::troxide_widget::series_poster::{
     IndexedMessage, Message as SeriesPosterMessage, SeriesPoster,
@@ -27,17 +30,25 @@ enum LoadState {
 pub struct MyShows<'a> {
     load_state: LoadState,
     series_posters: Vec<SeriesPoster<'a>>,
+    /// The indices of the series posters in the order they are displayed
+    ///
+    /// The posters themselves are never reordered as their messages are routed by their indices.
+    display_order: Vec<usize>,
+    sort_order: MyShowsSortOrder,
     series_page_sender: mpsc::Sender<SeriesMainInformation>,
 }
 
 impl<'a> MyShows<'a> {
     pub fn new_as_ended_tracked_series(
         series_page_sender: mpsc::Sender<SeriesMainInformation>,
+        sort_order: MyShowsSortOrder,
     ) -> (Self, Command<Message>) {
         (
             Self {
                 load_state: LoadState::default(),
                 series_posters: vec![],
+                display_order: vec![],
+                sort_order,
                 series_page_sender,
             },
             Command::perform(
@@ -53,11 +64,14 @@ impl<'a> MyShows<'a> {
 
     pub fn new_as_waiting_release_series(
         series_page_sender: mpsc::Sender<SeriesMainInformation>,
+        sort_order: MyShowsSortOrder,
     ) -> (Self, Command<Message>) {
         (
             Self {
                 load_state: LoadState::default(),
                 series_posters: vec![],
+                display_order: vec![],
+                sort_order,
                 series_page_sender,
             },
             Command::perform(
@@ -73,11 +87,14 @@ impl<'a> MyShows<'a> {
 
     pub fn new_as_untracked_series(
         series_page_sender: mpsc::Sender<SeriesMainInformation>,
+        sort_order: MyShowsSortOrder,
     ) -> (Self, Command<Message>) {
         (
             Self {
                 load_state: LoadState::default(),
                 series_posters: vec![],
+                display_order: vec![],
+                sort_order,
                 series_page_sender,
             },
             Command::perform(
@@ -95,10 +112,7 @@ impl<'a> MyShows<'a> {
         match message {
             Message::SeriesInformationReceived(series_infos) => {
                 self.load_state = LoadState::Loaded;
-                let mut series_infos = series_infos.unwrap();
-
-                // sorting the list according to name
-                series_infos.sort_by_key(|series_info| series_info.name.clone());
+                let series_infos = series_infos.unwrap();
 
                 let mut series_posters_commands = Vec::with_capacity(series_infos.len());
                 let mut series_posters = Vec::with_capacity(series_infos.len());
@@ -113,6 +127,7 @@ impl<'a> MyShows<'a> {
                     series_posters_commands.push(command);
                 }
                 self.series_posters = series_posters;
+                self.sort(self.sort_order);
                 Command::batch(series_posters_commands).map(Message::SeriesPosters)
             }
             Message::SeriesPosters(message) => self.series_posters[message.index()]
@@ -121,6 +136,48 @@ impl<'a> MyShows<'a> {
         }
     }
 
+    /// Changes the order the series posters are displayed in
+    pub fn sort(&mut self, sort_order: MyShowsSortOrder) {
+        self.sort_order = sort_order;
+
+        let series_names: Vec<&str> = self
+            .series_posters
+            .iter()
+            .map(|poster| poster.get_series_info().name.as_str())
+            .collect();
+
+        let mut display_order: Vec<usize> = (0..self.series_posters.len()).collect();
+
+        match sort_order {
+            MyShowsSortOrder::Name => {
+                display_order.sort_by_key(|index| series_names[*index]);
+            }
+            MyShowsSortOrder::Rating => {
+                let ratings: HashMap<u32, u8> = DB
+                    .get_series_collection()
+                    .unwrap_or_default()
+                    .iter()
+                    .filter_map(|series| {
+                        series
+                            .get_review()
+                            .get_rating()
+                            .map(|rating| (series.id(), rating))
+                    })
+                    .collect();
+
+                display_order.sort_by_key(|index| {
+                    let series_id = self.series_posters[*index].get_series_info().id;
+                    (
+                        std::cmp::Reverse(ratings.get(&series_id).copied()),
+                        series_names[*index],
+                    )
+                });
+            }
+        }
+
+        self.display_order = display_order;
+    }
+
     pub fn view(&self) -> Element<'_, Message, Renderer> {
         if let LoadState::Loading = self.load_state {
             return container(Spinner::new())
@@ -140,9 +197,13 @@ impl<'a> MyShows<'a> {
                 .into()
         } else {
             Wrap::with_elements(
-                self.series_posters
+                self.display_order
                     .iter()
-                    .map(|poster| poster.view(false).map(Message::SeriesPosters))
+                    .map(|index| {
+                        self.series_posters[*index]
+                            .view(false)
+                            .map(Message::SeriesPosters)
+                    })
                     .collect(),
             )
             .line_spacing(5.0)
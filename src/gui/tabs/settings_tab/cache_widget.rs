@@ -0,0 +1,178 @@
+use iced::widget::{
+    button, column, container, horizontal_space, pick_list, row, text, Column, Space,
+};
+use iced::{Command, Element, Length, Renderer};
+use iced_aw::NumberInput;
+
+use crate::core::caching::cache_usage::{self, CacheCategory, CategoryUsage};
+use crate::core::settings_config::{CacheBackend, ALL_CACHE_BACKENDS, SETTINGS};
+use crate::gui::styles;
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    UsageLoaded(Result<Vec<CategoryUsage>, String>),
+    MaxSizeChanged(u64),
+    BackendSelected(CacheBackend),
+    PurgePressed(CacheCategory),
+    PurgeComplete(Result<(), String>),
+}
+
+pub struct Cache {
+    cache_usage: Option<Result<Vec<CategoryUsage>, String>>,
+    purge_error: Option<String>,
+}
+
+impl Cache {
+    pub fn new() -> (Self, Command<Message>) {
+        (
+            Self {
+                cache_usage: None,
+                purge_error: None,
+            },
+            Self::load_usage(),
+        )
+    }
+
+    fn load_usage() -> Command<Message> {
+        Command::perform(cache_usage::get_cache_usage(), |result| {
+            Message::UsageLoaded(result.map_err(|err| err.to_string()))
+        })
+    }
+
+    pub fn update(&mut self, message: Message) -> Command<Message> {
+        match message {
+            Message::UsageLoaded(cache_usage) => {
+                self.cache_usage = Some(cache_usage);
+                Command::none()
+            }
+            Message::MaxSizeChanged(max_size_mb) => {
+                SETTINGS
+                    .write()
+                    .unwrap()
+                    .change_settings()
+                    .cache
+                    .max_size_mb = max_size_mb;
+                Command::none()
+            }
+            Message::BackendSelected(backend) => {
+                SETTINGS.write().unwrap().change_settings().cache.backend = backend;
+                Command::none()
+            }
+            Message::PurgePressed(category) => {
+                Command::perform(cache_usage::purge_cache(category), |result| {
+                    Message::PurgeComplete(result.map_err(|err| err.to_string()))
+                })
+            }
+            Message::PurgeComplete(result) => {
+                self.purge_error = result.err();
+                Self::load_usage()
+            }
+        }
+    }
+
+    pub fn view(&self) -> Element<'_, Message, Renderer> {
+        let cache_settings = SETTINGS
+            .read()
+            .unwrap()
+            .get_current_settings()
+            .cache
+            .clone();
+        let max_size_mb = cache_settings.max_size_mb;
+
+        let limit_info = column![
+            text("Cache Size Limit"),
+            text("The maximum size of the cache in megabytes, 0 disables the limit. Least recently used files are removed first and the cache of tracked series is always kept.")
+                .size(11)
+        ];
+        let limit_widget = column![
+            limit_info,
+            NumberInput::new(max_size_mb, u64::MAX, Message::MaxSizeChanged)
+                .width(Length::Fixed(200.0))
+        ]
+        .spacing(5);
+
+        let backend_widget = column![
+            text("Cache Storage"),
+            text("Where the series data is cached. The database keeps it in a single file which loads faster with many series, the change takes effect on the next start.")
+                .size(11),
+            pick_list(
+                &ALL_CACHE_BACKENDS[..],
+                Some(cache_settings.backend),
+                Message::BackendSelected
+            )
+        ]
+        .spacing(5);
+
+        let content = column![
+            text("Cache")
+                .size(21)
+                .style(styles::text_styles::accent_color_theme()),
+            limit_widget,
+            backend_widget,
+            text("Cache Usage").size(18),
+            self.usage_widget(),
+        ]
+        .spacing(5);
+
+        container(content)
+            .style(styles::container_styles::first_class_container_rounded_theme())
+            .padding(5)
+            .width(1000)
+            .into()
+    }
+
+    fn usage_widget(&self) -> Element<'_, Message, Renderer> {
+        let cache_usage = match &self.cache_usage {
+            Some(Ok(cache_usage)) => cache_usage,
+            Some(Err(err)) => {
+                return text(format!("failed to read the cache: {}", err))
+                    .style(styles::text_styles::red_text_theme())
+                    .into()
+            }
+            None => return text("Loading cache usage...").size(11).into(),
+        };
+
+        let mut usage_entries: Vec<Element<'_, Message, Renderer>> = cache_usage
+            .iter()
+            .map(|category_usage| {
+                row![
+                    text(category_usage.category.to_string()),
+                    horizontal_space(Length::Fill),
+                    text(format!(
+                        "{} in {} files",
+                        cache_usage::format_size(category_usage.size),
+                        category_usage.files
+                    ))
+                    .size(11),
+                    button(text("Purge").size(11))
+                        .style(
+                            styles::button_styles::transparent_button_with_rounded_border_theme()
+                        )
+                        .on_press(Message::PurgePressed(category_usage.category)),
+                ]
+                .spacing(10)
+                .into()
+            })
+            .collect();
+
+        let total_size: u64 = cache_usage
+            .iter()
+            .map(|category_usage| category_usage.size)
+            .sum();
+        usage_entries.push(
+            text(format!("Total: {}", cache_usage::format_size(total_size)))
+                .size(11)
+                .into(),
+        );
+
+        let purge_error: Element<'_, Message, Renderer> = match &self.purge_error {
+            Some(err) => text(err)
+                .style(styles::text_styles::red_text_theme())
+                .into(),
+            None => Space::new(0, 0).into(),
+        };
+        usage_entries.push(purge_error);
+
+        Column::with_children(usage_entries).spacing(5).into()
+    }
+}
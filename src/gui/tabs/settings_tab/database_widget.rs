@@ -14,10 +14,13 @@ mod trakt_integration;
 pub enum Message {
     ImportDatabasePressed,
     ExportDatabasePressed,
-    ImportReceived(Result<Option<TransferData>, String>),
+    ImportReceived(Result<Option<database_transfer::Import>, String>),
     ExportComplete(Result<(), String>),
+    ExportCalendarPressed,
+    ExportCalendarComplete(Result<(), String>),
     ImportTimeoutComplete,
     ExportTimeoutComplete,
+    ExportCalendarTimeoutComplete,
     ImportCachingEvent(full_caching::Event),
     TraktIntegration(trakt_integration::Message),
 }
@@ -25,6 +28,7 @@ pub enum Message {
 pub struct Database {
     import_status: Option<Result<(), String>>,
     export_status: Option<Result<(), String>>,
+    calendar_export_status: Option<Result<(), String>>,
     import_progress: (usize, usize),
     importing: bool,
     transfer_data: Option<TransferData>,
@@ -37,6 +41,7 @@ impl Database {
         Self {
             import_status: None,
             export_status: None,
+            calendar_export_status: None,
             import_progress: (0, 0),
             importing: false,
             transfer_data: None,
@@ -65,10 +70,31 @@ impl Database {
                     Message::ExportComplete(result.map_err(|err| err.to_string()))
                 })
             }
+            Message::ExportCalendarPressed => {
+                Command::perform(database_transfer::export_calendar(), |result| {
+                    Message::ExportCalendarComplete(result.map_err(|err| err.to_string()))
+                })
+            }
             // Message::ImportReceived(import_result) => todo!(),
             Message::ImportReceived(import_result) => match import_result {
-                Ok(transfer_data) => {
-                    if let Some(transfer_data) = transfer_data {
+                Ok(Some(database_transfer::Import::Report(report))) => {
+                    self.import_status = Some(if !report.unchecked_series.is_empty() {
+                        Err(format!(
+                            "skipped {} series as TVmaze could not be reached",
+                            report.unchecked_series.len()
+                        ))
+                    } else if !report.invalid_series.is_empty() {
+                        Err(format!(
+                            "skipped {} series with invalid ids",
+                            report.invalid_series.len()
+                        ))
+                    } else {
+                        Ok(())
+                    });
+                    Command::perform(status_timeout(), |_| Message::ImportTimeoutComplete)
+                }
+                Ok(import) => {
+                    if let Some(database_transfer::Import::TransferData(transfer_data)) = import {
                         let ids: Vec<u32> = transfer_data
                             .get_series()
                             .iter()
@@ -96,6 +122,10 @@ impl Database {
                 self.export_status = Some(export_result);
                 Command::perform(status_timeout(), |_| Message::ExportTimeoutComplete)
             }
+            Message::ExportCalendarComplete(export_result) => {
+                self.calendar_export_status = Some(export_result);
+                Command::perform(status_timeout(), |_| Message::ExportCalendarTimeoutComplete)
+            }
             Message::ImportTimeoutComplete => {
                 self.import_status = None;
                 Command::none()
@@ -104,6 +134,10 @@ impl Database {
                 self.export_status = None;
                 Command::none()
             }
+            Message::ExportCalendarTimeoutComplete => {
+                self.calendar_export_status = None;
+                Command::none()
+            }
             Message::ImportCachingEvent(event) => {
                 match event {
                     full_caching::Event::Ready(work_sender) => {
@@ -118,9 +152,7 @@ impl Database {
                             .as_ref()
                             .expect("there should be transfer data at this point");
 
-                        DB.import(data);
-
-                        self.import_status = Some(Ok(()));
+                        self.import_status = Some(DB.import(data).map_err(|err| err.to_string()));
                         return Command::perform(status_timeout(), |_| {
                             Message::ImportTimeoutComplete
                         });
@@ -189,10 +221,23 @@ impl Database {
             .spacing(5)
         ];
 
+        let calendar_export_widget = column![
+            text("Export Calendar"),
+            row![
+                text("Export upcoming episodes of your tracked series as an iCalendar file")
+                    .size(11),
+                horizontal_space(Length::Fill),
+                get_status_text(self.calendar_export_status.as_ref()),
+                button("Export").on_press(Message::ExportCalendarPressed)
+            ]
+            .spacing(5)
+        ];
+
         let series_troxide_data = column![
             text("Series Troxide Data").size(18),
             import_widget,
             export_widget,
+            calendar_export_widget,
         ]
         .spacing(5);
 
@@ -326,33 +371,71 @@ mod database_transfer {
     use directories::UserDirs;
     use std::path;
 
+    use crate::core::calendar_export;
     use crate::core::database::database_transfer::TransferData;
+    use crate::core::database::transfer_formats::{self, ImportReport, TransferFormat};
     use rfd::AsyncFileDialog;
 
+    /// The outcome of picking a file to import
+    #[derive(Debug, Clone)]
+    pub enum Import {
+        /// RON transfer data, imported once it's series are cached
+        TransferData(TransferData),
+        /// The report of an import done right away
+        Report(ImportReport),
+    }
+
     pub async fn export() -> anyhow::Result<()> {
         let chosen_path = AsyncFileDialog::new()
             .set_directory(get_home_directory()?)
+            .add_filter("Series Troxide Data", &["ron"])
+            .add_filter("JSON", &["json"])
+            .add_filter("CSV", &["csv"])
+            .add_filter("Markdown Report", &["md"])
             .save_file()
             .await
             .map(|file_handle| file_handle.path().to_owned());
 
         if let Some(chosen_path) = chosen_path {
-            TransferData::async_export_from_db(chosen_path).await?;
+            let format = TransferFormat::from_path(&chosen_path).unwrap_or(TransferFormat::Ron);
+            transfer_formats::export_to_file(chosen_path, format).await?;
         }
 
         Ok(())
     }
 
-    pub async fn import_transfer_data() -> anyhow::Result<Option<TransferData>> {
+    pub async fn export_calendar() -> anyhow::Result<()> {
         let chosen_path = AsyncFileDialog::new()
             .set_directory(get_home_directory()?)
+            .set_file_name("series-troxide.ics")
+            .add_filter("iCalendar", &["ics"])
+            .save_file()
+            .await
+            .map(|file_handle| file_handle.path().to_owned());
+
+        if let Some(chosen_path) = chosen_path {
+            calendar_export::export_upcoming_episodes(chosen_path).await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn import_transfer_data() -> anyhow::Result<Option<Import>> {
+        let chosen_path = AsyncFileDialog::new()
+            .set_directory(get_home_directory()?)
+            .add_filter("Series Troxide Data", &["ron", "json", "csv"])
             .pick_file()
             .await
             .map(|file_handle| file_handle.path().to_owned());
 
         if let Some(chosen_path) = chosen_path {
-            let data = TransferData::async_import(chosen_path).await?;
-            return Ok(Some(data));
+            let import = match TransferFormat::from_path(&chosen_path) {
+                Some(format @ (TransferFormat::Json | TransferFormat::Csv)) => {
+                    Import::Report(transfer_formats::import_from_file(chosen_path, format).await?)
+                }
+                _ => Import::TransferData(TransferData::async_import(chosen_path).await?),
+            };
+            return Ok(Some(import));
         }
 
         Ok(None)
@@ -829,7 +829,13 @@ impl ImportPage {
                         Ok(imports) => {
                             imports.0.into_iter().for_each(|(series_id, mut series)| {
                                 series.mark_tracked();
-                                DB.add_series(series_id, &series)
+                                DB.add_series(series_id, &series).unwrap_or_else(|err| {
+                                    tracing::error!(
+                                        "failed to add series with id '{}': {}",
+                                        series_id,
+                                        err
+                                    )
+                                })
                             });
                             self.failed_imports = imports.1;
                         }
@@ -6,24 +6,30 @@ use crate::gui::assets::icons::GEAR_WIDE_CONNECTED;
 use crate::gui::styles;
 use about_widget::{About, Message as AboutMessage};
 use appearance_widget::{Appearance, Message as AppearanceMessage};
+use cache_widget::{Cache, Message as CacheMessage};
 use database_widget::{Database, Message as DatabaseMessage};
 use discover_widget::{Discover, Message as DiscoverMessage};
 use notifications_widget::{Message as NotificationsMessage, Notifications};
+use profiles_widget::{Message as ProfilesMessage, Profiles};
 use settings_controls_widget::{Message as SettingsControlsMessage, SettingsControls};
 
 use super::Tab;
 
 mod about_widget;
 mod appearance_widget;
+mod cache_widget;
 mod database_widget;
 mod discover_widget;
 mod notifications_widget;
+mod profiles_widget;
 mod settings_controls_widget;
 
 #[derive(Debug, Clone)]
 pub enum Message {
     Appearance(AppearanceMessage),
+    Profiles(ProfilesMessage),
     Database(DatabaseMessage),
+    Cache(CacheMessage),
     Notifications(NotificationsMessage),
     Discover(DiscoverMessage),
     About(AboutMessage),
@@ -31,9 +37,21 @@ pub enum Message {
     PageScrolled(Viewport),
 }
 
+impl Message {
+    /// Whether the message reports that the active profile was switched
+    pub fn is_profile_switched(&self) -> bool {
+        matches!(
+            self,
+            Message::Profiles(ProfilesMessage::ProfileSwitched(Ok(())))
+        )
+    }
+}
+
 pub struct SettingsTab {
     appearance_settings: Appearance,
+    profiles_settings: Profiles,
     database_settings: Database,
+    cache_settings: Cache,
     notifications_settings: Notifications,
     discover_settings: Discover,
     about: About,
@@ -44,17 +62,23 @@ pub struct SettingsTab {
 impl SettingsTab {
     pub fn new() -> (Self, Command<Message>) {
         let (about_widget, about_command) = About::new();
+        let (cache_widget, cache_command) = Cache::new();
         (
             Self {
                 appearance_settings: Appearance,
+                profiles_settings: Profiles::new(),
                 database_settings: Database::new(),
+                cache_settings: cache_widget,
                 notifications_settings: Notifications,
                 discover_settings: Discover::default(),
                 about: about_widget,
                 scrollable_offset: RelativeOffset::START,
                 controls_settings: SettingsControls,
             },
-            about_command.map(Message::About),
+            Command::batch([
+                about_command.map(Message::About),
+                cache_command.map(Message::Cache),
+            ]),
         )
     }
 
@@ -70,6 +94,15 @@ impl SettingsTab {
                     .update(message)
                     .map(Message::Database)
             }
+            Message::Cache(message) => {
+                return self.cache_settings.update(message).map(Message::Cache)
+            }
+            Message::Profiles(message) => {
+                return self
+                    .profiles_settings
+                    .update(message)
+                    .map(Message::Profiles)
+            }
             Message::Discover(message) => {
                 return self
                     .discover_settings
@@ -90,7 +123,9 @@ impl SettingsTab {
         let settings_body = scrollable(
             column![
                 self.appearance_settings.view().map(Message::Appearance),
+                self.profiles_settings.view().map(Message::Profiles),
                 self.database_settings.view().map(Message::Database),
+                self.cache_settings.view().map(Message::Cache),
                 self.notifications_settings
                     .view()
                     .map(Message::Notifications),
@@ -2,6 +2,7 @@ use iced::widget::{column, container, text};
 use iced::{Element, Length, Renderer};
 use iced_aw::NumberInput;
 
+use crate::core::profiles;
 use crate::core::settings_config::SETTINGS;
 use crate::gui::styles;
 
@@ -17,21 +18,23 @@ impl Notifications {
     pub fn update(&mut self, message: Message) {
         match message {
             Message::TimeChanged(new_time) => {
+                let profile_name = profiles::get_active_profile();
                 SETTINGS
                     .write()
                     .unwrap()
                     .change_settings()
-                    .notifications
+                    .get_notification_settings_mut(&profile_name)
                     .time_to_notify = new_time;
             }
         }
     }
     pub fn view(&self) -> Element<'_, Message, Renderer> {
+        let profile_name = profiles::get_active_profile();
         let current_time_to_notify = SETTINGS
             .read()
             .unwrap()
             .get_current_settings()
-            .notifications
+            .get_notification_settings(&profile_name)
             .time_to_notify;
 
         let notifications_info = column![
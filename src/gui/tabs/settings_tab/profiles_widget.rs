@@ -0,0 +1,147 @@
+use iced::widget::{
+    button, column, container, horizontal_space, radio, row, text, text_input, Column, Space,
+};
+use iced::{Command, Element, Length, Renderer};
+
+use crate::core::profiles;
+use crate::gui::styles;
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    ProfileSelected(String),
+    ProfileSwitched(Result<(), String>),
+    NewProfileNameChanged(String),
+    AddProfilePressed,
+    StatusTimeoutComplete,
+}
+
+pub struct Profiles {
+    profiles: Vec<String>,
+    active_profile: String,
+    new_profile_name: String,
+    switching: bool,
+    status: Option<Result<(), String>>,
+}
+
+impl Profiles {
+    pub fn new() -> Self {
+        Self {
+            profiles: profiles::get_profiles(),
+            active_profile: profiles::get_active_profile(),
+            new_profile_name: String::new(),
+            switching: false,
+            status: None,
+        }
+    }
+
+    pub fn update(&mut self, message: Message) -> Command<Message> {
+        match message {
+            Message::ProfileSelected(profile_name) => {
+                if self.switching || profile_name == self.active_profile {
+                    return Command::none();
+                }
+                self.switching = true;
+                self.active_profile = profile_name.clone();
+                Command::perform(
+                    async move {
+                        profiles::switch_profile(&profile_name)
+                            .await
+                            .map_err(|err| err.to_string())
+                    },
+                    Message::ProfileSwitched,
+                )
+            }
+            Message::ProfileSwitched(result) => {
+                self.switching = false;
+                self.active_profile = profiles::get_active_profile();
+                self.status = Some(result);
+                Command::perform(status_timeout(), |_| Message::StatusTimeoutComplete)
+            }
+            Message::NewProfileNameChanged(profile_name) => {
+                self.new_profile_name = profile_name;
+                Command::none()
+            }
+            Message::AddProfilePressed => {
+                let result = profiles::add_profile(self.new_profile_name.trim())
+                    .map_err(|err| err.to_string());
+                if result.is_ok() {
+                    self.new_profile_name.clear();
+                    self.profiles = profiles::get_profiles();
+                }
+                self.status = Some(result);
+                Command::perform(status_timeout(), |_| Message::StatusTimeoutComplete)
+            }
+            Message::StatusTimeoutComplete => {
+                self.status = None;
+                Command::none()
+            }
+        }
+    }
+
+    pub fn view(&self) -> Element<'_, Message, Renderer> {
+        let profiles_info = column![
+            text("Active Profile").size(18),
+            text("Every profile has it's own tracked series, notification settings and hidden series while sharing the series cache.").size(11)
+        ];
+
+        let profile_list = Column::with_children(
+            self.profiles
+                .iter()
+                .map(|profile_name| {
+                    radio(
+                        profile_name.as_str(),
+                        profile_name,
+                        Some(&self.active_profile),
+                        |profile_name| Message::ProfileSelected(profile_name.clone()),
+                    )
+                    .into()
+                })
+                .collect(),
+        )
+        .spacing(5);
+
+        let add_profile = row![
+            text_input("New profile name", &self.new_profile_name)
+                .on_input(Message::NewProfileNameChanged)
+                .on_submit(Message::AddProfilePressed)
+                .width(200),
+            button("Add").on_press(Message::AddProfilePressed),
+            horizontal_space(Length::Fill),
+            self.status_text(),
+        ]
+        .spacing(5);
+
+        let content = column![
+            text("Profiles")
+                .size(21)
+                .style(styles::text_styles::accent_color_theme()),
+            profiles_info,
+            profile_list,
+            add_profile,
+        ]
+        .padding(5)
+        .spacing(5);
+
+        container(content)
+            .style(styles::container_styles::first_class_container_rounded_theme())
+            .width(1000)
+            .into()
+    }
+
+    fn status_text(&self) -> Element<'_, Message, Renderer> {
+        match &self.status {
+            Some(Ok(())) => text("Done!")
+                .style(styles::text_styles::green_text_theme())
+                .into(),
+            Some(Err(err)) => text(err)
+                .style(styles::text_styles::red_text_theme())
+                .into(),
+            None => Space::new(0, 0).into(),
+        }
+    }
+}
+
+/// Sleeps for 3 seconds to provide a timeout for the status text
+async fn status_timeout() {
+    tokio::time::sleep(std::time::Duration::from_secs(3)).await
+}
@@ -10,8 +10,14 @@ use super::Message;
 
 pub fn watch_count() -> Element<'static, Message, Renderer> {
     let series_total_number = database::DB.get_total_series();
-    let seasons_total_number = database::DB.get_total_seasons();
-    let episodes_total_number = database::DB.get_total_episodes();
+    let seasons_total_number = database::DB.get_total_seasons().unwrap_or_else(|err| {
+        tracing::error!("failed to get total seasons: {}", err);
+        0
+    });
+    let episodes_total_number = database::DB.get_total_episodes().unwrap_or_else(|err| {
+        tracing::error!("failed to get total episodes: {}", err);
+        0
+    });
 
     let episodes_count = column![
         text(episodes_total_number)
@@ -228,7 +234,7 @@ pub mod series_banner {
 
         pub fn view(&self) -> Element<'_, IndexedMessage<usize, Message>, Renderer> {
             let series_id = self.poster.get_series_info().id;
-            let series = database::DB.get_series(series_id).unwrap();
+            let series = database::DB.get_series(series_id).ok().flatten();
 
             let series_name = format!(
                 "{}: {}",
@@ -240,8 +246,14 @@ pub mod series_banner {
                 .map(|time| helpers::time::SaneTime::new(time).get_time_plurized())
                 .unwrap_or_default();
 
-            let seasons = series.get_total_seasons();
-            let episodes = series.get_total_episodes();
+            let seasons = series
+                .as_ref()
+                .map(|series| series.get_total_seasons())
+                .unwrap_or_default();
+            let episodes = series
+                .as_ref()
+                .map(|series| series.get_total_episodes())
+                .unwrap_or_default();
 
             let time_stats = Row::with_children(
                 times
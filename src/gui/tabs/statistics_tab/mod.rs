@@ -136,17 +136,32 @@ impl<'a> StatisticsTab<'a> {
 async fn get_series_with_runtime() -> Vec<(SeriesMainInformation, Option<u32>)> {
     let series_ids_handles: Vec<_> = database::DB
         .get_series_collection()
+        .unwrap_or_else(|err| {
+            tracing::error!("failed to get series collection: {}", err);
+            vec![]
+        })
         .into_iter()
-        .map(|series| tokio::spawn(async move { series.get_total_average_watchtime().await }))
+        .map(|series| {
+            tokio::spawn(async move {
+                let series_id = series.id();
+                (series_id, series.get_total_average_watchtime().await)
+            })
+        })
         .collect();
 
     let mut infos_and_time = Vec::with_capacity(series_ids_handles.len());
     for handle in series_ids_handles {
-        infos_and_time.push(
-            handle
-                .await
-                .expect("failed to await all series_infos and their average runtime"),
-        );
+        let (series_id, info_and_time) = handle
+            .await
+            .expect("failed to await all series_infos and their average runtime");
+        match info_and_time {
+            Ok(info_and_time) => infos_and_time.push(info_and_time),
+            Err(err) => tracing::error!(
+                "failed to get the watch time of series with id '{}', leaving it out: {}",
+                series_id,
+                err
+            ),
+        }
     }
     infos_and_time
 }
@@ -1,15 +1,14 @@
 use std::sync::mpsc;
 
 use iced::widget::scrollable::{RelativeOffset, Viewport};
-use iced::widget::{column, container, scrollable, text, Column, Space};
+use iced::widget::{column, container, pick_list, row, scrollable, text, Column, Space};
 use iced::{Command, Element, Length, Renderer};
 use iced_aw::Spinner;
 
 use super::Tab;
 use crate::core::api::tv_maze::series_information::SeriesMainInformation;
 use crate::core::caching::episode_list::EpisodeList;
-use crate::core::caching::series_list;
-use crate::core::{caching, database};
+use crate::core::up_next::{self, UpNextError, UpNextQueue};
 use crate::gui::assets::icons::CARD_CHECKLIST;
 use crate::gui::message::IndexedMessage;
 use crate::gui::styles;
@@ -18,11 +17,36 @@ use watchlist_summary::WatchlistSummary;
 
 #[derive(Debug, Clone)]
 pub enum Message {
-    SeriesInformationLoaded(Vec<(SeriesMainInformation, EpisodeList, usize)>),
+    SeriesInformationLoaded(
+        Vec<(SeriesMainInformation, EpisodeList, usize)>,
+        UpNextQueue,
+    ),
     WatchlistPoster(IndexedMessage<usize, WatchlistPosterMessage>),
+    FilterSelected(WatchlistFilter),
     PageScrolled(Viewport),
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WatchlistFilter {
+    #[default]
+    All,
+    /// Series with skipped episodes or abandoned
+    Gaps,
+}
+
+const ALL_WATCHLIST_FILTERS: [WatchlistFilter; 2] = [WatchlistFilter::All, WatchlistFilter::Gaps];
+
+impl std::fmt::Display for WatchlistFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str = match self {
+            WatchlistFilter::All => "All Series",
+            WatchlistFilter::Gaps => "Gaps and Abandoned",
+        };
+
+        write!(f, "{}", str)
+    }
+}
+
 #[derive(Default)]
 enum LoadState {
     #[default]
@@ -33,6 +57,12 @@ enum LoadState {
 pub struct WatchlistTab<'a> {
     load_state: LoadState,
     watchlist_posters: Vec<WatchlistPoster<'a>>,
+    up_next_queue: UpNextQueue,
+    /// Indices of the posters in the order of the up next queue
+    queued_posters: Vec<usize>,
+    /// Indices of the posters of the snoozed series
+    snoozed_posters: Vec<usize>,
+    filter: WatchlistFilter,
     watchlist_summary: Option<WatchlistSummary>,
     series_page_sender: mpsc::Sender<SeriesMainInformation>,
     scrollable_offset: RelativeOffset,
@@ -46,6 +76,10 @@ impl<'a> WatchlistTab<'a> {
         (
             Self {
                 watchlist_posters: vec![],
+                up_next_queue: UpNextQueue::default(),
+                queued_posters: vec![],
+                snoozed_posters: vec![],
+                filter: WatchlistFilter::default(),
                 watchlist_summary: None,
                 load_state: LoadState::Loading,
                 series_page_sender,
@@ -53,15 +87,18 @@ impl<'a> WatchlistTab<'a> {
             },
             Command::perform(
                 get_series_information_and_watched_episodes(),
-                Message::SeriesInformationLoaded,
+                |(series_infos, up_next_queue)| {
+                    Message::SeriesInformationLoaded(series_infos, up_next_queue)
+                },
             ),
         )
     }
 
     pub fn update(&mut self, message: Message) -> Command<Message> {
         match message {
-            Message::SeriesInformationLoaded(mut series_infos) => {
+            Message::SeriesInformationLoaded(series_infos, up_next_queue) => {
                 self.load_state = LoadState::Loaded;
+                self.up_next_queue = up_next_queue;
 
                 self.watchlist_summary = Some(WatchlistSummary::new(
                     series_infos
@@ -80,9 +117,6 @@ impl<'a> WatchlistTab<'a> {
                         .collect(),
                 ));
 
-                // Arranging the watchlist shows alphabetically
-                series_infos.sort_by_key(|(series_info, _, _)| series_info.name.clone());
-
                 let mut posters = Vec::with_capacity(series_infos.len());
                 let mut commands = Vec::with_capacity(series_infos.len());
                 for (index, (info, episode, total_episodes)) in series_infos.into_iter().enumerate()
@@ -99,18 +133,100 @@ impl<'a> WatchlistTab<'a> {
                 }
 
                 self.watchlist_posters = posters;
+                self.arrange_posters();
 
                 Command::batch(commands).map(Message::WatchlistPoster)
             }
-            Message::WatchlistPoster(message) => self.watchlist_posters[message.index()]
-                .update(message)
-                .map(Message::WatchlistPoster),
+            Message::WatchlistPoster(message) => {
+                let index = message.index();
+                let series_id = self.watchlist_posters[index].get_series_id();
+                match message.message() {
+                    WatchlistPosterMessage::MoveUpPressed => {
+                        self.update_queue(|queue| queue.shift(series_id, true))
+                    }
+                    WatchlistPosterMessage::MoveDownPressed => {
+                        self.update_queue(|queue| queue.shift(series_id, false))
+                    }
+                    WatchlistPosterMessage::PinToggled(pinned) => {
+                        self.update_queue(|queue| queue.set_pinned(series_id, pinned))
+                    }
+                    WatchlistPosterMessage::SnoozePressed => {
+                        let days = self.watchlist_posters[index].get_snooze_days();
+                        self.update_queue(|queue| queue.snooze(series_id, days))
+                    }
+                    WatchlistPosterMessage::UnsnoozePressed => {
+                        self.update_queue(|queue| queue.unsnooze(series_id))
+                    }
+                    message => {
+                        return self.watchlist_posters[index]
+                            .update(IndexedMessage::new(index, message))
+                            .map(Message::WatchlistPoster)
+                    }
+                }
+                Command::none()
+            }
+            Message::FilterSelected(filter) => {
+                self.filter = filter;
+                Command::none()
+            }
             Message::PageScrolled(view_port) => {
                 self.scrollable_offset = view_port.relative_offset();
                 Command::none()
             }
         }
     }
+    /// Applies the change to the up next queue, saves it and rearranges the posters
+    fn update_queue(&mut self, change: impl FnOnce(&mut UpNextQueue) -> Result<(), UpNextError>) {
+        if let Err(err) = change(&mut self.up_next_queue).and_then(|_| self.up_next_queue.save()) {
+            tracing::error!("failed to update the up next queue: {}", err);
+        }
+        self.arrange_posters();
+    }
+
+    fn arrange_posters(&mut self) {
+        let get_poster_index = |series_id: u32| {
+            self.watchlist_posters
+                .iter()
+                .position(|poster| poster.get_series_id() == series_id)
+        };
+
+        self.queued_posters = self
+            .up_next_queue
+            .get_queued_entries()
+            .filter_map(|entry| get_poster_index(entry.get_series_id()))
+            .collect();
+        self.snoozed_posters = self
+            .up_next_queue
+            .get_snoozed_entries()
+            .into_iter()
+            .filter_map(|entry| get_poster_index(entry.get_series_id()))
+            .collect();
+    }
+
+    fn posters_view(&self, poster_indices: &[usize]) -> Element<'_, Message, Renderer> {
+        Column::with_children(
+            poster_indices
+                .iter()
+                .filter(|index| match self.filter {
+                    WatchlistFilter::All => true,
+                    WatchlistFilter::Gaps => self.watchlist_posters[**index]
+                        .get_gap_analysis()
+                        .has_findings(),
+                })
+                .map(|index| {
+                    let poster = &self.watchlist_posters[*index];
+                    poster
+                        .view(self.up_next_queue.get_entry(poster.get_series_id()))
+                        .map(Message::WatchlistPoster)
+                })
+                .collect(),
+        )
+        .spacing(5)
+        .align_items(iced::Alignment::Center)
+        .width(Length::Fill)
+        .into()
+    }
+
     pub fn view(&self) -> Element<Message, Renderer> {
         match self.load_state {
             LoadState::Loading => container(Spinner::new())
@@ -131,27 +247,38 @@ impl<'a> WatchlistTab<'a> {
                     .width(Length::Fill)
                     .into()
                 } else {
-                    let watchlist_items: Vec<Element<'_, Message, Renderer>> = self
-                        .watchlist_posters
-                        .iter()
-                        .map(|poster| poster.view().map(Message::WatchlistPoster))
-                        .collect();
-
                     let watchlist_summary = self
                         .watchlist_summary
                         .as_ref()
                         .map(|watchlist_summary| watchlist_summary.view())
                         .unwrap_or(Space::new(0, 0).into());
 
-                    let watchlist_items = Column::with_children(watchlist_items)
-                        .spacing(5)
-                        .align_items(iced::Alignment::Center)
-                        .width(Length::Fill);
-
-                    let content = column![watchlist_summary, watchlist_items]
-                        .padding(5)
-                        .spacing(10)
-                        .align_items(iced::Alignment::Center);
+                    let filter = row![
+                        text("Show"),
+                        pick_list(
+                            &ALL_WATCHLIST_FILTERS[..],
+                            Some(self.filter),
+                            Message::FilterSelected
+                        ),
+                    ]
+                    .spacing(5)
+                    .align_items(iced::Alignment::Center);
+
+                    let mut content = column![
+                        watchlist_summary,
+                        filter,
+                        text("Up Next").size(21),
+                        self.posters_view(&self.queued_posters)
+                    ]
+                    .padding(5)
+                    .spacing(10)
+                    .align_items(iced::Alignment::Center);
+
+                    if !self.snoozed_posters.is_empty() {
+                        content = content
+                            .push(text("Snoozed").size(21))
+                            .push(self.posters_view(&self.snoozed_posters));
+                    }
 
                     scrollable(content)
                         .direction(styles::scrollable_styles::vertical_direction())
@@ -164,46 +291,27 @@ impl<'a> WatchlistTab<'a> {
     }
 }
 
-/// checks of the given series has pending episodes to be watched in the database. That given series
-/// is provided through it's EpisodeList Structure.
-fn has_pending_episodes(database_series: &database::Series, episodes_list: &EpisodeList) -> bool {
-    episodes_list.get_total_watchable_episodes() != database_series.get_total_episodes()
-}
-
-async fn get_series_information_and_watched_episodes(
-) -> Vec<(SeriesMainInformation, EpisodeList, usize)> {
-    let tracked_series_information = series_list::SeriesList::new()
-        .get_tracked_series_information()
+async fn get_series_information_and_watched_episodes() -> (
+    Vec<(SeriesMainInformation, EpisodeList, usize)>,
+    UpNextQueue,
+) {
+    let (up_next_queue, up_next_items) = up_next::load_synced_queue()
         .await
-        .unwrap();
-
-    let episode_lists_handles: Vec<_> = tracked_series_information
-        .iter()
-        .map(|series_info| tokio::spawn(caching::episode_list::EpisodeList::new(series_info.id)))
-        .collect();
-
-    let mut episodes_lists = Vec::with_capacity(episode_lists_handles.len());
-    for handle in episode_lists_handles {
-        let episode_list = handle
-            .await
-            .expect("failed to await episode list handle")
-            .expect("failed to get episode list");
+        .expect("failed to load the up next queue");
 
-        episodes_lists.push(episode_list);
-    }
-
-    tracked_series_information
+    let series_infos = up_next_items
         .into_iter()
-        .zip(episodes_lists.into_iter())
-        .filter(|(series_info, episode_list)| {
-            let series = database::DB.get_series(series_info.id).unwrap();
-            has_pending_episodes(&series, episode_list)
-        })
-        .map(|(series_info, episode_list)| {
-            let total_watchable_episodes = episode_list.get_total_watchable_episodes();
-            (series_info, episode_list, total_watchable_episodes)
+        .map(|up_next_item| {
+            let total_watchable_episodes = up_next_item.episode_list.get_total_watchable_episodes();
+            (
+                up_next_item.series_info,
+                up_next_item.episode_list,
+                total_watchable_episodes,
+            )
         })
-        .collect()
+        .collect();
+
+    (series_infos, up_next_queue)
 }
 
 impl<'a> Tab for WatchlistTab<'a> {
@@ -229,11 +337,15 @@ mod watchlist_poster {
         button, column, container, horizontal_rule, image, mouse_area, progress_bar, row, text,
         Space,
     };
-    use iced::{Command, Element, Length, Renderer};
+    use iced::{Alignment, Command, Element, Length, Renderer};
+    use iced_aw::NumberInput;
 
     use crate::core::api::tv_maze::series_information::SeriesMainInformation;
     use crate::core::caching::episode_list::EpisodeList;
     use crate::core::database;
+    use crate::core::gap_analysis::GapAnalysis;
+    use crate::core::settings_config::SETTINGS;
+    use crate::core::up_next::QueueEntry;
     use crate::gui::helpers::{self, season_episode_str_gen};
     use crate::gui::styles;
     use crate::gui::troxide_widget::episode_widget::{
@@ -249,16 +361,36 @@ mod watchlist_poster {
         EpisodePoster(IndexedMessage<usize, EpisodePosterMessage>),
         SeriesPosterPressed,
         ToggleEpisodeInfo,
+        MoveUpPressed,
+        MoveDownPressed,
+        PinToggled(bool),
+        SnoozeDaysChanged(u32),
+        SnoozePressed,
+        UnsnoozePressed,
+        MarkGapWatchedPressed,
+        ResumeFromGapPressed,
+        ResumeFromGapConfirmed,
+        ResumeFromGapCancelled,
     }
 
+    const DEFAULT_SNOOZE_DAYS: u32 = 7;
+    const MAX_SNOOZE_DAYS: u32 = 365;
+
     pub struct WatchlistPoster<'a> {
         index: usize,
         poster: GenericPoster<'a>,
         episode_list: EpisodeList,
         total_series_episodes: usize,
+        /// The watched episodes of the series, refreshed with the gap analysis
+        watched_episodes: usize,
         episode_poster: Option<EpisodePoster>,
         current_poster_id: usize,
         show_episode_info: bool,
+        snooze_days: u32,
+        gap_analysis: GapAnalysis,
+        /// Whether resuming from the gap waits for the user to confirm unmarking the episodes
+        /// after the gap
+        confirming_resume: bool,
     }
 
     impl<'a> WatchlistPoster<'a> {
@@ -270,6 +402,8 @@ mod watchlist_poster {
             series_page_sender: mpsc::Sender<SeriesMainInformation>,
         ) -> (Self, Command<IndexedMessage<usize, Message>>) {
             let (poster, poster_command) = GenericPoster::new(series_info, series_page_sender);
+            let (watched_episodes, gap_analysis) =
+                analyze_progress(poster.get_series_info().id, &episode_list);
 
             (
                 Self {
@@ -277,9 +411,13 @@ mod watchlist_poster {
                     poster,
                     episode_list,
                     total_series_episodes,
+                    watched_episodes,
                     episode_poster: None,
                     current_poster_id: 0,
                     show_episode_info: false,
+                    snooze_days: DEFAULT_SNOOZE_DAYS,
+                    gap_analysis,
+                    confirming_resume: false,
                 },
                 poster_command
                     .map(Message::Poster)
@@ -287,11 +425,67 @@ mod watchlist_poster {
             )
         }
 
+        pub fn get_series_id(&self) -> u32 {
+            self.poster.get_series_info().id
+        }
+
+        pub fn get_snooze_days(&self) -> u32 {
+            self.snooze_days
+        }
+
+        pub fn get_gap_analysis(&self) -> &GapAnalysis {
+            &self.gap_analysis
+        }
+
+        /// Applies the gap action to the series in the database
+        fn resolve_gaps(&mut self, action: fn(&GapAnalysis, &mut database::Series)) {
+            if let Ok(Some(mut series)) = database::DB.get_series(self.get_series_id()) {
+                action(&self.gap_analysis, &mut series);
+            }
+            // The next episode to watch changes with the gaps
+            self.episode_poster = None;
+        }
+
+        /// Handles the poster's own messages, the up next queue messages are handled by the
+        /// watchlist tab
         pub fn update(
             &mut self,
             message: IndexedMessage<usize, Message>,
         ) -> Command<IndexedMessage<usize, Message>> {
-            let command = match message.message() {
+            let message = message.message();
+            // The watch progress only changes when episodes get marked or unmarked
+            let mut is_progress_changed = matches!(
+                message,
+                Message::MarkGapWatchedPressed | Message::ResumeFromGapConfirmed
+            );
+
+            let command = match message {
+                Message::SnoozeDaysChanged(days) => {
+                    self.snooze_days = days;
+                    Command::none()
+                }
+                Message::MarkGapWatchedPressed => {
+                    self.resolve_gaps(GapAnalysis::mark_gaps_watched);
+                    self.update_episode_poster()
+                }
+                Message::ResumeFromGapPressed => {
+                    self.confirming_resume = true;
+                    Command::none()
+                }
+                Message::ResumeFromGapConfirmed => {
+                    self.confirming_resume = false;
+                    self.resolve_gaps(GapAnalysis::resume_from_gap);
+                    self.update_episode_poster()
+                }
+                Message::ResumeFromGapCancelled => {
+                    self.confirming_resume = false;
+                    Command::none()
+                }
+                Message::MoveUpPressed
+                | Message::MoveDownPressed
+                | Message::PinToggled(_)
+                | Message::SnoozePressed
+                | Message::UnsnoozePressed => Command::none(),
                 Message::Poster(message) => {
                     self.poster.update(message);
                     Command::none()
@@ -330,12 +524,18 @@ mod watchlist_poster {
                 .map(|poster| poster.is_set_watched())
                 .unwrap_or(false)
             {
+                is_progress_changed = true;
                 self.episode_poster = None;
                 self.update_episode_poster()
             } else {
                 Command::none()
             };
 
+            if is_progress_changed {
+                (self.watched_episodes, self.gap_analysis) =
+                    analyze_progress(self.get_series_id(), &self.episode_list);
+            }
+
             Command::batch([episode_update_command, command])
         }
 
@@ -358,7 +558,10 @@ mod watchlist_poster {
             }
         }
 
-        pub fn view(&self) -> Element<'_, IndexedMessage<usize, Message>, Renderer> {
+        pub fn view(
+            &self,
+            queue_entry: Option<&QueueEntry>,
+        ) -> Element<'_, IndexedMessage<usize, Message>, Renderer> {
             let mut content = row!().padding(2).spacing(5);
             if let Some(image_bytes) = self.poster.get_image() {
                 let image_handle = image::Handle::from_memory(image_bytes.clone());
@@ -376,10 +579,7 @@ mod watchlist_poster {
                     .style(styles::text_styles::accent_color_theme()),
             );
 
-            let watched_episodes = database::DB
-                .get_series(self.poster.get_series_info().id)
-                .map(|series| series.get_total_episodes())
-                .unwrap_or(0);
+            let watched_episodes = self.watched_episodes;
 
             let progress_bar = row![
                 progress_bar(
@@ -423,6 +623,14 @@ mod watchlist_poster {
 
             metadata = metadata.push(self.show_episode_info_button());
 
+            if self.gap_analysis.has_findings() {
+                metadata = metadata.push(self.gap_analysis_view());
+            }
+
+            if let Some(queue_entry) = queue_entry {
+                metadata = metadata.push(self.queue_controls(queue_entry));
+            }
+
             content = content.push(metadata);
 
             let mut content = column![content].spacing(5).width(Length::Fill);
@@ -449,6 +657,170 @@ mod watchlist_poster {
             element.map(|message| IndexedMessage::new(self.index, message))
         }
 
+        fn gap_analysis_view(&self) -> Element<'_, Message, Renderer> {
+            let mut content = column![].spacing(5);
+
+            let skipped_episodes = self.gap_analysis.get_skipped_episodes();
+            if !skipped_episodes.is_empty() {
+                let skipped_episodes_text = skipped_episodes
+                    .iter()
+                    .map(|episode_number| {
+                        season_episode_str_gen(episode_number.season, episode_number.episode)
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                content = content.push(
+                    text(format!(
+                        "Skipped {} episodes: {}",
+                        skipped_episodes.len(),
+                        skipped_episodes_text
+                    ))
+                    .size(11)
+                    .style(styles::text_styles::red_text_theme()),
+                );
+            }
+
+            for partial_season in self.gap_analysis.get_partial_seasons() {
+                content = content.push(
+                    text(format!(
+                        "Season {} partially watched, {}/{} episodes",
+                        partial_season.season,
+                        partial_season.watched_episodes,
+                        partial_season.watchable_episodes
+                    ))
+                    .size(11),
+                );
+            }
+
+            if let Some(abandoned_since) = self.gap_analysis.get_abandoned_since() {
+                content = content.push(
+                    text(format!(
+                        "Not watched since {}",
+                        abandoned_since
+                            .with_timezone(&chrono::Local)
+                            .format("%a, %d %b %Y")
+                    ))
+                    .size(11)
+                    .style(styles::text_styles::red_text_theme()),
+                );
+            }
+
+            let episodes_after_gap = self.gap_analysis.get_episodes_after_gap();
+            if let (true, Some(first_skipped_episode)) = (
+                self.confirming_resume,
+                self.gap_analysis.get_first_skipped_episode(),
+            ) {
+                let episodes_after_gap_text = episodes_after_gap
+                    .iter()
+                    .map(|episode_number| {
+                        season_episode_str_gen(episode_number.season, episode_number.episode)
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                content = content
+                    .push(
+                        text(format!(
+                            "Resuming from {} unmarks {} watched episodes of season {}: {}",
+                            season_episode_str_gen(
+                                first_skipped_episode.season,
+                                first_skipped_episode.episode
+                            ),
+                            episodes_after_gap.len(),
+                            first_skipped_episode.season,
+                            episodes_after_gap_text
+                        ))
+                        .size(11),
+                    )
+                    .push(
+                        row![
+                            button(text("Unmark And Resume").size(11))
+                                .on_press(Message::ResumeFromGapConfirmed)
+                                .style(
+                                    styles::button_styles::transparent_button_with_rounded_border_theme(
+                                    )
+                                ),
+                            button(text("Cancel").size(11))
+                                .on_press(Message::ResumeFromGapCancelled)
+                                .style(
+                                    styles::button_styles::transparent_button_with_rounded_border_theme(
+                                    )
+                                ),
+                        ]
+                        .spacing(5),
+                    );
+            } else if self.gap_analysis.has_gaps() {
+                let mut gap_controls = row![button(text("Mark Gap Watched").size(11))
+                    .on_press(Message::MarkGapWatchedPressed)
+                    .style(styles::button_styles::transparent_button_with_rounded_border_theme())]
+                .spacing(5);
+
+                // Nothing to unmark when the gap is the last watchable part of it's season
+                if !episodes_after_gap.is_empty() {
+                    gap_controls = gap_controls.push(
+                        button(text("Resume From Gap").size(11))
+                            .on_press(Message::ResumeFromGapPressed)
+                            .style(
+                                styles::button_styles::transparent_button_with_rounded_border_theme(
+                                ),
+                            ),
+                    );
+                }
+
+                content = content.push(gap_controls);
+            }
+
+            content.into()
+        }
+
+        fn queue_controls(&self, queue_entry: &QueueEntry) -> Element<'_, Message, Renderer> {
+            let control_button = |label: &'static str, message: Message| {
+                button(text(label).size(11))
+                    .on_press(message)
+                    .style(styles::button_styles::transparent_button_with_rounded_border_theme())
+            };
+
+            if let Some(snoozed_until) = queue_entry.get_snoozed_until() {
+                return row![
+                    text(format!(
+                        "Snoozed until {}",
+                        snoozed_until
+                            .with_timezone(&chrono::Local)
+                            .format("%a, %d %b %Y")
+                    ))
+                    .size(11),
+                    control_button("Unsnooze", Message::UnsnoozePressed),
+                ]
+                .spacing(5)
+                .align_items(Alignment::Center)
+                .into();
+            }
+
+            let pin_button = if queue_entry.is_pinned() {
+                control_button("Unpin", Message::PinToggled(false))
+            } else {
+                control_button("Pin", Message::PinToggled(true))
+            };
+
+            row![
+                control_button("Move Up", Message::MoveUpPressed),
+                control_button("Move Down", Message::MoveDownPressed),
+                pin_button,
+                NumberInput::new(
+                    self.snooze_days,
+                    MAX_SNOOZE_DAYS,
+                    Message::SnoozeDaysChanged
+                )
+                .min(1)
+                .width(Length::Fixed(100.0)),
+                control_button("Snooze Days", Message::SnoozePressed),
+            ]
+            .spacing(5)
+            .align_items(Alignment::Center)
+            .into()
+        }
+
         fn show_episode_info_button(&self) -> Element<'static, Message, Renderer> {
             let content = match self.show_episode_info {
                 true => "Hide Episode Info",
@@ -461,6 +833,30 @@ mod watchlist_poster {
                 .into()
         }
     }
+
+    /// The amount of watched episodes of the series together with it's gap analysis
+    fn analyze_progress(series_id: u32, episode_list: &EpisodeList) -> (usize, GapAnalysis) {
+        let abandoned_after_weeks = SETTINGS
+            .read()
+            .expect("failed to read settings")
+            .get_current_settings()
+            .watchlist
+            .abandoned_after_weeks;
+
+        database::DB
+            .get_series(series_id)
+            .ok()
+            .flatten()
+            .map(|series| {
+                let gap_analysis = GapAnalysis::new(
+                    &series,
+                    episode_list,
+                    chrono::Duration::weeks(abandoned_after_weeks as i64),
+                );
+                (series.get_total_episodes(), gap_analysis)
+            })
+            .unwrap_or_default()
+    }
 }
 
 mod watchlist_summary {
@@ -498,6 +894,8 @@ mod watchlist_summary {
                         .map(|(id, total_episodes, time)| {
                             let watched_episodes = database::DB
                                 .get_series(*id)
+                                .ok()
+                                .flatten()
                                 .map(|series| series.get_total_episodes())
                                 .unwrap_or(0);
 
@@ -514,6 +912,8 @@ mod watchlist_summary {
                 .map(|tup| {
                     database::DB
                         .get_series(tup.0)
+                        .ok()
+                        .flatten()
                         .map(|series| series.get_total_episodes())
                         .unwrap_or(0)
                 })
@@ -6,6 +6,9 @@ pub mod episode_widget {
     use crate::gui::helpers::{self, season_episode_str_gen};
     pub use crate::gui::message::IndexedMessage;
     use crate::gui::styles;
+    use crate::gui::troxide_widget::review_widget::{
+        Message as ReviewMessage, ReviewEditor, ReviewTarget,
+    };
     use bytes::Bytes;
     use iced::font::Weight;
     use iced::widget::{
@@ -18,7 +21,9 @@ pub mod episode_widget {
     pub enum Message {
         ImageLoaded(Option<Bytes>),
         MarkedWatched(PosterType),
-        TrackCommandComplete(bool),
+        TrackCommandComplete(Option<bool>),
+        ReviewPressed,
+        Review(ReviewMessage),
     }
 
     #[derive(Clone, Copy, Debug)]
@@ -35,6 +40,7 @@ pub mod episode_widget {
         series_id: u32,
         episode_image: Option<Bytes>,
         set_watched: bool,
+        review_editor: Option<ReviewEditor>,
     }
 
     impl Episode {
@@ -52,6 +58,7 @@ pub mod episode_widget {
                 series_id,
                 episode_image: None,
                 set_watched: false,
+                review_editor: None,
             };
 
             let command = if let Some(image) = episode_image {
@@ -89,23 +96,42 @@ pub mod episode_widget {
 
                     match poster_type {
                         PosterType::Watchlist => {
-                            self.set_watched = true;
-                            if let Some(mut series) = database::DB.get_series(series_id) {
-                                series.add_episode_unchecked(season_number, episode_number);
-                            } else {
-                                let mut series = database::Series::new(series_name, series_id);
-                                series.add_episode_unchecked(season_number, episode_number)
+                            match database::DB.get_series_or_new(series_id, series_name) {
+                                Ok(mut series) => {
+                                    self.set_watched = true;
+                                    series.add_episode_unchecked(season_number, episode_number);
+                                }
+                                Err(err) => tracing::error!(
+                                    "failed to mark episode of series with id '{}' as watched: {}",
+                                    series_id,
+                                    err
+                                ),
                             }
 
                             Command::none()
                         }
                         PosterType::Season => Command::perform(
                             async move {
-                                if let Some(mut series) = database::DB.get_series(series_id) {
-                                    series.add_episode(season_number, episode_number).await
-                                } else {
-                                    let mut series = database::Series::new(series_name, series_id);
-                                    series.add_episode(season_number, episode_number).await
+                                match database::DB.get_series_or_new(series_id, series_name) {
+                                    Ok(mut series) => series
+                                        .add_episode(season_number, episode_number)
+                                        .await
+                                        .map_err(|err| {
+                                            tracing::error!(
+                                                "failed to mark episode of series with id '{}' as watched: {}",
+                                                series_id,
+                                                err
+                                            )
+                                        })
+                                        .ok(),
+                                    Err(err) => {
+                                        tracing::error!(
+                                            "failed to mark episode of series with id '{}' as watched: {}",
+                                            series_id,
+                                            err
+                                        );
+                                        None
+                                    }
                                 }
                             },
                             Message::TrackCommandComplete,
@@ -114,8 +140,8 @@ pub mod episode_widget {
                     }
                 }
                 Message::TrackCommandComplete(is_newly_added) => {
-                    if !is_newly_added {
-                        if let Some(mut series) = database::DB.get_series(self.series_id) {
+                    if is_newly_added == Some(false) {
+                        if let Ok(Some(mut series)) = database::DB.get_series(self.series_id) {
                             series.remove_episode(
                                 self.episode_information.season,
                                 self.episode_information.number.unwrap(),
@@ -124,6 +150,28 @@ pub mod episode_widget {
                     }
                     Command::none()
                 }
+                Message::ReviewPressed => {
+                    self.review_editor = match self.review_editor {
+                        Some(_) => None,
+                        None => self.episode_information.number.map(|episode_number| {
+                            ReviewEditor::new(
+                                self.series_id,
+                                self.series_name.clone(),
+                                ReviewTarget::Episode {
+                                    season_number: self.episode_information.season,
+                                    episode_number,
+                                },
+                            )
+                        }),
+                    };
+                    Command::none()
+                }
+                Message::Review(message) => {
+                    if let Some(review_editor) = self.review_editor.as_mut() {
+                        review_editor.update(message);
+                    }
+                    Command::none()
+                }
             }
         }
 
@@ -150,13 +198,17 @@ pub mod episode_widget {
                 );
             };
 
-            let episode_details = column!(
+            let mut episode_details = column!(
                 heading_widget(self.series_id, &self.episode_information, poster_type),
                 date_time_widget(&self.episode_information),
                 vertical_space(5),
                 summary_widget(&self.episode_information)
             );
 
+            if let PosterType::Season = poster_type {
+                episode_details = episode_details.push(self.review_widget());
+            }
+
             let content = content.push(episode_details);
 
             let mut content = container(content);
@@ -172,6 +224,29 @@ pub mod episode_widget {
         }
     }
 
+    impl Episode {
+        fn review_widget(&self) -> Element<'_, Message, Renderer> {
+            if self.episode_information.number.is_none() {
+                return Space::new(0, 0).into();
+            }
+
+            let button_text = if self.review_editor.is_some() {
+                "Hide review"
+            } else {
+                "Rate / add notes"
+            };
+            let review_button = button(text(button_text).size(11))
+                .on_press(Message::ReviewPressed)
+                .style(styles::button_styles::transparent_button_with_rounded_border_theme());
+
+            let mut content = column![review_button].spacing(5);
+            if let Some(review_editor) = &self.review_editor {
+                content = content.push(review_editor.view().map(Message::Review));
+            }
+            content.into()
+        }
+    }
+
     fn summary_widget(episode_information: &EpisodeInfo) -> Text<'static, Renderer> {
         if let Some(summary) = &episode_information.summary {
             let summary = html2text::from_read(summary.as_bytes(), 1000);
@@ -213,6 +288,8 @@ pub mod episode_widget {
             PosterType::Season => {
                 let is_tracked = database::DB
                     .get_series(series_id)
+                    .ok()
+                    .flatten()
                     .map(|series| {
                         if let Some(season) = series.get_season(episode_information.season) {
                             season.is_episode_watched(episode_information.number.unwrap())
@@ -249,6 +326,154 @@ pub mod episode_widget {
     }
 }
 
+pub mod review_widget {
+    //! Editing of personal ratings and notes of series and episodes
+
+    use iced::widget::{button, pick_list, row, text, text_input};
+    use iced::{Element, Length, Renderer};
+
+    use crate::core::database::{self, Episode, Review, RATING_RANGE};
+    use crate::gui::styles;
+
+    #[derive(Clone, Debug)]
+    pub enum Message {
+        RatingSelected(RatingOption),
+        NotesChanged(String),
+        NotesSaved,
+    }
+
+    /// A rating choice of the rating pick list
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct RatingOption(Option<u8>);
+
+    impl std::fmt::Display for RatingOption {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self.0 {
+                Some(rating) => write!(f, "{}/{}", rating, RATING_RANGE.end()),
+                None => write!(f, "No rating"),
+            }
+        }
+    }
+
+    /// What the review belongs to
+    #[derive(Clone, Copy, Debug)]
+    pub enum ReviewTarget {
+        Series,
+        Episode {
+            season_number: u32,
+            episode_number: Episode,
+        },
+    }
+
+    #[derive(Clone)]
+    pub struct ReviewEditor {
+        series_id: u32,
+        series_name: String,
+        target: ReviewTarget,
+        review: Review,
+        /// The notes being edited, they are only saved on submission
+        notes: String,
+        rating_options: Vec<RatingOption>,
+    }
+
+    impl ReviewEditor {
+        pub fn new(series_id: u32, series_name: String, target: ReviewTarget) -> Self {
+            let review = database::DB
+                .get_series(series_id)
+                .ok()
+                .flatten()
+                .and_then(|series| match target {
+                    ReviewTarget::Series => Some(series.get_review().clone()),
+                    ReviewTarget::Episode {
+                        season_number,
+                        episode_number,
+                    } => series
+                        .get_episode_review(season_number, episode_number)
+                        .cloned(),
+                })
+                .unwrap_or_default();
+
+            let rating_options = std::iter::once(RatingOption(None))
+                .chain(RATING_RANGE.map(|rating| RatingOption(Some(rating))))
+                .collect();
+
+            Self {
+                series_id,
+                series_name,
+                target,
+                notes: review.get_notes().to_owned(),
+                review,
+                rating_options,
+            }
+        }
+
+        pub fn update(&mut self, message: Message) {
+            match message {
+                Message::RatingSelected(RatingOption(rating)) => {
+                    self.review.set_rating(rating);
+                    self.save();
+                }
+                Message::NotesChanged(notes) => self.notes = notes,
+                Message::NotesSaved => {
+                    self.review.set_notes(self.notes.clone());
+                    self.notes = self.review.get_notes().to_owned();
+                    self.save();
+                }
+            }
+        }
+
+        fn save(&self) {
+            let mut series =
+                match database::DB.get_series_or_new(self.series_id, self.series_name.clone()) {
+                    Ok(series) => series,
+                    Err(err) => {
+                        tracing::error!(
+                            "failed to save review of series with id '{}': {}",
+                            self.series_id,
+                            err
+                        );
+                        return;
+                    }
+                };
+
+            match self.target {
+                ReviewTarget::Series => series.set_review(self.review.clone()),
+                ReviewTarget::Episode {
+                    season_number,
+                    episode_number,
+                } => series.set_episode_review(season_number, episode_number, self.review.clone()),
+            }
+        }
+
+        pub fn view(&self) -> Element<'_, Message, Renderer> {
+            let rating_pick_list = pick_list(
+                self.rating_options.as_slice(),
+                Some(RatingOption(self.review.get_rating())),
+                Message::RatingSelected,
+            )
+            .text_size(11)
+            .width(110);
+
+            let notes_input = text_input("Personal notes", &self.notes)
+                .on_input(Message::NotesChanged)
+                .on_submit(Message::NotesSaved)
+                .size(11)
+                .width(Length::Fill);
+
+            let mut save_button = button(text("Save notes").size(11))
+                .style(styles::button_styles::transparent_button_with_rounded_border_theme());
+            if self.notes.trim() != self.review.get_notes() {
+                save_button = save_button.on_press(Message::NotesSaved);
+            }
+
+            row![rating_pick_list, notes_input, save_button]
+                .spacing(5)
+                .align_items(iced::Alignment::Center)
+                .into()
+        }
+    }
+}
+
 pub mod series_poster {
     use std::borrow::Cow;
     use std::sync::mpsc;
@@ -413,6 +638,10 @@ pub mod series_poster {
             self.hidden
         }
 
+        pub fn get_series_info(&self) -> &SeriesMainInformation {
+            self.poster.get_series_info()
+        }
+
         pub fn view(
             &self,
             expandable: bool,
@@ -533,8 +762,9 @@ pub mod title_bar {
     use iced::widget::{
         button, container, horizontal_space, mouse_area, row, svg, text, Row, Space,
     };
-    use iced::{Element, Length, Renderer};
+    use iced::{subscription, Element, Length, Renderer, Subscription};
 
+    use crate::core::caching::offline::{self, ConnectivityStatus};
     use crate::gui::assets::icons::CARET_LEFT_FILL;
     use crate::gui::styles;
     use crate::gui::tabs::TabLabel;
@@ -543,22 +773,46 @@ pub mod title_bar {
     pub enum Message {
         TabSelected(usize),
         BackButtonPressed,
+        ConnectivityChanged(ConnectivityStatus),
     }
 
     pub struct TitleBar {
         active_tab: usize,
+        connectivity: ConnectivityStatus,
     }
 
     impl TitleBar {
         pub fn new() -> Self {
             Self {
                 active_tab: usize::default(),
+                connectivity: ConnectivityStatus::default(),
             }
         }
 
+        pub fn subscription(&self) -> Subscription<Message> {
+            subscription::channel("connectivity-status", 10, |mut output| async move {
+                use iced::futures::SinkExt;
+
+                let mut receiver = offline::subscribe();
+                loop {
+                    let status = *receiver.borrow_and_update();
+                    output
+                        .send(Message::ConnectivityChanged(status))
+                        .await
+                        .expect("failed to send connectivity status");
+
+                    if receiver.changed().await.is_err() {
+                        std::future::pending::<()>().await;
+                    }
+                }
+            })
+        }
+
         pub fn update(&mut self, message: Message) {
-            if let Message::TabSelected(new_active_tab) = message {
-                self.active_tab = new_active_tab
+            match message {
+                Message::TabSelected(new_active_tab) => self.active_tab = new_active_tab,
+                Message::ConnectivityChanged(status) => self.connectivity = status,
+                Message::BackButtonPressed => {}
             }
         }
 
@@ -606,11 +860,31 @@ pub mod title_bar {
                 Space::new(0, 0).into()
             };
 
+            let offline_banner: Element<'_, Message, Renderer> = if self.connectivity.is_offline {
+                let mut banner_text = "Offline, showing cached data".to_owned();
+                if self.connectivity.pending_validations > 0 {
+                    banner_text.push_str(&format!(
+                        " ({} watched episodes waiting)",
+                        self.connectivity.pending_validations
+                    ));
+                }
+                container(
+                    text(banner_text)
+                        .size(13)
+                        .style(styles::text_styles::red_text_theme()),
+                )
+                .padding(5)
+                .into()
+            } else {
+                Space::new(0, 0).into()
+            };
+
             container(row![
                 back_button,
                 horizontal_space(Length::Fill),
                 tab_views,
-                horizontal_space(Length::Fill)
+                horizontal_space(Length::Fill),
+                offline_banner
             ])
             .style(styles::container_styles::first_class_container_square_theme())
             .into()
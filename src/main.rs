@@ -11,6 +11,11 @@ fn main() -> anyhow::Result<()> {
 
     core::cli::cli_handler::handle_cli()?;
 
+    let _instance_lock =
+        core::instance_lock::InstanceLock::acquire(core::instance_lock::InstanceKind::Gui)?;
+    core::database::open()?;
+    core::caching::cache_store::open();
+
     std::thread::spawn(|| {
         if let Err(err) = tokio::runtime::Runtime::new()
             .expect("failed to create tokio runtime")